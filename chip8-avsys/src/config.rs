@@ -2,4 +2,7 @@ pub const CHIP8_WIDTH: u32 = 64;
 pub const CHIP8_HEIGHT: u32 = 32;
 pub const CHIP8_WINDOW_MULTIPLIER: u32 = 20;
 
-pub const EMULATOR_WINDOW_TITLE: &'static str = "Chip-8 Emulator";
+pub const EMULATOR_WINDOW_TITLE: &str = "Chip-8 Emulator";
+
+/// Smallest window pixels per CHIP-8 pixel with the grid lines, which would hide smaller pixels.
+pub const GRID_MIN_PIXEL_SIZE: u32 = 4;
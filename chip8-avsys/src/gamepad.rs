@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use chip8_vm::ChipKey;
+use sdl2::controller::{Axis, Button};
+
+/// Stick deflection, out of 32767, past which the stick presses a D-pad direction.
+const STICK_THRESHOLD: i16 = 16_000;
+
+/// Built-in mappings of the ROMs whose controls don't fit the default one, by ROM name: the
+/// buttons that differ from the default mapping and their CHIP-8 key values.
+const PRESETS: [(&str, &[(Button, u8)]); 6] = [
+    ("PONG", &[(Button::DPadUp, 0x1), (Button::DPadDown, 0x4)]),
+    ("PONG2", &[(Button::DPadUp, 0x1), (Button::DPadDown, 0x4)]),
+    ("TANK", &[(Button::B, 0x5)]),
+    ("UFO", &[(Button::DPadUp, 0x5), (Button::B, 0x5)]),
+    ("INVADERS", &[(Button::DPadUp, 0x5), (Button::B, 0x5)]),
+    (
+        "TETRIS",
+        &[
+            (Button::DPadUp, 0x4),
+            (Button::DPadDown, 0x7),
+            (Button::DPadLeft, 0x5),
+            (Button::DPadRight, 0x6),
+            (Button::A, 0x4),
+            (Button::B, 0x7),
+        ],
+    ),
+];
+
+/// Mapping of the game controller buttons to the CHIP-8 keys. The left stick acts as the D-pad.
+#[derive(Clone, Debug)]
+pub struct ButtonMap {
+    buttons: HashMap<Button, ChipKey>,
+}
+
+impl Default for ButtonMap {
+    /// The D-pad mapped to the 2/8/4/6 arrows of the keypad, A to 5, B to 0, X to A, Y to B,
+    /// Back to C and Start to F.
+    fn default() -> Self {
+        let buttons = [
+            (Button::DPadUp, ChipKey::Key2),
+            (Button::DPadDown, ChipKey::Key8),
+            (Button::DPadLeft, ChipKey::Key4),
+            (Button::DPadRight, ChipKey::Key6),
+            (Button::A, ChipKey::Key5),
+            (Button::B, ChipKey::Key0),
+            (Button::X, ChipKey::KeyA),
+            (Button::Y, ChipKey::KeyB),
+            (Button::Back, ChipKey::KeyC),
+            (Button::Start, ChipKey::KeyF),
+        ];
+        ButtonMap {
+            buttons: buttons.into_iter().collect(),
+        }
+    }
+}
+
+impl ButtonMap {
+    /// Names of the built-in presets.
+    pub fn preset_names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Built-in mapping of a ROM, the default mapping with some buttons bound to the game
+    /// controls. The name is not case sensitive.
+    pub fn preset(name: &str) -> Option<ButtonMap> {
+        let (_, buttons) = PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))?;
+        let mut map = ButtonMap::default();
+        for (button, value) in buttons.iter() {
+            map.buttons.insert(*button, ChipKey::from_value(*value)?);
+        }
+        Some(map)
+    }
+
+    /// Bind a CHIP-8 key to the buttons with the given SDL names (e.g. `a`, `dpup` or
+    /// `leftshoulder`), replacing its previous bindings.
+    pub fn bind(&mut self, key: ChipKey, buttons: &[String]) -> Result<(), String> {
+        let buttons = buttons
+            .iter()
+            .map(|name| {
+                Button::from_string(name)
+                    .ok_or_else(|| format!("unknown controller button '{}'", name))
+            })
+            .collect::<Result<Vec<Button>, String>>()?;
+        self.buttons.retain(|_, chip_key| *chip_key != key);
+        for button in buttons {
+            self.buttons.insert(button, key);
+        }
+        Ok(())
+    }
+
+    /// CHIP-8 key of a button.
+    pub(crate) fn chip_key(&self, button: Button) -> Option<ChipKey> {
+        self.buttons.get(&button).copied()
+    }
+}
+
+/// Directions of the D-pad pressed with the left stick of the game controllers.
+#[derive(Default)]
+pub(crate) struct Stick {
+    pressed: HashSet<Button>,
+}
+
+impl Stick {
+    /// CHIP-8 keys pressed or released by a stick motion.
+    pub(crate) fn motion(
+        &mut self,
+        map: &ButtonMap,
+        axis: Axis,
+        value: i16,
+    ) -> Vec<(ChipKey, bool)> {
+        let (negative, positive) = match axis {
+            Axis::LeftX => (Button::DPadLeft, Button::DPadRight),
+            Axis::LeftY => (Button::DPadUp, Button::DPadDown),
+            _ => return Vec::new(),
+        };
+        let mut keys = Vec::new();
+        for (button, pressed) in [
+            (negative, value < -STICK_THRESHOLD),
+            (positive, value > STICK_THRESHOLD),
+        ] {
+            let changed = if pressed {
+                self.pressed.insert(button)
+            } else {
+                self.pressed.remove(&button)
+            };
+            if let Some(key) = map.chip_key(button).filter(|_| changed) {
+                keys.push((key, pressed));
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_vm::ChipKey;
+    use sdl2::controller::{Axis, Button};
+
+    use super::{ButtonMap, Stick};
+
+    #[test]
+    fn bind_buttons() {
+        let mut map = ButtonMap::default();
+        map.bind(
+            ChipKey::Key5,
+            &["leftshoulder".to_string(), "dpup".to_string()],
+        )
+        .unwrap();
+        assert_eq!(map.chip_key(Button::LeftShoulder), Some(ChipKey::Key5));
+        assert_eq!(map.chip_key(Button::DPadUp), Some(ChipKey::Key5));
+        // The previous binding of the key is replaced.
+        assert_eq!(map.chip_key(Button::A), None);
+        assert_eq!(map.chip_key(Button::B), Some(ChipKey::Key0));
+
+        assert_eq!(
+            map.bind(ChipKey::Key1, &["x".to_string(), "trigger".to_string()]),
+            Err("unknown controller button 'trigger'".to_string())
+        );
+        assert_eq!(map.chip_key(Button::X), Some(ChipKey::KeyA));
+    }
+
+    #[test]
+    fn presets() {
+        let default = ButtonMap::default();
+        assert_eq!(default.chip_key(Button::DPadUp), Some(ChipKey::Key2));
+        assert_eq!(default.chip_key(Button::DPadRight), Some(ChipKey::Key6));
+        assert_eq!(default.chip_key(Button::LeftShoulder), None);
+
+        let tank = ButtonMap::preset("tank").unwrap();
+        assert_eq!(tank.chip_key(Button::B), Some(ChipKey::Key5));
+        assert_eq!(tank.chip_key(Button::A), Some(ChipKey::Key5));
+        assert_eq!(tank.chip_key(Button::DPadUp), Some(ChipKey::Key2));
+
+        let ufo = ButtonMap::preset("UFO").unwrap();
+        assert_eq!(ufo.chip_key(Button::DPadUp), Some(ChipKey::Key5));
+        assert_eq!(ufo.chip_key(Button::DPadLeft), Some(ChipKey::Key4));
+        assert_eq!(ufo.chip_key(Button::B), Some(ChipKey::Key5));
+
+        let pong = ButtonMap::preset("Pong").unwrap();
+        assert_eq!(pong.chip_key(Button::DPadUp), Some(ChipKey::Key1));
+        assert_eq!(pong.chip_key(Button::DPadDown), Some(ChipKey::Key4));
+        assert_eq!(pong.chip_key(Button::A), Some(ChipKey::Key5));
+
+        assert!(ButtonMap::preset("BRIX").is_none());
+    }
+
+    #[test]
+    fn stick_presses_dpad() {
+        let map = ButtonMap::default();
+        let mut stick = Stick::default();
+        assert_eq!(
+            stick.motion(&map, Axis::LeftX, -20_000),
+            [(ChipKey::Key4, true)]
+        );
+        assert_eq!(stick.motion(&map, Axis::LeftX, -30_000), []);
+        assert_eq!(
+            stick.motion(&map, Axis::LeftX, 20_000),
+            [(ChipKey::Key4, false), (ChipKey::Key6, true)]
+        );
+        assert_eq!(stick.motion(&map, Axis::LeftX, 0), [(ChipKey::Key6, false)]);
+        assert_eq!(
+            stick.motion(&map, Axis::LeftY, 20_000),
+            [(ChipKey::Key8, true)]
+        );
+        assert_eq!(stick.motion(&map, Axis::RightX, -20_000), []);
+    }
+}
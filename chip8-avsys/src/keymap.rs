@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use chip8_vm::ChipKey;
+use sdl2::keyboard::Keycode;
+
+/// Mapping of the host keys to the CHIP-8 keys. A CHIP-8 key can be bound to several host keys.
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    keys: HashMap<Keycode, ChipKey>,
+}
+
+impl Default for KeyMap {
+    /// The keypad mapped to the four by four block on the left of a QWERTY keyboard:
+    ///
+    /// ```text
+    /// 1 2 3 C      1 2 3 4
+    /// 4 5 6 D  ->  Q W E R
+    /// 7 8 9 E      A S D F
+    /// A 0 B F      Z X C V
+    /// ```
+    fn default() -> Self {
+        let host_rows = [
+            [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4],
+            [Keycode::Q, Keycode::W, Keycode::E, Keycode::R],
+            [Keycode::A, Keycode::S, Keycode::D, Keycode::F],
+            [Keycode::Z, Keycode::X, Keycode::C, Keycode::V],
+        ];
+        let keys = host_rows
+            .iter()
+            .flatten()
+            .copied()
+            .zip(ChipKey::KEYPAD.iter().flatten().copied())
+            .collect();
+        KeyMap { keys }
+    }
+}
+
+impl KeyMap {
+    /// Bind a CHIP-8 key to the host keys with the given SDL names (e.g. `Q`, `Up` or
+    /// `Keypad 5`), replacing its previous bindings.
+    pub fn bind(&mut self, key: ChipKey, host_keys: &[String]) -> Result<(), String> {
+        let keycodes = host_keys
+            .iter()
+            .map(|name| {
+                Keycode::from_name(name).ok_or_else(|| format!("unknown key name '{}'", name))
+            })
+            .collect::<Result<Vec<Keycode>, String>>()?;
+        self.keys.retain(|_, chip_key| *chip_key != key);
+        for keycode in keycodes {
+            self.keys.insert(keycode, key);
+        }
+        Ok(())
+    }
+
+    /// CHIP-8 key of a host key.
+    pub(crate) fn chip_key(&self, keycode: Keycode) -> Option<ChipKey> {
+        self.keys.get(&keycode).copied()
+    }
+
+    /// Host keys bound to a CHIP-8 key, the inverse of the mapping.
+    pub fn host_keys(&self, key: ChipKey) -> Vec<Keycode> {
+        let mut keycodes = self
+            .keys
+            .iter()
+            .filter(|(_, chip_key)| **chip_key == key)
+            .map(|(keycode, _)| *keycode)
+            .collect::<Vec<Keycode>>();
+        keycodes.sort_by_key(|keycode| keycode.name());
+        keycodes
+    }
+
+    /// Text description of the bindings, the CHIP-8 keypad with the host keys of each key.
+    pub fn describe(&self) -> String {
+        ChipKey::KEYPAD
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|key| {
+                        let names = self
+                            .host_keys(*key)
+                            .iter()
+                            .map(|keycode| keycode.name())
+                            .collect::<Vec<String>>();
+                        format!("{:X}: {:<12}", key.value(), names.join("/"))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
@@ -1,223 +1,329 @@
 mod config;
+mod gamepad;
+mod keymap;
+mod settings;
 
 extern crate sdl2;
 
-use sdl2::audio::{AudioCallback, AudioSpec, AudioSpecDesired};
-use sdl2::event::Event;
+use std::collections::HashMap;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpec, AudioSpecDesired};
+use sdl2::controller::GameController;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
-use sdl2::sys::SDL_KeyCode;
-use sdl2::video::Window;
-use sdl2::AudioSubsystem;
+use sdl2::video::{FullscreenType, Window};
+use sdl2::{AudioSubsystem, EventPump, GameControllerSubsystem};
 
 use crate::config::*;
-use chip8_vm::{Signal, VM};
-
-const TIME_PER_FRAME_IN_MILLIS: u32 = 16;
-
-static KEYMAP: &'static [(i32, usize)] = &[
-    (SDL_KeyCode::SDLK_1 as i32, 0x1),
-    (SDL_KeyCode::SDLK_2 as i32, 0x2),
-    (SDL_KeyCode::SDLK_3 as i32, 0x3),
-    (SDL_KeyCode::SDLK_4 as i32, 0xC),
-    (SDL_KeyCode::SDLK_q as i32, 0x4),
-    (SDL_KeyCode::SDLK_w as i32, 0x5),
-    (SDL_KeyCode::SDLK_e as i32, 0x6),
-    (SDL_KeyCode::SDLK_r as i32, 0xD),
-    (SDL_KeyCode::SDLK_a as i32, 0x7),
-    (SDL_KeyCode::SDLK_s as i32, 0x8),
-    (SDL_KeyCode::SDLK_d as i32, 0x9),
-    (SDL_KeyCode::SDLK_f as i32, 0xE),
-    (SDL_KeyCode::SDLK_z as i32, 0xA),
-    (SDL_KeyCode::SDLK_x as i32, 0x0),
-    (SDL_KeyCode::SDLK_c as i32, 0xB),
-    (SDL_KeyCode::SDLK_v as i32, 0xF),
-];
-
-static INVERSE_KEYMAP: &'static [(usize, i32)] = &[
-    (0x1, SDL_KeyCode::SDLK_1 as i32),
-    (0x2, SDL_KeyCode::SDLK_2 as i32),
-    (0x3, SDL_KeyCode::SDLK_3 as i32),
-    (0xC, SDL_KeyCode::SDLK_4 as i32),
-    (0x4, SDL_KeyCode::SDLK_q as i32),
-    (0x5, SDL_KeyCode::SDLK_w as i32),
-    (0x6, SDL_KeyCode::SDLK_e as i32),
-    (0xD, SDL_KeyCode::SDLK_r as i32),
-    (0x7, SDL_KeyCode::SDLK_a as i32),
-    (0x8, SDL_KeyCode::SDLK_s as i32),
-    (0x9, SDL_KeyCode::SDLK_d as i32),
-    (0xE, SDL_KeyCode::SDLK_f as i32),
-    (0xA, SDL_KeyCode::SDLK_z as i32),
-    (0x0, SDL_KeyCode::SDLK_x as i32),
-    (0xB, SDL_KeyCode::SDLK_c as i32),
-    (0xF, SDL_KeyCode::SDLK_v as i32),
-];
-
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
-}
+use crate::gamepad::Stick;
+use chip8_vm::{Audio, Driver, HostEvent, Input, PatternWave, Video, VM};
+
+pub use crate::gamepad::ButtonMap;
+pub use crate::keymap::KeyMap;
+pub use crate::settings::Settings;
 
-impl AudioCallback for SquareWave {
+/// Plays the VM 1-bit audio pattern in a loop, at the VM pattern playback rate.
+struct DeviceWave(PatternWave);
+
+impl AudioCallback for DeviceWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.0.fill(out);
     }
 }
 
-pub fn start(rom: Vec<u8>, debug_mode: bool) -> Result<(), String> {
-    let mut chip8: VM = VM::new();
-    chip8.load_program(&rom)?;
-
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video()?;
-    let window = video_subsystem
-        .window(
-            EMULATOR_WINDOW_TITLE,
-            CHIP8_WIDTH * CHIP8_WINDOW_MULTIPLIER,
-            CHIP8_HEIGHT * CHIP8_WINDOW_MULTIPLIER,
-        )
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())?;
-    let timer = sdl_context.timer()?;
-
-    let audio_subsystem = sdl_context.audio()?;
-    let device = build_audio_device(&audio_subsystem);
-
-    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
-    let mut event_pump = sdl_context.event_pump()?;
+/// SDL host: the window, the audio device, the keyboard and the game controllers.
+struct SdlHost {
+    canvas: Canvas<Window>,
+    device: AudioDevice<DeviceWave>,
+    event_pump: EventPump,
+    game_controller_subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>,
+    stick: Stick,
+    settings: Settings,
+    /// The window was resized or the palette changed, the screen must be drawn again.
+    redraw: bool,
+}
 
-    // Time
-    let mut start_time = 0;
-    let mut end_time: u32 = 0;
-    let mut delta;
-    let mut time_acc = 0;
+impl Video for SdlHost {
+    fn present(&mut self, chip8: &VM, screen_changed: bool) -> Result<(), String> {
+        if screen_changed || self.redraw {
+            draw_screen(chip8, &mut self.canvas, &self.settings)?;
+            self.redraw = false;
+        }
+        Ok(())
+    }
+}
 
-    'running: loop {
-        delta = end_time - start_time;
-        time_acc += delta;
+impl Audio for SdlHost {
+    fn update(&mut self, chip8: &VM, playing: bool) -> Result<(), String> {
+        if playing {
+            self.device.lock().0.update(chip8);
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+        Ok(())
+    }
+}
 
-        for event in event_pump.poll_iter() {
+impl Input for SdlHost {
+    fn poll(&mut self, _frame: u64) -> Result<Vec<HostEvent>, String> {
+        let mut events = Vec::new();
+        // Collected first, the handlers need the whole host.
+        let pending = self.event_pump.poll_iter().collect::<Vec<Event>>();
+        for event in pending {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => events.push(HostEvent::Quit),
                 Event::KeyDown {
-                    keycode: Some(kc), ..
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => events.push(HostEvent::Rewind(true)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => events.push(HostEvent::Rewind(false)),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => events.push(HostEvent::SaveState),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    ..
+                } => events.push(HostEvent::LoadState),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => events.push(HostEvent::PreviousSlot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    ..
+                } => events.push(HostEvent::NextSlot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    ..
                 } => {
-                    chip8.keyboard_key_down(kc as i32, KEYMAP);
+                    let name = self.settings.next_palette();
+                    self.message(&format!("Palette {}", name));
+                    self.redraw = true;
+                    events.push(HostEvent::Colors(self.settings.colors));
                 }
-                Event::KeyUp {
-                    keycode: Some(kc), ..
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    ..
                 } => {
-                    chip8.keyboard_key_up(kc as i32, KEYMAP);
+                    self.settings.grid = !self.settings.grid;
+                    self.redraw = true;
                 }
-                _ => {}
-            }
-        }
-
-        // Beep sound
-        if chip8.registers_st() > 0 {
-            device.resume(); // Start playback
-        } else {
-            device.pause();
-        }
-
-        match chip8.exec_next_opcode(debug_mode, &mut time_acc)? {
-            Signal::DrawScreen => {
-                if delta < TIME_PER_FRAME_IN_MILLIS {
-                    timer.delay(TIME_PER_FRAME_IN_MILLIS - delta);
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    self.settings.fullscreen = !self.settings.fullscreen;
+                    set_fullscreen(self.canvas.window_mut(), self.settings.fullscreen)?;
                 }
-                draw_screen(&mut chip8, &mut canvas)?;
-            }
-            Signal::WaitKeyUp(key) => 'wait_keyup: loop {
-                for event in event_pump.poll_iter() {
-                    match event {
-                        Event::KeyUp {
-                            keycode: Some(kc), ..
-                        } => {
-                            if let Some(sdl_key) = ch8_key2sdl_key(key as usize) {
-                                if sdl_key == kc as i32 {
-                                    chip8.keyboard_key_up(sdl_key, KEYMAP);
-                                    break 'wait_keyup;
-                                }
-                            }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => self.redraw = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::Screenshot),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Minus | Keycode::KpMinus),
+                    ..
+                } => events.push(HostEvent::SpeedDown),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Equals | Keycode::KpPlus),
+                    ..
+                } => events.push(HostEvent::SpeedUp),
+                Event::KeyDown {
+                    keycode: Some(kc),
+                    repeat: false,
+                    ..
+                } => events.extend(
+                    self.settings
+                        .keymap
+                        .chip_key(kc)
+                        .map(|key| HostEvent::Key(key, true)),
+                ),
+                Event::KeyUp {
+                    keycode: Some(kc), ..
+                } => events.extend(
+                    self.settings
+                        .keymap
+                        .chip_key(kc)
+                        .map(|key| HostEvent::Key(key, false)),
+                ),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match self.game_controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            self.message(&format!(
+                                "Game controller connected: {}",
+                                controller.name()
+                            ));
+                            self.controllers
+                                .insert(controller.instance_id(), controller);
                         }
-                        Event::Quit { .. }
-                        | Event::KeyDown {
-                            keycode: Some(Keycode::Escape),
-                            ..
-                        } => break 'running,
-                        _ => {}
+                        Err(e) => self
+                            .message(&format!("Unable to open game controller {}: {}", which, e)),
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(controller) = self.controllers.remove(&which) {
+                        self.message(&format!(
+                            "Game controller disconnected: {}",
+                            controller.name()
+                        ));
                     }
                 }
-            },
-            _ => {}
+                Event::ControllerButtonDown { button, .. } => events.extend(
+                    self.settings
+                        .buttons
+                        .chip_key(button)
+                        .map(|key| HostEvent::Key(key, true)),
+                ),
+                Event::ControllerButtonUp { button, .. } => events.extend(
+                    self.settings
+                        .buttons
+                        .chip_key(button)
+                        .map(|key| HostEvent::Key(key, false)),
+                ),
+                Event::ControllerAxisMotion { axis, value, .. } => events.extend(
+                    self.stick
+                        .motion(&self.settings.buttons, axis, value)
+                        .into_iter()
+                        .map(|(key, pressed)| HostEvent::Key(key, pressed)),
+                ),
+                _ => {}
+            }
         }
+        Ok(events)
+    }
+}
+
+/// Runs the VM, with the ROM already loaded, in an SDL window with the `driver` loop. The speed can
+/// be changed at runtime with the `-` and `=` keys. F5 saves the quick save state of the current
+/// slot, F9 loads it and F6/F7 select the slot. Holding Backspace rewinds the program one frame at
+/// a time and F12 saves a screenshot. F2 switches to the next palette, F3 shows or hides the pixel
+/// grid and F11 toggles fullscreen. The window can be resized, the screen is scaled by the largest
+/// integer factor that fits. Game controllers can be plugged in at any time. The key and button
+/// bindings, colours, grid, fullscreen and initial window scale come from `settings`.
+pub fn start(mut chip8: VM, mut driver: Driver, settings: &Settings) -> Result<(), String> {
+    println!("Keypad:\n{}", settings.keymap.describe());
 
-        start_time = end_time;
-        end_time = timer.ticks();
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let window = video_subsystem
+        .window(
+            EMULATOR_WINDOW_TITLE,
+            CHIP8_WIDTH * settings.scale.max(1),
+            CHIP8_HEIGHT * settings.scale.max(1),
+        )
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let audio_subsystem = sdl_context.audio()?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    if settings.fullscreen {
+        set_fullscreen(canvas.window_mut(), true)?;
     }
 
+    let mut host = SdlHost {
+        canvas,
+        device: build_audio_device(&audio_subsystem, &chip8),
+        event_pump: sdl_context.event_pump()?,
+        game_controller_subsystem: sdl_context.game_controller()?,
+        controllers: HashMap::new(),
+        stick: Stick::default(),
+        settings: settings.clone(),
+        redraw: true,
+    };
+    driver.run(&mut chip8, &mut host)?;
     Ok(())
 }
 
-fn build_audio_device(audio_subsystem: &AudioSubsystem) -> sdl2::audio::AudioDevice<SquareWave> {
+fn build_audio_device(audio_subsystem: &AudioSubsystem, chip8: &VM) -> AudioDevice<DeviceWave> {
     let desired_spec = AudioSpecDesired {
         freq: Some(44100),
         channels: Some(1), // mono
         samples: None,     // default sample size
     };
-    let audio_spec = |spec: AudioSpec| SquareWave {
-        phase_inc: 440.0 / spec.freq as f32,
-        phase: 0.0,
-        volume: 0.25,
-    };
+    let audio_spec = |spec: AudioSpec| DeviceWave(PatternWave::new(chip8, spec.freq as u32));
     audio_subsystem
         .open_playback(None, &desired_spec, audio_spec)
         .unwrap()
 }
 
-fn draw_screen(chip8: &mut VM, canvas: &mut Canvas<Window>) -> Result<(), String> {
-    canvas.set_draw_color(Color::RGB(153, 102, 0));
+fn set_fullscreen(window: &mut Window, fullscreen: bool) -> Result<(), String> {
+    window.set_fullscreen(if fullscreen {
+        FullscreenType::Desktop
+    } else {
+        FullscreenType::Off
+    })
+}
+
+fn draw_screen(chip8: &VM, canvas: &mut Canvas<Window>, settings: &Settings) -> Result<(), String> {
+    let colors = settings.colors.map(|[r, g, b]| Color::RGB(r, g, b));
+    canvas.set_draw_color(Color::BLACK);
     canvas.clear();
-    canvas.set_draw_color(Color::RGB(255, 204, 0));
-    for x in 0..CHIP8_WIDTH {
-        for y in 0..CHIP8_HEIGHT {
-            if chip8.screen_is_pixel_set(x as usize, y as usize)? {
+    // Scale the active resolution (low or high) by the largest integer factor fitting the
+    // window, centred with black borders.
+    let width = chip8.screen_width() as u32;
+    let height = chip8.screen_height() as u32;
+    let (window_width, window_height) = canvas.output_size()?;
+    let pixel_size = (window_width / width).min(window_height / height).max(1);
+    let left = (window_width as i32 - (width * pixel_size) as i32) / 2;
+    let top = (window_height as i32 - (height * pixel_size) as i32) / 2;
+    canvas.set_draw_color(colors[0]);
+    canvas.fill_rect(Rect::new(
+        left,
+        top,
+        width * pixel_size,
+        height * pixel_size,
+    ))?;
+    for x in 0..width {
+        for y in 0..height {
+            let planes = chip8.screen_pixel_planes(x as usize, y as usize)?;
+            if planes != 0 {
+                canvas.set_draw_color(colors[planes as usize]);
                 canvas.fill_rect(Rect::new(
-                    (x * CHIP8_WINDOW_MULTIPLIER) as i32,
-                    (y * CHIP8_WINDOW_MULTIPLIER) as i32,
-                    CHIP8_WINDOW_MULTIPLIER,
-                    CHIP8_WINDOW_MULTIPLIER,
+                    left + (x * pixel_size) as i32,
+                    top + (y * pixel_size) as i32,
+                    pixel_size,
+                    pixel_size,
                 ))?;
             }
         }
     }
-    canvas.present();
-    Ok(())
-}
-
-fn ch8_key2sdl_key(key: usize) -> Option<i32> {
-    for (ch8_k, sdl_k) in INVERSE_KEYMAP.into_iter() {
-        if key == *ch8_k {
-            return Some(*sdl_k);
+    if settings.grid && pixel_size >= GRID_MIN_PIXEL_SIZE {
+        // Lines of the background colour on the top and left edges of every pixel.
+        canvas.set_draw_color(colors[0]);
+        let (right, bottom) = (
+            left + (width * pixel_size) as i32,
+            top + (height * pixel_size) as i32,
+        );
+        for x in 0..width {
+            let line_x = left + (x * pixel_size) as i32;
+            canvas.draw_line((line_x, top), (line_x, bottom - 1))?;
+        }
+        for y in 0..height {
+            let line_y = top + (y * pixel_size) as i32;
+            canvas.draw_line((left, line_y), (right - 1, line_y))?;
         }
     }
-    None
+    canvas.present();
+    Ok(())
 }
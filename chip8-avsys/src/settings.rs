@@ -0,0 +1,115 @@
+use crate::{config::CHIP8_WINDOW_MULTIPLIER, gamepad::ButtonMap, keymap::KeyMap};
+
+/// Built-in palettes: the colours of the pixels with no bit plane, the first, the second and
+/// both.
+const PALETTES: [(&str, [[u8; 3]; 4]); 4] = [
+    (
+        "amber",
+        [[153, 102, 0], [255, 204, 0], [204, 51, 0], [255, 255, 153]],
+    ),
+    (
+        "green",
+        [[0, 32, 0], [51, 255, 51], [0, 136, 68], [204, 255, 153]],
+    ),
+    (
+        "white",
+        [[0, 0, 0], [255, 255, 255], [128, 128, 128], [192, 192, 192]],
+    ),
+    (
+        "lcd",
+        [[155, 188, 15], [15, 56, 15], [48, 98, 48], [8, 24, 32]],
+    ),
+];
+
+/// Front-end settings of the emulator window.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub keymap: KeyMap,
+    /// Game controller bindings.
+    pub buttons: ButtonMap,
+    /// RGB colours for each combination of the XO-CHIP bit planes: none, first, second and both.
+    pub colors: [[u8; 3]; 4],
+    /// Window pixels per CHIP-8 low resolution pixel, when the window opens.
+    pub scale: u32,
+    /// Draw lines between the CHIP-8 pixels.
+    pub grid: bool,
+    pub fullscreen: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            keymap: KeyMap::default(),
+            buttons: ButtonMap::default(),
+            colors: PALETTES[0].1,
+            scale: CHIP8_WINDOW_MULTIPLIER,
+            grid: false,
+            fullscreen: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Names of the built-in palettes.
+    pub fn palette_names() -> Vec<&'static str> {
+        PALETTES.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Use the colours of a built-in palette. The name is not case sensitive.
+    pub fn set_palette(&mut self, name: &str) -> Result<(), String> {
+        let (_, colors) = PALETTES
+            .iter()
+            .find(|(palette, _)| palette.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "unknown palette '{}', expected one of: {}",
+                    name,
+                    Settings::palette_names().join(", ")
+                )
+            })?;
+        self.colors = *colors;
+        Ok(())
+    }
+
+    /// Switch to the built-in palette after the current one, or to the first one with custom
+    /// colours, and return its name.
+    pub(crate) fn next_palette(&mut self) -> &'static str {
+        let next = PALETTES
+            .iter()
+            .position(|(_, colors)| *colors == self.colors)
+            .map_or(0, |index| (index + 1) % PALETTES.len());
+        let (name, colors) = PALETTES[next];
+        self.colors = colors;
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_palette() {
+        let mut settings = Settings::default();
+        for name in ["green", "white", "lcd", "amber"] {
+            assert_eq!(settings.next_palette(), name);
+        }
+        assert_eq!(settings.colors, PALETTES[0].1);
+
+        settings.colors = [[1, 2, 3]; 4];
+        assert_eq!(settings.next_palette(), "amber");
+        assert_eq!(settings.colors, PALETTES[0].1);
+    }
+
+    #[test]
+    fn set_palette() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.set_palette("LCD"), Ok(()));
+        assert_eq!(settings.colors, PALETTES[3].1);
+        assert_eq!(
+            settings.set_palette("x"),
+            Err("unknown palette 'x', expected one of: amber, green, white, lcd".to_string())
+        );
+        assert_eq!(settings.colors, PALETTES[3].1);
+    }
+}
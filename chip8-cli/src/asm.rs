@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use chip8_vm::asm::assemble_file;
+use clap::Args;
+
+#[derive(Args)]
+pub(crate) struct AsmArgs {
+    source_file: PathBuf,
+    /// Output ROM file, the source file with the `.ch8` extension by default.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Symbol file, the output file with the `.sym` extension by default.
+    #[arg(long)]
+    symbols: Option<PathBuf>,
+}
+
+pub(crate) fn asm(args: AsmArgs) -> Result<(), String> {
+    let program = assemble_file(&args.source_file)?;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| args.source_file.with_extension("ch8"));
+    let symbols = args.symbols.unwrap_or_else(|| output.with_extension("sym"));
+    std::fs::write(&output, &program.bytes).map_err(|e| format!("{}: {}", output.display(), e))?;
+    std::fs::write(&symbols, program.symbol_file())
+        .map_err(|e| format!("{}: {}", symbols.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,492 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use chip8_avsys::Settings;
+use chip8_vm::{Audio, Capture, WavWriter, VM};
+
+/// Width and height of the CHIP-8 low resolution screen, the size of the captures before
+/// scaling. The captures are twice as large once the program switches to the high resolution.
+const LORES_WIDTH: usize = 64;
+const LORES_HEIGHT: usize = 32;
+
+/// Shortest GIF frame duration in hundredths of a second, browsers slow down shorter frames.
+const MIN_GIF_DELAY: u16 = 2;
+
+/// Screenshots and recordings of the emulator screen at the scale and colours of the window.
+/// The pictures have the size of the low resolution screen until the program switches to the
+/// high resolution, and the size of the high resolution screen from then on, the low
+/// resolution pixels being doubled.
+pub(crate) struct FileCapture {
+    colors: [[u8; 3]; 4],
+    /// Picture pixels per low resolution pixel.
+    scale: usize,
+    /// The program switched to the high resolution during the run.
+    hires: bool,
+    /// Screenshots are saved in numbered files starting with this prefix.
+    screenshot_prefix: String,
+    /// File of the screenshot saved at the end of the run.
+    final_screenshot: Option<String>,
+    recorder: Option<Recorder>,
+}
+
+/// The VM screen at its own resolution, one colour index per pixel.
+#[derive(PartialEq, Eq)]
+struct Snapshot {
+    width: usize,
+    pixels: Vec<u8>,
+}
+
+impl Snapshot {
+    fn new(vm: &VM) -> Result<Self, String> {
+        let (width, height) = (vm.screen_width(), vm.screen_height());
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(vm.screen_pixel_planes(x, y)?);
+            }
+        }
+        Ok(Snapshot { width, pixels })
+    }
+
+    /// The screen scaled to a `width` x `height` picture.
+    fn picture(&self, width: usize, height: usize) -> Vec<u8> {
+        let screen_height = self.pixels.len() / self.width;
+        let mut picture = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = &self.pixels[y * screen_height / height * self.width..];
+            picture.extend((0..width).map(|x| row[x * self.width / width]));
+        }
+        picture
+    }
+}
+
+/// A recorded picture: the screen, its colours and the frames where it is shown.
+struct Picture {
+    snapshot: Snapshot,
+    colors: [[u8; 3]; 4],
+    start: u64,
+    end: u64,
+}
+
+/// Recording of the presented frames. The size of a recording can't change, so its pictures
+/// are kept in memory until it is known: when the program switches to the high resolution, or
+/// at the end of the run.
+struct Recorder {
+    /// Animated GIF, with a frame per screen or colour change, instead of raw RGB24 frames, one
+    /// per presented frame.
+    gif: bool,
+    /// File of the recording, until the size is known.
+    file: Option<BufWriter<File>>,
+    output: Option<Output>,
+    /// Colours of the global palette of a GIF, at the start of the recording.
+    palette: [[u8; 3]; 4],
+    /// Last picture, not complete until the next change gives its duration.
+    picture: Option<Picture>,
+    /// Complete pictures not written yet.
+    pending: Vec<Picture>,
+    /// Frames presented since the start of the recording.
+    frames: u64,
+}
+
+enum Output {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Raw(BufWriter<File>),
+}
+
+impl FileCapture {
+    /// Capture with the colours and scale of the settings. A screenshot is saved to
+    /// `final_screenshot` at the end of the run, and every frame is recorded to `recording`:
+    /// an animated GIF if the file has the `.gif` extension, raw RGB24 frames otherwise.
+    pub(crate) fn new(
+        settings: &Settings,
+        screenshot_prefix: &str,
+        final_screenshot: Option<String>,
+        recording: Option<&str>,
+    ) -> Result<FileCapture, String> {
+        let recorder = match recording {
+            Some(file_name) => {
+                let file = File::create(file_name)
+                    .map(BufWriter::new)
+                    .map_err(|e| format!("Unable to create {}: {}", file_name, e))?;
+                Some(Recorder {
+                    gif: Path::new(file_name)
+                        .extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif")),
+                    file: Some(file),
+                    output: None,
+                    palette: settings.colors,
+                    picture: None,
+                    pending: Vec::new(),
+                    frames: 0,
+                })
+            }
+            None => None,
+        };
+        Ok(FileCapture {
+            colors: settings.colors,
+            scale: settings.scale.max(1) as usize,
+            hires: false,
+            screenshot_prefix: screenshot_prefix.to_string(),
+            final_screenshot,
+            recorder,
+        })
+    }
+
+    /// Switch to the size of the high resolution screen once the program does.
+    fn follow_resolution(&mut self, vm: &VM) {
+        self.hires |= vm.screen_width() > LORES_WIDTH;
+    }
+
+    /// Width and height of the pictures.
+    fn size(&self) -> (usize, usize) {
+        let pixel = if self.hires {
+            2 * self.scale
+        } else {
+            self.scale
+        };
+        (LORES_WIDTH * pixel, LORES_HEIGHT * pixel)
+    }
+
+    fn save_png(&self, vm: &VM, file_name: &str) -> Result<(), String> {
+        let (width, height) = self.size();
+        let file = File::create(file_name).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(self.colors.as_flattened());
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&Snapshot::new(vm)?.picture(width, height))
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Recorder {
+    /// Record a presented frame. `size` is the size of the recording, once it is known.
+    fn frame(
+        &mut self,
+        vm: &VM,
+        screen_changed: bool,
+        colors: [[u8; 3]; 4],
+        size: Option<(usize, usize)>,
+    ) -> Result<(), String> {
+        let colors_changed = self
+            .picture
+            .as_ref()
+            .is_none_or(|picture| picture.colors != colors);
+        if screen_changed || colors_changed {
+            let snapshot = Snapshot::new(vm)?;
+            let unchanged = self
+                .picture
+                .as_ref()
+                .is_some_and(|picture| picture.snapshot == snapshot && picture.colors == colors);
+            if !unchanged {
+                let mut start = self.frames;
+                match self.picture.take() {
+                    // GIF pictures shown for less than the minimum delay are dropped, their
+                    // time goes to the next one.
+                    Some(last) if self.gif && gif_delay(last.start, start) < MIN_GIF_DELAY => {
+                        start = last.start;
+                    }
+                    Some(mut last) => {
+                        last.end = start;
+                        self.pending.push(last);
+                    }
+                    None => {}
+                }
+                self.picture = Some(Picture {
+                    snapshot,
+                    colors,
+                    start,
+                    end: start,
+                });
+            }
+        }
+        self.frames += 1;
+        match size {
+            Some(size) => self.write(size),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the complete pictures into a recording of the given size.
+    fn write(&mut self, (width, height): (usize, usize)) -> Result<(), String> {
+        if let Some(file) = self.file.take() {
+            self.output = Some(if self.gif {
+                let mut encoder = gif::Encoder::new(
+                    file,
+                    width as u16,
+                    height as u16,
+                    self.palette.as_flattened(),
+                )
+                .map_err(|e| e.to_string())?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                Output::Gif(encoder)
+            } else {
+                Output::Raw(file)
+            });
+        }
+        let Some(output) = &mut self.output else {
+            return Ok(());
+        };
+        for picture in self.pending.drain(..) {
+            let pixels = picture.snapshot.picture(width, height);
+            match output {
+                Output::Gif(encoder) => {
+                    let mut frame =
+                        gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+                    if picture.colors != self.palette {
+                        frame.palette = Some(picture.colors.as_flattened().to_vec());
+                    }
+                    frame.delay = gif_delay(picture.start, picture.end).max(MIN_GIF_DELAY);
+                    encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+                }
+                Output::Raw(file) => {
+                    let rgb = pixels
+                        .iter()
+                        .flat_map(|index| picture.colors[*index as usize])
+                        .collect::<Vec<u8>>();
+                    for _ in picture.start..picture.end {
+                        file.write_all(&rgb).map_err(|e| e.to_string())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the last pictures into a recording of the given size.
+    fn finish(&mut self, size: (usize, usize)) -> Result<(), String> {
+        if let Some(mut last) = self.picture.take() {
+            last.end = self.frames;
+            self.pending.push(last);
+        }
+        self.write(size)?;
+        match &mut self.output {
+            Some(Output::Gif(encoder)) => encoder.get_mut().flush(),
+            Some(Output::Raw(file)) => file.flush(),
+            None => Ok(()),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Sound recorder writing to `file_name`, if any.
+pub(crate) fn wav_writer(file_name: Option<&str>) -> Result<Option<Box<dyn Audio>>, String> {
+    let Some(file_name) = file_name else {
+        return Ok(None);
+    };
+    let file = File::create(file_name)
+        .map(BufWriter::new)
+        .map_err(|e| format!("Unable to create {}: {}", file_name, e))?;
+    Ok(Some(Box::new(WavWriter::new(file)?)))
+}
+
+/// Duration of a GIF frame in hundredths of a second, from the 60 Hz frame where it starts to
+/// the one where it ends, rounded so that the durations add up to the elapsed time.
+fn gif_delay(start: u64, end: u64) -> u16 {
+    (end * 100 / 60 - start * 100 / 60).min(u16::MAX as u64) as u16
+}
+
+impl Capture for FileCapture {
+    fn frame(&mut self, vm: &VM, screen_changed: bool) -> Result<(), String> {
+        self.follow_resolution(vm);
+        let size = self.hires.then(|| self.size());
+        match &mut self.recorder {
+            Some(recorder) => recorder.frame(vm, screen_changed, self.colors, size),
+            None => Ok(()),
+        }
+    }
+
+    fn screenshot(&mut self, vm: &VM) -> Result<String, String> {
+        self.follow_resolution(vm);
+        let file_name = (0..)
+            .map(|index| format!("{}.screenshot{}.png", self.screenshot_prefix, index))
+            .find(|file_name| !Path::new(file_name).exists())
+            .unwrap_or_default();
+        self.save_png(vm, &file_name)?;
+        Ok(file_name)
+    }
+
+    fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+        self.colors = colors;
+    }
+
+    fn finish(&mut self, vm: &VM) -> Result<(), String> {
+        self.follow_resolution(vm);
+        let size = self.size();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.finish(size)?;
+        }
+        if let Some(file_name) = &self.final_screenshot {
+            self.save_png(vm, file_name)
+                .map_err(|e| format!("Unable to save screenshot to {}: {}", file_name, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use chip8_avsys::Settings;
+    use chip8_vm::{Capture, Quirks, VM};
+
+    use super::{gif_delay, FileCapture, Snapshot};
+
+    #[test]
+    fn gif_delays_add_up() {
+        assert_eq!(
+            (0..6)
+                .map(|frame| gif_delay(frame, frame + 1))
+                .collect::<Vec<u16>>(),
+            [1, 2, 2, 1, 2, 2]
+        );
+        assert_eq!(
+            (0..600)
+                .map(|frame| gif_delay(frame, frame + 1) as u32)
+                .sum::<u32>(),
+            1000
+        );
+        let changes = [0, 1, 4, 5, 17, 100, 101, 600];
+        assert_eq!(
+            changes
+                .windows(2)
+                .map(|frames| gif_delay(frames[0], frames[1]) as u32)
+                .sum::<u32>(),
+            1000
+        );
+    }
+
+    /// Run a program drawing the pixel (5, 5), in high resolution if `hires`.
+    fn draw_pixel(hires: bool) -> VM {
+        // 0x200: HIGH or CLS, 0x202: LD V1, 5, 0x204: LD I, 0x20C, 0x206: DRW V1, V1, 1,
+        // 0x208: JP 0x208, 0x20C: sprite
+        let first = if hires { 0xFF } else { 0xE0 };
+        let program = [
+            0x00, first, 0x61, 0x05, 0xA2, 0x0C, 0xD1, 0x11, 0x12, 0x08, 0x00, 0x00, 0x80, 0x00,
+        ];
+        let mut chip8 = VM::new(Quirks::SUPER_CHIP);
+        chip8.load_program(&program).unwrap();
+        chip8.run_frame(10).unwrap();
+        chip8
+    }
+
+    /// Coordinates of the pixels set in the capture picture of the VM screen.
+    fn set_pixels(capture: &FileCapture, chip8: &VM) -> Vec<(usize, usize)> {
+        let (width, height) = capture.size();
+        Snapshot::new(chip8)
+            .unwrap()
+            .picture(width, height)
+            .iter()
+            .enumerate()
+            .filter(|(_, index)| **index != 0)
+            .map(|(offset, _)| (offset % width, offset / width))
+            .collect()
+    }
+
+    /// Coordinates of the picture pixels of the CHIP-8 pixel (5, 5), `size` pixels wide.
+    fn square(size: usize) -> Vec<(usize, usize)> {
+        (5 * size..6 * size)
+            .flat_map(|y| (5 * size..6 * size).map(move |x| (x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn picture_size() {
+        let settings = Settings {
+            scale: 3,
+            ..Settings::default()
+        };
+        let mut capture = FileCapture::new(&settings, "test", None, None).unwrap();
+        let (lores, hires) = (draw_pixel(false), draw_pixel(true));
+        capture.follow_resolution(&lores);
+        assert_eq!(capture.size(), (192, 96));
+        assert_eq!(set_pixels(&capture, &lores), square(3));
+
+        // The pictures stay at the high resolution size once the program switched to it.
+        capture.follow_resolution(&hires);
+        assert_eq!(capture.size(), (384, 192));
+        assert_eq!(set_pixels(&capture, &hires), square(3));
+        capture.follow_resolution(&lores);
+        assert_eq!(capture.size(), (384, 192));
+        assert_eq!(set_pixels(&capture, &lores), square(6));
+    }
+
+    #[test]
+    fn recording_switches_to_hires() {
+        let file_name = std::env::temp_dir().join("chip8-capture-hires.rgb");
+        let file_name = file_name.to_str().unwrap();
+        let settings = Settings {
+            scale: 1,
+            ..Settings::default()
+        };
+        let mut capture = FileCapture::new(&settings, "test", None, Some(file_name)).unwrap();
+        let (lores, hires) = (draw_pixel(false), draw_pixel(true));
+        capture.frame(&lores, true).unwrap();
+        capture.frame(&lores, false).unwrap();
+        capture.frame(&hires, true).unwrap();
+        capture.finish(&hires).unwrap();
+        drop(capture);
+
+        let rgb = fs::read(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+        // Every frame has the high resolution size, the low resolution ones being doubled.
+        let frame_size = 128 * 64 * 3;
+        assert_eq!(rgb.len(), 3 * frame_size);
+        let pixel = |frame: usize, x: usize, y: usize| {
+            let offset = frame * frame_size + (y * 128 + x) * 3;
+            rgb[offset..offset + 3].to_vec()
+        };
+        let [background, set, _, _] = settings.colors;
+        for frame in 0..2 {
+            assert_eq!(pixel(frame, 10, 10), set);
+            assert_eq!(pixel(frame, 11, 11), set);
+            assert_eq!(pixel(frame, 12, 12), background);
+        }
+        assert_eq!(pixel(2, 5, 5), set);
+        assert_eq!(pixel(2, 6, 6), background);
+    }
+
+    #[test]
+    fn gif_follows_colors() {
+        let file_name = std::env::temp_dir().join("chip8-capture-colors.gif");
+        let file_name = file_name.to_str().unwrap();
+        let settings = Settings::default();
+        let mut capture = FileCapture::new(&settings, "test", None, Some(file_name)).unwrap();
+        let chip8 = draw_pixel(false);
+        let green = [[0, 32, 0], [51, 255, 51], [0, 136, 68], [204, 255, 153]];
+        for frame in 0..12 {
+            if frame == 6 {
+                capture.set_colors(green);
+            }
+            capture.frame(&chip8, frame == 0).unwrap();
+        }
+        capture.finish(&chip8).unwrap();
+        // The encoder writes the end of the file when dropped.
+        drop(capture);
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(file_name).unwrap())
+            .unwrap();
+        assert_eq!(
+            decoder.global_palette(),
+            Some(settings.colors.as_flattened())
+        );
+        let mut palettes = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            palettes.push((frame.delay, frame.palette.clone()));
+        }
+        fs::remove_file(file_name).unwrap();
+        assert_eq!(
+            palettes,
+            [(10, None), (10, Some(green.as_flattened().to_vec()))]
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chip8_avsys::{ButtonMap, Settings};
+use chip8_tui::KeyMap;
+use chip8_vm::ChipKey;
+use serde::Deserialize;
+
+use crate::QuirksPreset;
+
+/// Emulator configuration read from TOML files, e.g.:
+///
+/// ```toml
+/// quirks = "super-chip"
+/// cycles-per-frame = 20
+/// scale = 12
+/// palette = "green"
+/// colors = ["#000000", "#FFFFFF", "#FF0000", "#FFFF00"]
+/// grid = true
+/// fullscreen = false
+///
+/// controller-preset = "tank"
+///
+/// [keys]
+/// 5 = ["W", "Up"]
+/// 8 = ["S", "Down"]
+///
+/// [buttons]
+/// 5 = ["a", "rightshoulder"]
+/// ```
+///
+/// Every setting is optional. The global file is read first, then the file of the ROM, whose
+/// settings override the global ones.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    pub(crate) quirks: Option<QuirksPreset>,
+    pub(crate) cycles_per_frame: Option<usize>,
+    pub(crate) scale: Option<u32>,
+    /// Built-in palette, replaced by `colors` if both are set.
+    palette: Option<String>,
+    /// `#RRGGBB` colours of the bit plane combinations: none, first, second and both.
+    colors: Option<[String; 4]>,
+    grid: Option<bool>,
+    fullscreen: Option<bool>,
+    /// SDL names of the host keys bound to each CHIP-8 key, by hexadecimal key value.
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+    /// Built-in game controller mapping, by default the preset named like the ROM if any.
+    controller_preset: Option<String>,
+    /// SDL names of the game controller buttons bound to each CHIP-8 key.
+    #[serde(default)]
+    buttons: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    /// Load the global configuration, from `file_name` or else from `chip8/config.toml` in the
+    /// user configuration directory, and the configuration of the ROM, from the file next to the
+    /// ROM with the `.toml` extension. Missing default files are ignored.
+    pub(crate) fn load(file_name: Option<&str>, rom_file_name: &str) -> Result<Config, String> {
+        let global = match file_name {
+            Some(file_name) => Config::read(Path::new(file_name))?,
+            None => match dirs::config_dir() {
+                Some(dir) => Config::read_if_exists(&dir.join("chip8").join("config.toml"))?,
+                None => Config::default(),
+            },
+        };
+        let rom = Config::read_if_exists(&PathBuf::from(rom_file_name).with_extension("toml"))?;
+        global.merge(rom)
+    }
+
+    fn read(path: &Path) -> Result<Config, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        toml::from_str(&source).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    fn read_if_exists(path: &Path) -> Result<Config, String> {
+        if path.exists() {
+            Config::read(path)
+        } else {
+            Ok(Config::default())
+        }
+    }
+
+    /// Settings of `other` override the settings of `self`, key by key for the bindings. The
+    /// palette and the colours are one setting: either of them in `other` replaces both.
+    fn merge(self, other: Config) -> Result<Config, String> {
+        let mut keys = normalize_keys(self.keys)?;
+        keys.extend(normalize_keys(other.keys)?);
+        let mut buttons = normalize_keys(self.buttons)?;
+        buttons.extend(normalize_keys(other.buttons)?);
+        let (palette, colors) = if other.palette.is_some() || other.colors.is_some() {
+            (other.palette, other.colors)
+        } else {
+            (self.palette, self.colors)
+        };
+        Ok(Config {
+            quirks: other.quirks.or(self.quirks),
+            cycles_per_frame: other.cycles_per_frame.or(self.cycles_per_frame),
+            scale: other.scale.or(self.scale),
+            palette,
+            colors,
+            grid: other.grid.or(self.grid),
+            fullscreen: other.fullscreen.or(self.fullscreen),
+            keys,
+            controller_preset: other.controller_preset.or(self.controller_preset),
+            buttons,
+        })
+    }
+
+    /// Settings of the emulator window for a ROM, the defaults changed by the configuration.
+    pub(crate) fn settings(&self, rom_file_name: &str) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+        if let Some(scale) = self.scale {
+            settings.scale = scale;
+        }
+        if let Some(name) = &self.palette {
+            settings.set_palette(name)?;
+        }
+        if let Some(colors) = &self.colors {
+            settings.colors = parse_colors(colors)?;
+        }
+        settings.grid = self.grid.unwrap_or(settings.grid);
+        settings.fullscreen = self.fullscreen.unwrap_or(settings.fullscreen);
+        for (key, host_keys) in &self.keys {
+            settings.keymap.bind(parse_key(key)?, host_keys)?;
+        }
+        match &self.controller_preset {
+            Some(name) => {
+                settings.buttons = ButtonMap::preset(name).ok_or_else(|| {
+                    format!(
+                        "unknown controller preset '{}', expected one of: {}",
+                        name,
+                        ButtonMap::preset_names().join(", ")
+                    )
+                })?
+            }
+            None => {
+                let rom_name = Path::new(rom_file_name)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy())
+                    .unwrap_or_default();
+                if let Some(buttons) = ButtonMap::preset(&rom_name) {
+                    settings.buttons = buttons;
+                }
+            }
+        }
+        for (key, buttons) in &self.buttons {
+            settings.buttons.bind(parse_key(key)?, buttons)?;
+        }
+        Ok(settings)
+    }
+}
+
+impl Config {
+    /// Key bindings of the terminal front-end, the defaults changed by the `[keys]` table.
+    pub(crate) fn tui_keymap(&self) -> Result<KeyMap, String> {
+        let mut keymap = KeyMap::default();
+        for (key, host_keys) in &self.keys {
+            keymap.bind(parse_key(key)?, host_keys)?;
+        }
+        Ok(keymap)
+    }
+}
+
+/// Bindings by CHIP-8 key value in upper case without leading zeros, so that `a` and `0A`
+/// name the same key.
+fn normalize_keys(
+    bindings: BTreeMap<String, Vec<String>>,
+) -> Result<BTreeMap<String, Vec<String>>, String> {
+    bindings
+        .into_iter()
+        .map(|(key, names)| Ok((format!("{:X}", parse_key(&key)?.value()), names)))
+        .collect()
+}
+
+/// CHIP-8 key of a hexadecimal key value.
+fn parse_key(key: &str) -> Result<ChipKey, String> {
+    u8::from_str_radix(key, 16)
+        .ok()
+        .and_then(ChipKey::from_value)
+        .ok_or_else(|| format!("invalid CHIP-8 key '{}'", key))
+}
+
+/// Colours of the bit plane combinations, from 4 `#RRGGBB` colours.
+pub(crate) fn parse_colors(hex: &[String]) -> Result<[[u8; 3]; 4], String> {
+    if hex.len() != 4 {
+        return Err(format!("expected 4 colours, got {}", hex.len()));
+    }
+    let mut colors = [[0; 3]; 4];
+    for (color, hex) in colors.iter_mut().zip(hex) {
+        *color = parse_color(hex)?;
+    }
+    Ok(colors)
+}
+
+fn parse_color(hex: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("invalid colour '{}', expected #RRGGBB", hex);
+    let digits = hex.strip_prefix('#').ok_or_else(invalid)?;
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let value = u32::from_str_radix(digits, 16).map_err(|_| invalid())?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{parse_color, parse_colors, Config};
+
+    fn config(source: &str) -> Config {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn merge_config() {
+        let global = config(
+            "quirks = \"super-chip\"\nscale = 12\npalette = \"green\"\n\
+             [keys]\na = [\"Z\"]\n5 = [\"W\"]\n[buttons]\n5 = [\"a\"]",
+        );
+        let rom = config(
+            "scale = 8\ncolors = [\"#000000\", \"#FFFFFF\", \"#FF0000\", \"#FFFF00\"]\n\
+             [keys]\n0A = [\"X\"]\n[buttons]\n05 = [\"b\"]",
+        );
+        let merged = global.merge(rom).unwrap();
+        assert!(matches!(
+            merged.quirks,
+            Some(crate::QuirksPreset::SuperChip)
+        ));
+        assert_eq!(merged.scale, Some(8));
+        // The colours of the ROM replace the palette of the global file.
+        assert_eq!(merged.palette, None);
+        assert_eq!(merged.colors.as_ref().unwrap()[1], "#FFFFFF");
+        assert_eq!(
+            merged.keys,
+            BTreeMap::from([
+                ("5".to_string(), vec!["W".to_string()]),
+                ("A".to_string(), vec!["X".to_string()]),
+            ])
+        );
+        assert_eq!(
+            merged.buttons,
+            BTreeMap::from([("5".to_string(), vec!["b".to_string()])])
+        );
+
+        let merged = merged.merge(config("palette = \"lcd\"")).unwrap();
+        assert_eq!(merged.palette.as_deref(), Some("lcd"));
+        assert_eq!(merged.colors, None);
+        assert_eq!(merged.scale, Some(8));
+
+        let invalid = Config::default().merge(config("[keys]\n10 = [\"Q\"]"));
+        assert_eq!(invalid.err().unwrap(), "invalid CHIP-8 key '10'");
+    }
+
+    #[test]
+    fn parse_colors_errors() {
+        assert_eq!(parse_color("#FF8000"), Ok([255, 128, 0]));
+        assert_eq!(parse_color("#ff8000"), Ok([255, 128, 0]));
+        for color in ["FF8000", "#FF800", "#FF80000", "#GG8000", "#+F8000"] {
+            assert_eq!(
+                parse_color(color),
+                Err(format!("invalid colour '{}', expected #RRGGBB", color))
+            );
+        }
+
+        let colors = ["#000000", "#FFFFFF", "#FF0000"].map(String::from);
+        assert_eq!(
+            parse_colors(&colors),
+            Err("expected 4 colours, got 3".to_string())
+        );
+        let colors = ["#000000", "#FFFFFF", "#FF0000", "yellow"].map(String::from);
+        assert_eq!(
+            parse_colors(&colors),
+            Err("invalid colour 'yellow', expected #RRGGBB".to_string())
+        );
+        let colors = ["#000000", "#FFFFFF", "#FF0000", "#FFFF00"].map(String::from);
+        assert_eq!(
+            parse_colors(&colors),
+            Ok([[0, 0, 0], [255, 255, 255], [255, 0, 0], [255, 255, 0]])
+        );
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        assert!(toml::from_str::<Config>("scale = 4").is_ok());
+        let error = toml::from_str::<Config>("scael = 4").err().unwrap();
+        assert!(error.to_string().contains("unknown field `scael`"));
+        assert!(toml::from_str::<Config>("palette = 1").is_err());
+    }
+}
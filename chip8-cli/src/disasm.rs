@@ -0,0 +1,57 @@
+use chip8_vm::disasm::disassemble;
+use clap::{Args, ValueEnum};
+use serde_json::json;
+
+use crate::{read_rom, PROGRAM_LOAD_ADDRESS};
+
+#[derive(Args)]
+pub(crate) struct DisasmArgs {
+    rom_file: String,
+    /// Listing format.
+    #[arg(long, value_enum, default_value = "text")]
+    format: DisasmFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DisasmFormat {
+    /// Assembler source, with the addresses and bytes in comments.
+    Text,
+    /// JSON document with the labels and the lines.
+    Json,
+}
+
+pub(crate) fn disasm(args: DisasmArgs) -> Result<(), String> {
+    let rom = read_rom(&args.rom_file);
+    let disassembly = disassemble(&rom, PROGRAM_LOAD_ADDRESS);
+
+    match args.format {
+        DisasmFormat::Text => print!("{}", disassembly),
+        DisasmFormat::Json => {
+            let labels = disassembly
+                .labels()
+                .map(|(addr, label)| json!({ "address": addr, "name": label }))
+                .collect::<Vec<_>>();
+            let lines = disassembly
+                .lines
+                .iter()
+                .map(|line| {
+                    json!({
+                        "address": line.addr,
+                        "label": disassembly.label(line.addr),
+                        "kind": if line.instruction.is_some() { "code" } else { "data" },
+                        "bytes": line.bytes,
+                        "text": disassembly.line_text(line),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let document = json!({
+                "origin": disassembly.origin,
+                "labels": labels,
+                "lines": lines,
+            });
+            let text = serde_json::to_string_pretty(&document).map_err(|e| e.to_string())?;
+            println!("{}", text);
+        }
+    }
+    Ok(())
+}
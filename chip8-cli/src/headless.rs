@@ -0,0 +1,64 @@
+use chip8_vm::{state_dump, Audio, Headless, KeyScript, Movie, RunLength, VM};
+
+use crate::{capture::FileCapture, RunArgs};
+
+/// Run the VM configured by `movie` headless. A replayed movie provides the key input and the
+/// run length, a recorded one gets the key script.
+pub(crate) fn run(
+    mut chip8: VM,
+    args: &RunArgs,
+    mut movie: Movie,
+    capture: FileCapture,
+    audio_sink: Option<Box<dyn Audio>>,
+) -> Result<(), String> {
+    let length = match (args.cycles, args.frames, &args.replay) {
+        (Some(cycles), _, _) => RunLength::Cycles(cycles),
+        (None, Some(frames), _) => RunLength::Frames(frames),
+        (None, None, Some(_)) => RunLength::Frames(movie.frames()),
+        (None, None, None) => {
+            return Err("headless mode needs --cycles, --frames or --replay".to_string())
+        }
+    };
+
+    let mut headless = Headless::new();
+    headless.set_cycles_per_frame(movie.cycles_per_frame());
+    if args.replay.is_some() {
+        headless.set_key_script(movie.key_script());
+    } else if let Some(file_name) = &args.keys {
+        let script = std::fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+        let script = script.parse::<KeyScript>()?;
+        for event in script.events() {
+            movie.record_key(event.frame, event.key, event.pressed);
+        }
+        headless.set_key_script(script);
+    }
+
+    let summary =
+        headless.run_with_sinks(&mut chip8, length, Some(Box::new(capture)), audio_sink)?;
+    if let Some(mut trace) = chip8.take_trace() {
+        trace.flush()?;
+    }
+    if let Some(file_name) = &args.record {
+        movie.set_frames(summary.frames);
+        std::fs::write(file_name, movie.to_string()).map_err(|e| e.to_string())?;
+    }
+    eprintln!(
+        "Ran {} cycles, {} frames{}",
+        summary.cycles,
+        summary.frames,
+        if summary.exited {
+            ", the program exited"
+        } else {
+            ""
+        }
+    );
+
+    let dump = state_dump(&chip8)?;
+    match &args.dump {
+        Some(file_name) => std::fs::write(file_name, dump).map_err(|e| e.to_string()),
+        None => {
+            print!("{}", dump);
+            Ok(())
+        }
+    }
+}
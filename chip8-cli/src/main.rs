@@ -1,23 +1,281 @@
-use chip8_avsys;
+mod asm;
+mod capture;
+mod config;
+mod disasm;
+mod headless;
+mod repl;
+mod trace;
+
+use chip8_vm::{Driver, Movie, MovieMode, Quirks, RngAlgorithm, VM};
 use std::io::Read;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+
+/// Address where the ROMs are loaded and start running.
+const PROGRAM_LOAD_ADDRESS: u16 = 0x200;
 
 #[derive(Parser)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Without a command the ROM is run.
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a ROM.
+    Run(Box<RunArgs>),
+    /// Print the disassembly listing of a ROM.
+    Disasm(disasm::DisasmArgs),
+    /// Assemble a source file into a ROM and its symbol file.
+    Asm(asm::AsmArgs),
+    /// Find the first instruction where a ROM runs differently under two quirk profiles, or
+    /// differently from a trace file.
+    TraceDiff(trace::TraceDiffArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
     rom_file: String,
-    debug: bool,
+    /// Interpreter quirks profile used to run the ROM, cosmac-vip by default.
+    #[arg(long, value_enum)]
+    quirks: Option<QuirksPreset>,
+    /// Seed of the random number generator, random by default.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Random number generator used by the RND instruction.
+    #[arg(long, value_enum, default_value = "xorshift")]
+    rng: RngPreset,
+    /// Window pixels per CHIP-8 pixel.
+    #[arg(long)]
+    scale: Option<u32>,
+    /// Colour palette of the window: amber, green, white or lcd.
+    #[arg(long)]
+    palette: Option<String>,
+    /// Custom `#RRGGBB` colours of the pixels with no bit plane, the first, the second and both,
+    /// separated by commas.
+    #[arg(long, value_delimiter = ',', conflicts_with = "palette")]
+    colors: Option<Vec<String>>,
+    /// Draw lines between the CHIP-8 pixels.
+    #[arg(long)]
+    grid: bool,
+    /// Start the window in fullscreen.
+    #[arg(long)]
+    fullscreen: bool,
+    /// Configuration file used instead of the global one, see the README.
+    #[arg(long)]
+    config: Option<String>,
+    /// Front-end showing the emulator: an SDL window or the terminal.
+    #[arg(
+        long,
+        value_enum,
+        default_value = "sdl",
+        conflicts_with_all = ["debugger", "headless"]
+    )]
+    frontend: Frontend,
+    /// Save a PNG screenshot of the screen at the end of the run.
+    #[arg(long, conflicts_with = "debugger")]
+    screenshot: Option<String>,
+    /// Record every frame into an animated GIF, or raw RGB24 frames unless the file has the .gif
+    /// extension.
+    #[arg(long, conflicts_with = "debugger")]
+    capture: Option<String>,
+    /// Render the sound to a WAV file, in emulated time so it's the same for every run.
+    #[arg(long, conflicts_with = "debugger")]
+    wav: Option<String>,
+    /// Run the ROM in the interactive debugger instead of the emulator window.
+    #[arg(long)]
+    debugger: bool,
+    /// Instructions executed per 60 Hz frame.
+    #[arg(long, conflicts_with = "ips")]
+    cycles_per_frame: Option<usize>,
+    /// Instructions executed per second, rounded to a whole number of instructions per frame.
+    #[arg(long)]
+    ips: Option<usize>,
+    /// Run without window, audio or keyboard, for a fixed number of cycles or frames.
+    #[arg(long, conflicts_with = "debugger")]
+    headless: bool,
+    /// Instructions executed in headless mode.
+    #[arg(long, requires = "headless", conflicts_with = "frames")]
+    cycles: Option<u64>,
+    /// Frames run in headless mode.
+    #[arg(long, requires = "headless")]
+    frames: Option<u64>,
+    /// Key script for headless mode, one `FRAME KEY down|up` event per line.
+    #[arg(long, requires = "headless")]
+    keys: Option<String>,
+    /// File where headless mode writes the final registers and framebuffer, stdout by default.
+    #[arg(long, requires = "headless")]
+    dump: Option<String>,
+    /// Record the seed, quirks, speed and key input into a movie file.
+    #[arg(long, conflicts_with_all = ["debugger", "replay"])]
+    record: Option<String>,
+    /// Replay a movie file, with the configuration and key input it was recorded with.
+    #[arg(
+        long,
+        conflicts_with_all = ["debugger", "seed", "rng", "quirks", "cycles_per_frame", "ips", "keys"]
+    )]
+    replay: Option<String>,
+    #[command(flatten)]
+    trace: trace::TraceArgs,
+}
+
+impl RunArgs {
+    /// Fill the settings not given on the command line from the configuration files.
+    fn apply_config(&mut self, config: &config::Config) {
+        self.quirks = self.quirks.or(config.quirks);
+        if self.cycles_per_frame.is_none() && self.ips.is_none() {
+            self.cycles_per_frame = config.cycles_per_frame;
+        }
+        self.scale = self.scale.or(config.scale);
+    }
+
+    fn cycles_per_frame(&self) -> usize {
+        match (self.cycles_per_frame, self.ips) {
+            (Some(cycles), _) => cycles.max(1),
+            (None, Some(ips)) => ((ips + 30) / 60).max(1),
+            (None, None) => VM::DEFAULT_CYCLES_PER_FRAME,
+        }
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|time| time.as_nanos() as u64)
+                .unwrap_or_default()
+        })
+    }
+
+    /// The VM configuration of the run: the replayed movie or a new movie with the configuration
+    /// of the arguments.
+    fn movie(&self, rom: &[u8]) -> Result<Movie, String> {
+        match &self.replay {
+            Some(file_name) => {
+                let movie = std::fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+                Ok(movie.parse::<Movie>()?)
+            }
+            None => Ok(Movie::new(
+                rom,
+                self.seed(),
+                self.rng.into(),
+                self.quirks.unwrap_or(QuirksPreset::CosmacVip).into(),
+                self.cycles_per_frame(),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Frontend {
+    Sdl,
+    Tui,
+}
+
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum QuirksPreset {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl From<QuirksPreset> for Quirks {
+    fn from(preset: QuirksPreset) -> Self {
+        match preset {
+            QuirksPreset::CosmacVip => Quirks::COSMAC_VIP,
+            QuirksPreset::Chip48 => Quirks::CHIP48,
+            QuirksPreset::SuperChip => Quirks::SUPER_CHIP,
+            QuirksPreset::XoChip => Quirks::XO_CHIP,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RngPreset {
+    Xorshift,
+    CosmacVip,
+}
+
+impl From<RngPreset> for RngAlgorithm {
+    fn from(preset: RngPreset) -> Self {
+        match preset {
+            RngPreset::Xorshift => RngAlgorithm::Xorshift,
+            RngPreset::CosmacVip => RngAlgorithm::CosmacVip,
+        }
+    }
 }
 
 fn main() -> Result<(), String> {
-    let args = Args::parse();
-    let rom_file_name = args.rom_file;
-    let debug_mode = args.debug;
+    let cli = Cli::parse();
+    match (cli.command, cli.run) {
+        (Some(Command::Run(args)), _) => run(*args),
+        (None, Some(args)) => run(args),
+        (Some(Command::Disasm(args)), _) => disasm::disasm(args),
+        (Some(Command::Asm(args)), _) => asm::asm(args),
+        (Some(Command::TraceDiff(args)), _) => trace::trace_diff(args),
+        // clap requires the ROM file when there is no command.
+        (None, None) => unreachable!(),
+    }
+}
 
-    // Load ROM file
+fn read_rom(rom_file_name: &str) -> Vec<u8> {
     let mut file = std::fs::File::open(rom_file_name).unwrap();
     let mut buf = Vec::new();
     file.read_to_end(&mut buf).expect("read all ROM file");
+    buf
+}
+
+fn run(mut args: RunArgs) -> Result<(), String> {
+    let config = config::Config::load(args.config.as_deref(), &args.rom_file)?;
+    args.apply_config(&config);
+    let mut settings = config.settings(&args.rom_file)?;
+    if let Some(scale) = args.scale {
+        settings.scale = scale;
+    }
+    if let Some(name) = &args.palette {
+        settings.set_palette(name)?;
+    }
+    if let Some(colors) = &args.colors {
+        settings.colors = config::parse_colors(colors)?;
+    }
+    settings.grid |= args.grid;
+    settings.fullscreen |= args.fullscreen;
+    let rom_file_name = &args.rom_file;
+    let rom = read_rom(rom_file_name);
+    let movie = args.movie(&rom)?;
+    let mut chip8 = movie.vm(&rom)?;
+    chip8.set_trace(args.trace.trace()?);
 
-    chip8_avsys::start(buf, debug_mode)
+    if args.debugger {
+        return repl::start(chip8);
+    }
+    let capture = capture::FileCapture::new(
+        &settings,
+        rom_file_name,
+        args.screenshot.clone(),
+        args.capture.as_deref(),
+    )?;
+    let audio_sink = capture::wav_writer(args.wav.as_deref())?;
+    if args.headless {
+        return headless::run(chip8, &args, movie, capture, audio_sink);
+    }
+    let mut driver = Driver::new(movie.cycles_per_frame());
+    driver.set_state_file_prefix(Some(rom_file_name.clone()));
+    driver.set_capture(Some(Box::new(capture)));
+    driver.set_audio_sink(audio_sink);
+    driver.set_movie(match (&args.record, &args.replay) {
+        (Some(file_name), _) => MovieMode::Record(movie, file_name.clone()),
+        (None, Some(_)) => MovieMode::Replay(movie),
+        (None, None) => MovieMode::None,
+    });
+    match args.frontend {
+        Frontend::Sdl => chip8_avsys::start(chip8, driver, &settings),
+        Frontend::Tui => chip8_tui::start(chip8, driver, &config.tui_keymap()?, settings.colors),
+    }
 }
@@ -0,0 +1,377 @@
+use std::io::{BufRead, Write};
+
+use chip8_vm::{
+    disasm::Instruction, ChipKey, Condition, Debugger, StopReason, WatchKind, Watchpoint, VM,
+};
+
+/// Instructions shown before and after the PC in the disassembly.
+const DISASM_INSTRUCTIONS_BEFORE: u16 = 4;
+const DISASM_INSTRUCTIONS_AFTER: u16 = 6;
+
+const HELP: &str = "\
+Commands:
+  s, step [N]         execute the next N instructions (default 1)
+  n, next             execute the next instruction, running over subroutine calls
+  o, out              run until the current subroutine returns
+  c, continue         run until a breakpoint or the end of the program
+  u, until ADDR       run until the PC reaches ADDR
+  b, break ADDR [if COND]
+                      set a breakpoint at ADDR, stopping only when COND holds if given
+  d, delete ADDR      delete the breakpoint at ADDR
+  w, watch ADDR[-END] [read|write|change]
+                      stop when an instruction accesses the memory from ADDR to END
+                      (default change)
+  uw, unwatch N       delete the watchpoint number N
+  bl, breakpoints     list the breakpoints and watchpoints
+  k, key KEY down|up  press or release the CHIP-8 key KEY (0-F)
+  r, regs             show the registers, stack and disassembly around PC
+  h, help             show this help
+  q, quit             exit the debugger
+Addresses are hexadecimal, with or without the 0x prefix. An empty line repeats the last command.
+Conditions compare registers (V0-VF, I, PC, SP, DT, ST), numbers and memory bytes [ADDR], e.g.
+  b 2a0 if V3 == 0x10 && I > 0x300";
+
+/// Runs the ROM under the interactive debugger, reading commands from stdin.
+pub(crate) fn start(mut chip8: VM) -> Result<(), String> {
+    let mut debugger = Debugger::new();
+
+    print_state(&chip8, &debugger)?;
+    let stdin = std::io::stdin();
+    let mut last_command = String::new();
+    loop {
+        print!("(chip8) ");
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
+
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            break;
+        }
+        let line = match line.trim() {
+            "" => last_command.clone(),
+            line => line.to_string(),
+        };
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let arg = words.next();
+
+        let result = match command {
+            "s" | "step" => {
+                let count = match arg.map(str::parse::<usize>) {
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        println!("Invalid instruction count");
+                        continue;
+                    }
+                    None => 1,
+                };
+                let mut result = Ok(StopReason::Step);
+                for _ in 0..count {
+                    result = debugger.step(&mut chip8);
+                    if !matches!(result, Ok(StopReason::Step)) {
+                        break;
+                    }
+                }
+                result
+            }
+            "n" | "next" => debugger.step_over(&mut chip8),
+            "o" | "out" => {
+                if chip8.registers_sp() == 0 {
+                    println!("Not in a subroutine");
+                    continue;
+                }
+                debugger.step_out(&mut chip8)
+            }
+            "c" | "continue" => debugger.cont(&mut chip8),
+            "u" | "until" => match parse_addr(arg) {
+                Some(addr) => debugger.run_to(&mut chip8, addr),
+                None => continue,
+            },
+            "k" | "key" => {
+                let key = arg
+                    .and_then(|arg| u8::from_str_radix(arg, 16).ok())
+                    .and_then(ChipKey::from_value);
+                let Some(key) = key else {
+                    println!("Invalid key, expected 0 to F");
+                    continue;
+                };
+                match words.next() {
+                    Some("down") => chip8.set_key(key, true),
+                    Some("up") => chip8.set_key(key, false),
+                    _ => println!("Expected 'down' or 'up'"),
+                }
+                continue;
+            }
+            "b" | "break" => {
+                let Some(addr) = parse_addr(arg) else {
+                    continue;
+                };
+                match words.next() {
+                    None => {
+                        debugger.add_breakpoint(addr);
+                        println!("Breakpoint set at {:#05X}", addr);
+                    }
+                    Some("if") => {
+                        let source = words.collect::<Vec<&str>>().join(" ");
+                        match source.parse::<Condition>() {
+                            Ok(condition) => {
+                                println!("Breakpoint set at {:#05X} if {}", addr, condition);
+                                debugger.add_conditional_breakpoint(addr, condition);
+                            }
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    Some(word) => println!("Expected 'if' instead of '{}'", word),
+                }
+                continue;
+            }
+            "d" | "delete" => {
+                if let Some(addr) = parse_addr(arg) {
+                    if !debugger.remove_breakpoint(addr) {
+                        println!("No breakpoint at {:#05X}", addr);
+                    }
+                }
+                continue;
+            }
+            "w" | "watch" => {
+                let Some((start, end)) = parse_range(arg) else {
+                    continue;
+                };
+                let kind = match words.next() {
+                    None | Some("change") => WatchKind::Change,
+                    Some("write") => WatchKind::Write,
+                    Some("read") => WatchKind::Read,
+                    Some(word) => {
+                        println!("Invalid watch kind '{}'", word);
+                        continue;
+                    }
+                };
+                debugger.add_watchpoint(Watchpoint::new(start..=end, kind));
+                println!(
+                    "Watchpoint {} set on {:#05X}-{:#05X}",
+                    debugger.watchpoints().len() - 1,
+                    start,
+                    end
+                );
+                continue;
+            }
+            "uw" | "unwatch" => {
+                match arg.map(str::parse::<usize>) {
+                    Some(Ok(index)) => {
+                        if debugger.remove_watchpoint(index).is_none() {
+                            println!("No watchpoint {}", index);
+                        }
+                    }
+                    _ => println!("Invalid watchpoint number"),
+                }
+                continue;
+            }
+            "bl" | "breakpoints" => {
+                for (addr, condition) in debugger.breakpoints() {
+                    match condition {
+                        Some(condition) => println!("{:#05X} if {}", addr, condition),
+                        None => println!("{:#05X}", addr),
+                    }
+                }
+                for (index, watchpoint) in debugger.watchpoints().iter().enumerate() {
+                    println!(
+                        "watch {}: {:#05X}-{:#05X} {:?}",
+                        index,
+                        watchpoint.addrs.start(),
+                        watchpoint.addrs.end(),
+                        watchpoint.kind
+                    );
+                }
+                continue;
+            }
+            "r" | "regs" => {
+                print_state(&chip8, &debugger)?;
+                continue;
+            }
+            "h" | "help" => {
+                println!("{}", HELP);
+                continue;
+            }
+            "q" | "quit" => break,
+            _ => {
+                println!("Unknown command '{}', type 'help' for a list", command);
+                continue;
+            }
+        };
+        last_command = line;
+
+        let reason = match result {
+            Ok(reason) => reason,
+            Err(e) => {
+                // The VM is left at the failing instruction, which can be inspected.
+                println!("Error: {}", e);
+                print_state(&chip8, &debugger)?;
+                continue;
+            }
+        };
+        match reason {
+            StopReason::Step => {}
+            StopReason::Breakpoint(addr) => println!("Breakpoint at {:#05X}", addr),
+            StopReason::Watchpoint(hit) => match hit.kind {
+                WatchKind::Read => println!(
+                    "Watchpoint: {:#05X} read {:#04X} by the instruction at {:#05X}",
+                    hit.addr, hit.new, hit.pc
+                ),
+                WatchKind::Write | WatchKind::Change => println!(
+                    "Watchpoint: {:#05X} written {:#04X} -> {:#04X} by the instruction at {:#05X}",
+                    hit.addr, hit.old, hit.new, hit.pc
+                ),
+            },
+            StopReason::Exit => println!("Program exited"),
+            StopReason::StepLimit => println!("Step limit reached"),
+        }
+        print_state(&chip8, &debugger)?;
+    }
+    Ok(())
+}
+
+fn parse_addr(arg: Option<&str>) -> Option<u16> {
+    let Some(arg) = arg else {
+        println!("Missing address");
+        return None;
+    };
+    let digits = arg.trim_start_matches("0x").trim_start_matches("0X");
+    match u16::from_str_radix(digits, 16) {
+        Ok(addr) => Some(addr),
+        Err(_) => {
+            println!("Invalid address '{}'", arg);
+            None
+        }
+    }
+}
+
+/// Parse an address range `ADDR` or `ADDR-END`.
+fn parse_range(arg: Option<&str>) -> Option<(u16, u16)> {
+    let Some(arg) = arg else {
+        println!("Missing address");
+        return None;
+    };
+    let (start, end) = match arg.split_once('-') {
+        Some((start, end)) => (parse_addr(Some(start))?, parse_addr(Some(end))?),
+        None => {
+            let addr = parse_addr(Some(arg))?;
+            (addr, addr)
+        }
+    };
+    if start > end {
+        println!("Invalid address range '{}'", arg);
+        return None;
+    }
+    Some((start, end))
+}
+
+fn print_state(chip8: &VM, debugger: &Debugger) -> Result<(), String> {
+    for row in 0..2 {
+        let registers = (0..8)
+            .map(|column| {
+                let index = row * 8 + column;
+                format!("V{:X}={:02X}", index, chip8.registers_v(index))
+            })
+            .collect::<Vec<String>>();
+        println!("{}", registers.join(" "));
+    }
+    println!(
+        "I={:#05X} PC={:#05X} SP={} DT={:02X} ST={:02X}",
+        chip8.registers_i(),
+        chip8.registers_pc(),
+        chip8.registers_sp(),
+        chip8.registers_dt(),
+        chip8.registers_st()
+    );
+
+    let stack = (0..chip8.registers_sp())
+        .map(|level| chip8.stack_get(level).map(|addr| format!("{:#05X}", addr)))
+        .collect::<Result<Vec<String>, _>>()?;
+    println!("Stack: [{}]", stack.join(" "));
+
+    for line in listing(chip8, debugger) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Disassembly of the instructions around the PC, marked with `>`, and the breakpoints, marked
+/// with `*`.
+fn listing(chip8: &VM, debugger: &Debugger) -> Vec<String> {
+    let pc = chip8.registers_pc();
+    let word = |addr: u16| chip8.memory_opcode(addr as usize).ok();
+    let mut lines = Vec::new();
+    let mut addr = pc.saturating_sub(DISASM_INSTRUCTIONS_BEFORE * 2);
+    let mut after = 0;
+    while after <= DISASM_INSTRUCTIONS_AFTER {
+        let Some(opcode) = word(addr) else {
+            break;
+        };
+        // The instructions before the PC can't run over it.
+        let next = if addr >= pc || addr + 2 < pc {
+            addr.checked_add(2).and_then(word)
+        } else {
+            None
+        };
+        let instruction = Instruction::decode(opcode, next);
+        let (bytes, text) = match (instruction, next) {
+            (Some(instruction), Some(next)) if instruction.size() == 4 => (
+                format!("{:04X} {:04X}", opcode, next),
+                instruction.to_string(),
+            ),
+            (Some(instruction), _) => (format!("{:04X}", opcode), instruction.to_string()),
+            (None, _) => (format!("{:04X}", opcode), "???".to_string()),
+        };
+        let marker = if addr == pc { '>' } else { ' ' };
+        let breakpoint = if debugger.has_breakpoint(addr) {
+            '*'
+        } else {
+            ' '
+        };
+        lines.push(format!(
+            "{}{} {:#05X}: {:<9}  {}",
+            marker, breakpoint, addr, bytes, text
+        ));
+        if addr >= pc {
+            after += 1;
+        }
+        let size = instruction.map_or(2, |instruction| instruction.size() as u16);
+        let Some(next_addr) = addr.checked_add(size) else {
+            break;
+        };
+        addr = next_addr;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_vm::{Debugger, Quirks, VM};
+
+    use super::listing;
+
+    #[test]
+    fn listing_long_instructions() {
+        // 0x200: LD I, long 0x1234, 0x204: CLS, 0x206: LD I, long 0xFFFF
+        let program = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xF0, 0x00, 0xFF, 0xFF];
+        let mut chip8 = VM::new(Quirks::XO_CHIP);
+        chip8.load_program(&program).unwrap();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x204);
+        debugger.step(&mut chip8).unwrap();
+
+        let lines = listing(&chip8, &debugger);
+        assert_eq!(lines[0], "   0x1FC: 0000       SYS 0x000");
+        assert_eq!(lines[2], "   0x200: F000 1234  LD I, long 0x1234");
+        assert_eq!(lines[3], ">* 0x204: 00E0       CLS");
+        assert_eq!(lines[4], "   0x206: F000 FFFF  LD I, long 0xFFFF");
+        assert_eq!(lines[5], "   0x20A: 0000       SYS 0x000");
+        assert_eq!(lines.len(), 10);
+    }
+}
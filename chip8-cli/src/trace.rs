@@ -0,0 +1,183 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    ops::RangeInclusive,
+};
+
+use chip8_vm::{read_trace, Headless, KeyScript, Movie, RunLength, Trace, TraceFormat, VM};
+use clap::{Args, ValueEnum};
+
+use crate::{read_rom, QuirksPreset, RngPreset};
+
+/// Frames compared by `trace-diff` when no length is given, 10 seconds.
+const DEFAULT_DIFF_FRAMES: u64 = 600;
+
+#[derive(Args)]
+pub(crate) struct TraceArgs {
+    /// Trace the executed instructions to the standard output, as text.
+    #[arg(long, conflicts_with = "trace")]
+    debug: bool,
+    /// Trace the executed instructions into this file.
+    #[arg(long)]
+    trace: Option<String>,
+    /// Trace file format.
+    #[arg(long, value_enum, default_value = "text", requires = "trace")]
+    trace_format: TraceFormatArg,
+    /// Only trace the instructions at these addresses, e.g. `0x200-0x2FF`.
+    #[arg(long, value_parser = parse_range, requires = "trace")]
+    trace_addresses: Option<RangeInclusive<u64>>,
+    /// Only trace the instructions executed in these cycles, counted from the start, e.g. `1000-2000`.
+    #[arg(long, value_parser = parse_range, requires = "trace")]
+    trace_cycles: Option<RangeInclusive<u64>>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TraceFormatArg {
+    Text,
+    Binary,
+}
+
+impl From<TraceFormatArg> for TraceFormat {
+    fn from(format: TraceFormatArg) -> Self {
+        match format {
+            TraceFormatArg::Text => TraceFormat::Text,
+            TraceFormatArg::Binary => TraceFormat::Binary,
+        }
+    }
+}
+
+/// Parse a decimal or `0x` hexadecimal number.
+pub(crate) fn parse_number(number: &str) -> Result<u64, String> {
+    match number.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => number.parse::<u64>(),
+    }
+    .map_err(|_| format!("invalid number '{}'", number))
+}
+
+/// Parse a `START-END` range, both ends included.
+fn parse_range(range: &str) -> Result<RangeInclusive<u64>, String> {
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, found '{}'", range))?;
+    Ok(parse_number(start.trim())?..=parse_number(end.trim())?)
+}
+
+impl TraceArgs {
+    /// The trace requested by the arguments, if any.
+    pub(crate) fn trace(&self) -> Result<Option<Trace>, String> {
+        if self.debug {
+            return Ok(Some(Trace::new(Box::new(io::stdout()), TraceFormat::Text)?));
+        }
+        let Some(file_name) = &self.trace else {
+            return Ok(None);
+        };
+        let file = File::create(file_name).map_err(|e| format!("{}: {}", file_name, e))?;
+        let mut trace = Trace::new(Box::new(BufWriter::new(file)), self.trace_format.into())?;
+        if let Some(addresses) = &self.trace_addresses {
+            let start = (*addresses.start()).min(u16::MAX as u64) as u16;
+            let end = (*addresses.end()).min(u16::MAX as u64) as u16;
+            trace.set_address_range(start..=end);
+        }
+        if let Some(cycles) = &self.trace_cycles {
+            trace.set_cycle_range(*cycles.start()..cycles.end().saturating_add(1));
+        }
+        Ok(Some(trace))
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct TraceDiffArgs {
+    rom_file: String,
+    /// Interpreter quirks profile of the first run.
+    #[arg(long, value_enum, default_value = "cosmac-vip")]
+    quirks: QuirksPreset,
+    /// Interpreter quirks profile of the second run.
+    #[arg(
+        long,
+        value_enum,
+        required_unless_present = "trace",
+        conflicts_with = "trace"
+    )]
+    other_quirks: Option<QuirksPreset>,
+    /// Trace file to compare the run with, e.g. converted from the log of another emulator.
+    #[arg(long)]
+    trace: Option<String>,
+    /// Seed of the random number generator of both runs.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Random number generator used by the RND instruction.
+    #[arg(long, value_enum, default_value = "xorshift")]
+    rng: RngPreset,
+    /// Instructions executed per 60 Hz frame.
+    #[arg(long)]
+    cycles_per_frame: Option<usize>,
+    /// Key script, one `FRAME KEY down|up` event per line.
+    #[arg(long)]
+    keys: Option<String>,
+    /// Movie providing the configuration and the key input of the first run.
+    #[arg(long, conflicts_with_all = ["seed", "rng", "quirks", "cycles_per_frame", "keys"])]
+    replay: Option<String>,
+    /// Instructions compared. A trace file is compared up to its last record.
+    #[arg(long, conflicts_with_all = ["frames", "trace"])]
+    cycles: Option<u64>,
+    /// Frames compared, 600 by default.
+    #[arg(long, conflicts_with = "trace")]
+    frames: Option<u64>,
+}
+
+/// Run the ROM under two configurations, or against a trace file, and print the first
+/// instruction where the runs diverge.
+pub(crate) fn trace_diff(args: TraceDiffArgs) -> Result<(), String> {
+    let rom = read_rom(&args.rom_file);
+    let movie = match &args.replay {
+        Some(file_name) => std::fs::read_to_string(file_name)
+            .map_err(|e| format!("{}: {}", file_name, e))?
+            .parse::<Movie>()?,
+        None => Movie::new(
+            &rom,
+            args.seed,
+            args.rng.into(),
+            args.quirks.into(),
+            args.cycles_per_frame
+                .unwrap_or(VM::DEFAULT_CYCLES_PER_FRAME),
+        ),
+    };
+    let mut headless = Headless::new();
+    headless.set_cycles_per_frame(movie.cycles_per_frame());
+    match &args.keys {
+        Some(file_name) => {
+            let script =
+                std::fs::read_to_string(file_name).map_err(|e| format!("{}: {}", file_name, e))?;
+            headless.set_key_script(script.parse::<KeyScript>()?);
+        }
+        None => headless.set_key_script(movie.key_script()),
+    }
+    let length = match (args.cycles, args.frames, &args.replay) {
+        (Some(cycles), _, _) => RunLength::Cycles(cycles),
+        (None, Some(frames), _) => RunLength::Frames(frames),
+        (None, None, Some(_)) => RunLength::Frames(movie.frames()),
+        (None, None, None) => RunLength::Frames(DEFAULT_DIFF_FRAMES),
+    };
+
+    let mut chip8 = movie.vm(&rom)?;
+    let divergence = match (&args.trace, args.other_quirks) {
+        (Some(file_name), _) => {
+            let trace = std::fs::read(file_name).map_err(|e| format!("{}: {}", file_name, e))?;
+            headless.diff_trace(&mut chip8, &read_trace(&trace)?)?
+        }
+        (None, Some(other_quirks)) => {
+            let mut other = VM::new(other_quirks.into());
+            other.set_rng(movie.rng(), movie.seed());
+            other.load_program(&rom)?;
+            headless.diff(&mut chip8, &mut other, length)?
+        }
+        // clap requires --trace or --other-quirks.
+        (None, None) => unreachable!(),
+    };
+    match divergence {
+        Some(divergence) => println!("First divergence at {}", divergence),
+        None => println!("No divergence"),
+    }
+    Ok(())
+}
@@ -0,0 +1,8 @@
+pub const EMULATOR_TITLE: &str = "Chip-8 Emulator";
+
+/// Columns between the screen and the register panel.
+pub const PANEL_MARGIN: u16 = 2;
+
+/// Frames a key stays pressed after its last press, on terminals that don't report key
+/// releases. Held keys are pressed again by the terminal auto-repeat.
+pub const KEY_HOLD_FRAMES: u32 = 10;
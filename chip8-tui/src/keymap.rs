@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use chip8_vm::ChipKey;
+use crossterm::event::KeyCode;
+
+/// Terminal keys that are not characters, by SDL key name.
+const NAMED_KEYS: [(&str, KeyCode); 14] = [
+    ("Space", KeyCode::Char(' ')),
+    ("Return", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+];
+
+/// SDL names of the keys that terminals don't report, left out of the bindings, besides the
+/// `Keypad ...` keys.
+const UNREPORTED_KEYS: [&str; 15] = [
+    "Left Shift",
+    "Right Shift",
+    "Left Ctrl",
+    "Right Ctrl",
+    "Left Alt",
+    "Right Alt",
+    "Left GUI",
+    "Right GUI",
+    "CapsLock",
+    "Numlock",
+    "ScrollLock",
+    "PrintScreen",
+    "Pause",
+    "Menu",
+    "Application",
+];
+
+/// Mapping of the terminal keys to the CHIP-8 keys. The keys have the SDL names of the window
+/// front-end (`Q`, `Up`, `Space`...), so both front-ends share the bindings of the configuration
+/// files. A CHIP-8 key can be bound to several terminal keys.
+#[derive(Clone, Debug)]
+pub struct KeyMap {
+    keys: HashMap<KeyCode, ChipKey>,
+}
+
+impl Default for KeyMap {
+    /// The default keys of the window, the rows `1234`, `QWER`, `ASDF` and `ZXCV` mapped to
+    /// the rows of `ChipKey::KEYPAD`.
+    fn default() -> Self {
+        let keys = "1234qwerasdfzxcv"
+            .chars()
+            .map(KeyCode::Char)
+            .zip(ChipKey::KEYPAD.iter().flatten().copied())
+            .collect();
+        KeyMap { keys }
+    }
+}
+
+impl KeyMap {
+    /// Bind a CHIP-8 key to the terminal keys with the given SDL names, replacing its previous
+    /// bindings. The names are not case sensitive. Keys that terminals don't report, such as
+    /// `Keypad 5` or `Left Shift`, are left out.
+    pub fn bind(&mut self, key: ChipKey, host_keys: &[String]) -> Result<(), String> {
+        let codes = host_keys
+            .iter()
+            .filter(|name| !is_unreported(name))
+            .map(|name| key_code(name).ok_or_else(|| format!("unknown key name '{}'", name)))
+            .collect::<Result<Vec<KeyCode>, String>>()?;
+        self.keys.retain(|_, chip_key| *chip_key != key);
+        for code in codes {
+            self.keys.insert(code, key);
+        }
+        Ok(())
+    }
+
+    /// CHIP-8 key of a terminal key. Letters are the same key with or without Shift.
+    pub(crate) fn chip_key(&self, code: KeyCode) -> Option<ChipKey> {
+        let code = match code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        self.keys.get(&code).copied()
+    }
+
+    /// Text description of the bindings, the CHIP-8 keypad with the terminal keys of each key.
+    pub(crate) fn describe(&self) -> Vec<String> {
+        ChipKey::KEYPAD
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|key| {
+                        let mut names = self
+                            .keys
+                            .iter()
+                            .filter(|(_, chip_key)| *chip_key == key)
+                            .map(|(code, _)| key_name(*code))
+                            .collect::<Vec<String>>();
+                        names.sort();
+                        format!("{:X}:{}", key.value(), names.join("/"))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+}
+
+/// Terminal key of an SDL key name: a single character, a function key or one of `NAMED_KEYS`.
+fn key_code(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_graphic() => Some(KeyCode::Char(c.to_ascii_lowercase())),
+        _ => function_key(name).or_else(|| {
+            NAMED_KEYS
+                .iter()
+                .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+                .map(|(_, code)| *code)
+        }),
+    }
+}
+
+/// Function key of an SDL key name, `F1` to `F24`.
+fn function_key(name: &str) -> Option<KeyCode> {
+    let number = name.strip_prefix(['F', 'f'])?.parse::<u8>().ok()?;
+    (1..=24).contains(&number).then_some(KeyCode::F(number))
+}
+
+/// Whether the SDL key name is a key that terminals don't report.
+fn is_unreported(name: &str) -> bool {
+    name.get(..7)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("Keypad "))
+        || UNREPORTED_KEYS
+            .iter()
+            .any(|key_name| key_name.eq_ignore_ascii_case(name))
+}
+
+fn key_name(code: KeyCode) -> String {
+    match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+        Some((name, _)) => name.to_string(),
+        None => match code {
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        },
+    }
+}
@@ -0,0 +1,151 @@
+use chip8_vm::ChipKey;
+use crossterm::event::{KeyEvent, KeyEventKind};
+
+use crate::{config::KEY_HOLD_FRAMES, keymap::KeyMap};
+
+/// State of the CHIP-8 keys pressed from the terminal.
+///
+/// Terminals only report key presses, and the auto-repeat of held keys. Unless the terminal
+/// reports key releases too (keyboard enhancement protocol), a pressed key is released after
+/// `KEY_HOLD_FRAMES` frames without being pressed again.
+pub(crate) struct Keypad {
+    keymap: KeyMap,
+    /// Frames left before the emulated release of each key, `None` for released keys.
+    held: [Option<u32>; 16],
+    reports_releases: bool,
+}
+
+impl Keypad {
+    pub(crate) fn new(keymap: KeyMap, reports_releases: bool) -> Self {
+        Keypad {
+            keymap,
+            held: [None; 16],
+            reports_releases,
+        }
+    }
+
+    /// Update the keys with a terminal key event, and return the CHIP-8 key pressed or released.
+    pub(crate) fn key_event(&mut self, event: &KeyEvent) -> Option<(ChipKey, bool)> {
+        let key = self.keymap.chip_key(event.code)?;
+        let held = &mut self.held[key.value() as usize];
+        match event.kind {
+            KeyEventKind::Release => {
+                *held = None;
+                Some((key, false))
+            }
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                let was_pressed = held.is_some();
+                *held = Some(KEY_HOLD_FRAMES);
+                (!was_pressed).then_some((key, true))
+            }
+        }
+    }
+
+    /// Count down the held keys at the end of a frame, and return the released keys.
+    pub(crate) fn tick(&mut self) -> Vec<ChipKey> {
+        if self.reports_releases {
+            return Vec::new();
+        }
+        let mut released = Vec::new();
+        for (value, held) in self.held.iter_mut().enumerate() {
+            match held {
+                Some(0) => {
+                    *held = None;
+                    released.extend(ChipKey::from_value(value as u8));
+                }
+                Some(frames) => *frames = frames.saturating_sub(1),
+                None => {}
+            }
+        }
+        released
+    }
+
+    /// Text description of the bindings, the CHIP-8 keypad with the terminal keys of each key.
+    pub(crate) fn describe(&self) -> Vec<String> {
+        self.keymap.describe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_vm::ChipKey;
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+    use super::Keypad;
+    use crate::{config::KEY_HOLD_FRAMES, keymap::KeyMap};
+
+    fn key(c: char, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent::new_with_kind(KeyCode::Char(c), KeyModifiers::NONE, kind)
+    }
+
+    #[test]
+    fn release_held_keys() {
+        let mut keypad = Keypad::new(KeyMap::default(), false);
+        assert_eq!(
+            keypad.key_event(&key('w', KeyEventKind::Press)),
+            Some((ChipKey::Key5, true))
+        );
+        assert_eq!(keypad.key_event(&key('p', KeyEventKind::Press)), None);
+        for _ in 0..KEY_HOLD_FRAMES {
+            assert!(keypad.tick().is_empty());
+        }
+        // The auto-repeat keeps the key pressed.
+        assert_eq!(keypad.key_event(&key('W', KeyEventKind::Repeat)), None);
+        for _ in 0..KEY_HOLD_FRAMES {
+            assert!(keypad.tick().is_empty());
+        }
+        assert_eq!(keypad.tick(), [ChipKey::Key5]);
+        assert!(keypad.tick().is_empty());
+    }
+
+    #[test]
+    fn reported_releases() {
+        let mut keypad = Keypad::new(KeyMap::default(), true);
+        assert_eq!(
+            keypad.key_event(&key('x', KeyEventKind::Press)),
+            Some((ChipKey::Key0, true))
+        );
+        for _ in 0..=KEY_HOLD_FRAMES {
+            assert!(keypad.tick().is_empty());
+        }
+        assert_eq!(
+            keypad.key_event(&key('x', KeyEventKind::Release)),
+            Some((ChipKey::Key0, false))
+        );
+    }
+
+    #[test]
+    fn configured_bindings() {
+        let mut keymap = KeyMap::default();
+        keymap
+            .bind(
+                ChipKey::Key5,
+                &[
+                    "Up".to_string(),
+                    "space".to_string(),
+                    "Keypad 5".to_string(),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            keymap.bind(ChipKey::Key6, &["F3".to_string(), "Uup".to_string()]),
+            Err("unknown key name 'Uup'".to_string())
+        );
+        keymap
+            .bind(ChipKey::Key6, &["F3".to_string(), "Left Shift".to_string()])
+            .unwrap();
+        let mut keypad = Keypad::new(keymap, false);
+        assert_eq!(keypad.key_event(&key('w', KeyEventKind::Press)), None);
+        assert_eq!(
+            keypad.key_event(&KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)),
+            Some((ChipKey::Key5, true))
+        );
+        // Space presses the same key, already held.
+        assert_eq!(keypad.key_event(&key(' ', KeyEventKind::Press)), None);
+        assert_eq!(
+            keypad.key_event(&KeyEvent::new(KeyCode::F(3), KeyModifiers::NONE)),
+            Some((ChipKey::Key6, true))
+        );
+        assert_eq!(keypad.describe()[1], "4:q 5:Space/Up 6:F3 D:r");
+    }
+}
@@ -0,0 +1,169 @@
+mod config;
+mod keymap;
+mod keypad;
+mod screen;
+
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print};
+use crossterm::terminal::{
+    self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+};
+use crossterm::{cursor, execute, queue};
+
+use crate::config::*;
+use crate::keypad::Keypad;
+use chip8_vm::{Audio, Driver, HostEvent, Input, Video, VM};
+
+pub use crate::keymap::KeyMap;
+
+/// Terminal in raw mode on the alternate screen, restored when dropped, even on errors.
+struct Terminal {
+    out: Stdout,
+    reports_releases: bool,
+}
+
+impl Terminal {
+    fn new() -> Result<Self, String> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+        execute!(
+            out,
+            EnterAlternateScreen,
+            SetTitle(EMULATOR_TITLE),
+            cursor::Hide,
+            Clear(ClearType::All)
+        )
+        .map_err(|e| e.to_string())?;
+        // Terminals supporting the keyboard enhancement protocol report key releases.
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false)
+            && execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )
+            .is_ok();
+        Ok(Terminal {
+            out,
+            reports_releases,
+        })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Terminal host: the screen and the register panel, the bell and the keyboard.
+struct TuiHost {
+    terminal: Terminal,
+    keypad: Keypad,
+    /// Colours for each combination of the XO-CHIP bit planes: none, first, second and both.
+    colors: [Color; 4],
+    screen_width: usize,
+    beeping: bool,
+    /// Last status message, shown in the panel.
+    message: String,
+}
+
+impl Video for TuiHost {
+    fn present(&mut self, chip8: &VM, screen_changed: bool) -> Result<(), String> {
+        let out = &mut self.terminal.out;
+        if chip8.screen_width() != self.screen_width {
+            // The resolution changed, the panel moves.
+            self.screen_width = chip8.screen_width();
+            queue!(out, Clear(ClearType::All)).map_err(|e| e.to_string())?;
+            screen::draw_screen(out, chip8, &self.colors)?;
+        } else if screen_changed {
+            screen::draw_screen(out, chip8, &self.colors)?;
+        }
+        screen::draw_panel(out, chip8, &self.message, &self.keypad)?;
+        out.flush().map_err(|e| e.to_string())
+    }
+
+    fn message(&mut self, text: &str) {
+        self.message = text.to_string();
+    }
+}
+
+impl Audio for TuiHost {
+    fn update(&mut self, _chip8: &VM, playing: bool) -> Result<(), String> {
+        if playing && !self.beeping {
+            execute!(self.terminal.out, Print('\x07')).map_err(|e| e.to_string())?;
+        }
+        self.beeping = playing;
+        Ok(())
+    }
+}
+
+impl Input for TuiHost {
+    fn poll(&mut self, _frame: u64) -> Result<Vec<HostEvent>, String> {
+        let mut events = self
+            .keypad
+            .tick()
+            .into_iter()
+            .map(|key| HostEvent::Key(key, false))
+            .collect::<Vec<HostEvent>>();
+        while event::poll(Duration::ZERO).map_err(|e| e.to_string())? {
+            let Event::Key(key_event) = event::read().map_err(|e| e.to_string())? else {
+                continue;
+            };
+            let pressed = key_event.kind != KeyEventKind::Release;
+            match key_event.code {
+                KeyCode::Esc => events.push(HostEvent::Quit),
+                KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    events.push(HostEvent::Quit)
+                }
+                KeyCode::Char('-') if pressed => events.push(HostEvent::SpeedDown),
+                KeyCode::Char('=' | '+') if pressed => events.push(HostEvent::SpeedUp),
+                KeyCode::F(5) if pressed => events.push(HostEvent::SaveState),
+                KeyCode::F(9) if pressed => events.push(HostEvent::LoadState),
+                KeyCode::F(6) if pressed => events.push(HostEvent::PreviousSlot),
+                KeyCode::F(7) if pressed => events.push(HostEvent::NextSlot),
+                KeyCode::F(12) if pressed => events.push(HostEvent::Screenshot),
+                _ => events.extend(
+                    self.keypad
+                        .key_event(&key_event)
+                        .map(|(key, pressed)| HostEvent::Key(key, pressed)),
+                ),
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Runs the VM, with the ROM already loaded, in the terminal with the `driver` loop. The screen is
+/// drawn with Unicode half blocks next to a panel with the registers and the stack, so the terminal
+/// needs at least 150 columns for the high resolution mode. The speed can be changed at runtime
+/// with the `-` and `=` keys, F5/F9 save and load the quick save states and F6/F7 select the slot,
+/// F12 saves a screenshot. Esc or Ctrl-C quits. The sound is the terminal bell, rung when the sound
+/// timer starts. The keypad is bound to the terminal keys of `keymap`, and the pixels have the
+/// RGB `colors` of the window for each combination of the bit planes.
+pub fn start(
+    mut chip8: VM,
+    mut driver: Driver,
+    keymap: &KeyMap,
+    colors: [[u8; 3]; 4],
+) -> Result<(), String> {
+    let terminal = Terminal::new()?;
+    let mut host = TuiHost {
+        keypad: Keypad::new(keymap.clone(), terminal.reports_releases),
+        colors: colors.map(|[r, g, b]| Color::Rgb { r, g, b }),
+        terminal,
+        screen_width: 0,
+        beeping: false,
+        message: format!("Speed {} per frame (-/=)", driver.cycles_per_frame()),
+    };
+    driver.run(&mut chip8, &mut host)?;
+    Ok(())
+}
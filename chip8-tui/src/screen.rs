@@ -0,0 +1,105 @@
+use std::io::Write;
+
+use chip8_vm::VM;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+
+use crate::{config::PANEL_MARGIN, keypad::Keypad};
+
+/// Draws the VM screen in the terminal with the upper half block character: each character
+/// cell shows two pixels, the top one in the foreground colour and the bottom one in the
+/// background colour. `plane_colors` are the colours of each combination of the XO-CHIP bit
+/// planes: none, first, second and both.
+pub(crate) fn draw_screen(
+    out: &mut impl Write,
+    chip8: &VM,
+    plane_colors: &[Color; 4],
+) -> Result<(), String> {
+    let width = chip8.screen_width();
+    let height = chip8.screen_height();
+    for row in 0..height.div_ceil(2) {
+        queue!(out, MoveTo(0, row as u16)).map_err(|e| e.to_string())?;
+        let mut colors = None;
+        for x in 0..width {
+            let top = chip8.screen_pixel_planes(x, row * 2)?;
+            let bottom = if row * 2 + 1 < height {
+                chip8.screen_pixel_planes(x, row * 2 + 1)?
+            } else {
+                0
+            };
+            if colors != Some((top, bottom)) {
+                queue!(
+                    out,
+                    SetForegroundColor(plane_colors[top as usize]),
+                    SetBackgroundColor(plane_colors[bottom as usize])
+                )
+                .map_err(|e| e.to_string())?;
+                colors = Some((top, bottom));
+            }
+            queue!(out, Print('▀')).map_err(|e| e.to_string())?;
+        }
+        queue!(out, ResetColor).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Draws the registers, the stack, the last status message and the key bindings on the right of
+/// the screen.
+pub(crate) fn draw_panel(
+    out: &mut impl Write,
+    chip8: &VM,
+    message: &str,
+    keypad: &Keypad,
+) -> Result<(), String> {
+    let column = chip8.screen_width() as u16 + PANEL_MARGIN;
+    let mut lines = vec![
+        format!(
+            "PC {:#06X}  I {:#06X}",
+            chip8.registers_pc(),
+            chip8.registers_i()
+        ),
+        format!(
+            "DT {:#04X}    ST {:#04X}",
+            chip8.registers_dt(),
+            chip8.registers_st()
+        ),
+    ];
+    for row in 0..4 {
+        lines.push(
+            (row * 4..row * 4 + 4)
+                .map(|index| format!("V{:X} {:02X}", index, chip8.registers_v(index)))
+                .collect::<Vec<String>>()
+                .join(" "),
+        );
+    }
+    lines.push(format!("SP {}", chip8.registers_sp()));
+    for level in (0..chip8.registers_sp()).rev() {
+        lines.push(format!("  {:#06X}", chip8.stack_get(level)?));
+    }
+    lines.push(String::new());
+    lines.push(message.to_string());
+    lines.push(String::new());
+    lines.extend(keypad.describe());
+    lines.push("Esc quit".to_string());
+
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            out,
+            MoveTo(column, row as u16),
+            Print(line),
+            Clear(ClearType::UntilNewLine)
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    // Clear the lines of the stack entries popped since the last frame.
+    let (_, rows) = terminal::size().map_err(|e| e.to_string())?;
+    for row in lines.len() as u16..rows {
+        queue!(out, MoveTo(column, row), Clear(ClearType::UntilNewLine))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
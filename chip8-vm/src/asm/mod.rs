@@ -0,0 +1,399 @@
+//! CHIP-8 assembler. The syntax is the one of the opcode descriptions and of the disassembler
+//! listings, one statement per line:
+//!
+//! ```text
+//! ; comments start with a semicolon
+//! SPRITE_HEIGHT equ 2          ; constant
+//! start:                       ; label, the address of the next statement
+//!     LD I, sprite
+//!     DRW V0, V1, SPRITE_HEIGHT
+//!     JP start
+//! sprite:
+//!     db 0xFF, 0b10000001      ; bytes
+//!     dw 0x1234                ; big endian words
+//! include "font.asm"           ; path relative to the including file
+//! ```
+//!
+//! Mnemonics, registers and keywords are case insensitive, symbols are not. Numbers are
+//! decimal, `0x` hexadecimal or `0b` binary, and operands can add and subtract numbers and
+//! symbols. The program is assembled at the load address, 0x200.
+
+mod operand;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{CHIP8_MEM_SIZE, CHIP8_PROGRAM_LOAD_ADDRESS},
+    errors::VMError,
+};
+use operand::{build_instruction, is_symbol, Expr, Operand};
+
+/// Maximum nesting of included files, deeper includes are most likely a cycle.
+const INCLUDE_DEPTH_LIMIT: usize = 16;
+
+/// Assembled program, loadable with `VM::load_program`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    /// Label addresses by name.
+    pub symbols: BTreeMap<String, u16>,
+}
+
+impl Program {
+    /// Symbol file contents, one `address name` line per label sorted by address.
+    pub fn symbol_file(&self) -> String {
+        let mut symbols = self.symbols.iter().collect::<Vec<(&String, &u16)>>();
+        symbols.sort_by_key(|(name, addr)| (**addr, name.as_str()));
+        symbols
+            .into_iter()
+            .map(|(name, addr)| format!("{:#05X} {}\n", addr, name))
+            .collect()
+    }
+}
+
+/// Source line, after the includes are expanded.
+struct SourceLine {
+    file: String,
+    number: usize,
+    text: String,
+}
+
+enum Statement {
+    Instruction(String, Vec<Operand>),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => {
+                if operands
+                    .iter()
+                    .any(|operand| matches!(operand, Operand::Long(_)))
+                {
+                    4
+                } else {
+                    2
+                }
+            }
+            Statement::Bytes(values) => values.len(),
+            Statement::Words(values) => values.len() * 2,
+        }
+    }
+}
+
+/// Assemble a program source, included files are looked up from the current directory.
+pub fn assemble(source: &str) -> Result<Program, VMError> {
+    let mut lines = Vec::new();
+    expand(source, "<source>", Path::new(""), 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+/// Assemble a program source file.
+pub fn assemble_file(path: impl AsRef<Path>) -> Result<Program, VMError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|err| VMError::InvalidAssembly(path.display().to_string(), 0, err.to_string()))?;
+    let mut lines = Vec::new();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    expand(&source, &path.display().to_string(), dir, 0, &mut lines)?;
+    assemble_lines(&lines)
+}
+
+/// Strip the comment of a line, ignoring semicolons inside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Split the leading word of a statement from the rest.
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+/// Append the lines of `source` to `lines`, replacing the includes with the included files.
+fn expand(
+    source: &str,
+    file: &str,
+    dir: &Path,
+    depth: usize,
+    lines: &mut Vec<SourceLine>,
+) -> Result<(), VMError> {
+    for (index, text) in source.lines().enumerate() {
+        let text = strip_comment(text).trim();
+        let (word, rest) = split_word(text);
+        if !word.eq_ignore_ascii_case("include") {
+            lines.push(SourceLine {
+                file: file.to_string(),
+                number: index + 1,
+                text: text.to_string(),
+            });
+            continue;
+        }
+
+        let error = |reason: String| VMError::InvalidAssembly(file.to_string(), index + 1, reason);
+        let name = rest
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .ok_or_else(|| error("expected a quoted file name".to_string()))?;
+        if depth >= INCLUDE_DEPTH_LIMIT {
+            return Err(error(format!("too many nested includes at '{}'", name)));
+        }
+        let path: PathBuf = dir.join(name);
+        let included = fs::read_to_string(&path)
+            .map_err(|err| error(format!("cannot include '{}': {}", path.display(), err)))?;
+        let included_dir = path.parent().unwrap_or(Path::new(""));
+        expand(
+            &included,
+            &path.display().to_string(),
+            included_dir,
+            depth + 1,
+            lines,
+        )?;
+    }
+    Ok(())
+}
+
+fn assemble_lines(lines: &[SourceLine]) -> Result<Program, VMError> {
+    let origin = CHIP8_PROGRAM_LOAD_ADDRESS;
+    let mut symbols: BTreeMap<String, i64> = BTreeMap::new();
+    let mut labels: BTreeMap<String, u16> = BTreeMap::new();
+    let mut statements: Vec<(&SourceLine, Statement)> = Vec::new();
+
+    // First pass: parse the statements and give their addresses to the labels.
+    let mut addr = origin;
+    for line in lines.iter() {
+        let error =
+            |reason: String| VMError::InvalidAssembly(line.file.clone(), line.number, reason);
+        let define = |symbols: &mut BTreeMap<String, i64>, name: &str, value: i64| {
+            if !is_symbol(name) {
+                return Err(error(format!("invalid symbol name '{}'", name)));
+            }
+            if symbols.insert(name.to_string(), value).is_some() {
+                return Err(error(format!("symbol '{}' is already defined", name)));
+            }
+            Ok(())
+        };
+
+        let mut text = line.text.as_str();
+        while let Some((name, rest)) = text.split_once(':') {
+            let name = name.trim();
+            if name.contains(char::is_whitespace) {
+                break;
+            }
+            define(&mut symbols, name, addr as i64)?;
+            labels.insert(name.to_string(), addr as u16);
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let (word, rest) = split_word(text);
+        let (directive, _) = split_word(rest);
+        if directive.eq_ignore_ascii_case("equ") {
+            let (_, expr) = split_word(rest);
+            let value = Expr::parse(expr)
+                .and_then(|expr| expr.eval(&symbols))
+                .map_err(error)?;
+            define(&mut symbols, word, value)?;
+            continue;
+        }
+
+        let operands = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(str::trim).collect::<Vec<&str>>()
+        };
+        let statement = match word.to_ascii_lowercase().as_str() {
+            "db" | "dw" => {
+                let values = operands
+                    .iter()
+                    .map(|operand| Expr::parse(operand))
+                    .collect::<Result<Vec<Expr>, String>>()
+                    .map_err(error)?;
+                if values.is_empty() {
+                    return Err(error(format!("'{}' expects at least one value", word)));
+                }
+                if word.eq_ignore_ascii_case("db") {
+                    Statement::Bytes(values)
+                } else {
+                    Statement::Words(values)
+                }
+            }
+            _ => {
+                let operands = operands
+                    .iter()
+                    .map(|operand| Operand::parse(operand))
+                    .collect::<Result<Vec<Operand>, String>>()
+                    .map_err(error)?;
+                Statement::Instruction(word.to_string(), operands)
+            }
+        };
+        addr += statement.size();
+        if addr >= CHIP8_MEM_SIZE {
+            return Err(VMError::ProgramSizeOverflow);
+        }
+        statements.push((line, statement));
+    }
+
+    // Second pass: every symbol is known, encode the statements.
+    let mut bytes = Vec::with_capacity(addr - origin);
+    for (line, statement) in statements.iter() {
+        let error =
+            |reason: String| VMError::InvalidAssembly(line.file.clone(), line.number, reason);
+        let value = |expr: &Expr, min: i64, max: i64| {
+            let value = expr.eval(&symbols).map_err(error)?;
+            if value < min || value > max {
+                return Err(error(format!("value {} is out of range", value)));
+            }
+            Ok(value)
+        };
+        match statement {
+            Statement::Instruction(mnemonic, operands) => {
+                let instruction = build_instruction(mnemonic, operands, &symbols).map_err(error)?;
+                bytes.extend(instruction.encode());
+            }
+            Statement::Bytes(values) => {
+                for expr in values.iter() {
+                    bytes.push(value(expr, i8::MIN as i64, u8::MAX as i64)? as u8);
+                }
+            }
+            Statement::Words(values) => {
+                for expr in values.iter() {
+                    let word = value(expr, i16::MIN as i64, u16::MAX as i64)? as u16;
+                    bytes.extend(word.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    Ok(Program {
+        origin: origin as u16,
+        bytes,
+        symbols: labels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::{assemble, assemble_file};
+    use crate::{disasm::disassemble, Quirks, VM};
+
+    #[test]
+    fn labels_constants_and_data() {
+        let source = "
+            HEIGHT equ 2
+            start:  LD I, sprite     ; forward reference
+                    DRW V0, V1, HEIGHT
+            loop:   ld v2, k
+                    JP loop
+            sprite: db 0xFF, 0b10000001, -1
+                    dw 0x1234, start + 2
+        ";
+        let program = assemble(source).unwrap();
+
+        assert_eq!(
+            program.bytes,
+            [
+                0xA2, 0x08, 0xD0, 0x12, 0xF2, 0x0A, 0x12, 0x04, 0xFF, 0x81, 0xFF, 0x12, 0x34, 0x02,
+                0x02
+            ]
+        );
+        assert_eq!(program.symbols.get("sprite"), Some(&0x208));
+        assert_eq!(
+            program.symbol_file(),
+            "0x200 start\n0x204 loop\n0x208 sprite\n"
+        );
+        let mut vm = VM::new(Quirks::default());
+        assert!(vm.load_program(&program.bytes).is_ok());
+    }
+
+    #[test]
+    fn long_and_register_range_operands() {
+        let program =
+            assemble("LD I, long 0x1234\nLD [I], V1-V3\nLD V0-V5, R\nLD Vf, [I]").unwrap();
+        assert_eq!(
+            program.bytes,
+            [0xF0, 0x00, 0x12, 0x34, 0x51, 0x32, 0xF5, 0x85, 0xFF, 0x65]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let message = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(
+            message("CLS\nJP nowhere"),
+            "<source>:2: undefined symbol 'nowhere'"
+        );
+        assert_eq!(
+            message("a: CLS\na: RET"),
+            "<source>:2: symbol 'a' is already defined"
+        );
+        assert_eq!(
+            message("LD V0, 0x100"),
+            "<source>:1: value 256 does not fit in 8 bits"
+        );
+        assert_eq!(
+            message("ADD V0, I"),
+            "<source>:1: invalid instruction or operands for 'ADD'"
+        );
+        assert_eq!(
+            message("include \"missing.asm\"").split(':').next(),
+            Some("<source>")
+        );
+    }
+
+    #[test]
+    fn includes() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(
+            dir.join("main.asm"),
+            "JP end\ninclude \"lib/data.asm\"\nend: EXIT",
+        )
+        .unwrap();
+        fs::write(dir.join("lib/data.asm"), "data: db 1, 2").unwrap();
+        fs::write(dir.join("lib/self.asm"), "include \"self.asm\"").unwrap();
+
+        let program = assemble_file(dir.join("main.asm")).unwrap();
+        assert_eq!(program.bytes, [0x12, 0x04, 0x01, 0x02, 0x00, 0xFD]);
+        assert_eq!(program.symbols.get("data"), Some(&0x202));
+        assert!(assemble_file(dir.join("lib/self.asm"))
+            .unwrap_err()
+            .to_string()
+            .contains("too many nested includes"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn round_trip_disassembled_roms() {
+        let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("../chip8-roms");
+        for entry in fs::read_dir(roms).unwrap() {
+            let path = entry.unwrap().path();
+            let rom = fs::read(&path).unwrap();
+            let listing = disassemble(&rom, 0x200).to_string();
+            let program = assemble(&listing).unwrap();
+            assert_eq!(program.bytes, rom, "{}", path.display());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use crate::disasm::Instruction;
+
+/// Numeric expression: a sum of numbers and symbols, e.g. `sprites + 5` or `-1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Expr {
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+/// Instruction operand, the keywords are case insensitive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Operand {
+    /// `Vx`
+    V(u8),
+    /// `Vx-Vy`
+    VRange(u8, u8),
+    /// `I`
+    I,
+    /// `[I]`
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Hf,
+    R,
+    Audio,
+    Pitch,
+    /// `long expr`, the 16 bits address of `LD I, long addr`.
+    Long(Expr),
+    Value(Expr),
+}
+
+impl Expr {
+    pub(crate) fn parse(text: &str) -> Result<Expr, String> {
+        let mut terms = Vec::new();
+        let mut negative = false;
+        let mut term = String::new();
+        let mut push_term = |term: &mut String, negative: bool| -> Result<(), String> {
+            let word = term.trim();
+            if word.is_empty() {
+                return Err(format!("invalid expression '{}'", text.trim()));
+            }
+            terms.push((negative, parse_term(word)?));
+            term.clear();
+            Ok(())
+        };
+        for c in text.chars() {
+            match c {
+                '+' | '-' if term.trim().is_empty() => negative ^= c == '-',
+                '+' | '-' => {
+                    push_term(&mut term, negative)?;
+                    negative = c == '-';
+                }
+                _ => term.push(c),
+            }
+        }
+        push_term(&mut term, negative)?;
+        Ok(Expr { terms })
+    }
+
+    pub(crate) fn eval(&self, symbols: &BTreeMap<String, i64>) -> Result<i64, String> {
+        let mut value: i64 = 0;
+        for (negative, term) in self.terms.iter() {
+            let term = match term {
+                Term::Number(number) => *number,
+                Term::Symbol(name) => *symbols
+                    .get(name)
+                    .ok_or_else(|| format!("undefined symbol '{}'", name))?,
+            };
+            value = if *negative {
+                value.wrapping_sub(term)
+            } else {
+                value.wrapping_add(term)
+            };
+        }
+        Ok(value)
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, String> {
+    let lower = word.to_ascii_lowercase();
+    let number = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse::<i64>()
+    } else if is_symbol(word) {
+        return Ok(Term::Symbol(word.to_string()));
+    } else {
+        return Err(format!("invalid operand '{}'", word));
+    };
+    number
+        .map(Term::Number)
+        .map_err(|_| format!("invalid number '{}'", word))
+}
+
+/// Symbol names start with a letter, `_` or `.` and go on with letters, digits, `_` or `.`,
+/// and can't be a register or keyword.
+pub(crate) fn is_symbol(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && parse_keyword(word).is_none()
+        && !word.eq_ignore_ascii_case("long")
+}
+
+fn parse_register(word: &str) -> Option<u8> {
+    let index = word.strip_prefix(['V', 'v'])?;
+    if index.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(index, 16).ok()
+}
+
+fn parse_keyword(word: &str) -> Option<Operand> {
+    let operand = match word.to_ascii_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        "HF" => Operand::Hf,
+        "R" => Operand::R,
+        "AUDIO" => Operand::Audio,
+        "PITCH" => Operand::Pitch,
+        _ => match word.split_once('-') {
+            Some((from, to)) => {
+                Operand::VRange(parse_register(from.trim())?, parse_register(to.trim())?)
+            }
+            None => Operand::V(parse_register(word)?),
+        },
+    };
+    Some(operand)
+}
+
+impl Operand {
+    pub(crate) fn parse(text: &str) -> Result<Operand, String> {
+        let text = text.trim();
+        if let Some(operand) = parse_keyword(text) {
+            return Ok(operand);
+        }
+        match text.split_once(char::is_whitespace) {
+            Some((keyword, addr)) if keyword.eq_ignore_ascii_case("long") => {
+                Ok(Operand::Long(Expr::parse(addr)?))
+            }
+            _ => Ok(Operand::Value(Expr::parse(text)?)),
+        }
+    }
+}
+
+/// Evaluate an expression and check that it fits in `bits` bits, negative values are taken as
+/// two's complement.
+fn eval_bits(expr: &Expr, symbols: &BTreeMap<String, i64>, bits: u32) -> Result<u16, String> {
+    let value = expr.eval(symbols)?;
+    let max = (1i64 << bits) - 1;
+    let min = -(1i64 << (bits - 1));
+    if value < min || value > max {
+        return Err(format!("value {} does not fit in {} bits", value, bits));
+    }
+    Ok((value & max) as u16)
+}
+
+/// Build the instruction for a mnemonic and its operands.
+pub(crate) fn build_instruction(
+    mnemonic: &str,
+    operands: &[Operand],
+    symbols: &BTreeMap<String, i64>,
+) -> Result<Instruction, String> {
+    use Operand::*;
+
+    let addr = |expr: &Expr| eval_bits(expr, symbols, 12);
+    let byte = |expr: &Expr| eval_bits(expr, symbols, 8).map(|value| value as u8);
+    let nibble = |expr: &Expr| eval_bits(expr, symbols, 4).map(|value| value as u8);
+
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let instruction = match (mnemonic.as_str(), operands) {
+        ("CLS", []) => Instruction::Cls,
+        ("RET", []) => Instruction::Ret,
+        ("SCD", [Value(n)]) => Instruction::Scd(nibble(n)?),
+        ("SCR", []) => Instruction::Scr,
+        ("SCL", []) => Instruction::Scl,
+        ("EXIT", []) => Instruction::Exit,
+        ("LOW", []) => Instruction::Low,
+        ("HIGH", []) => Instruction::High,
+        ("SYS", [Value(a)]) => Instruction::Sys(addr(a)?),
+        ("JP", [Value(a)]) => Instruction::Jp(addr(a)?),
+        ("JP", [V(0), Value(a)]) => Instruction::JpV0Addr(addr(a)?),
+        ("CALL", [Value(a)]) => Instruction::Call(addr(a)?),
+        ("SE", [V(x), Value(kk)]) => Instruction::SeVxByte(*x, byte(kk)?),
+        ("SE", [V(x), V(y)]) => Instruction::SeVxVy(*x, *y),
+        ("SNE", [V(x), Value(kk)]) => Instruction::SneVxByte(*x, byte(kk)?),
+        ("SNE", [V(x), V(y)]) => Instruction::SneVxVy(*x, *y),
+        ("LD", [IndirectI, VRange(x, y)]) => Instruction::LdIVxVy(*x, *y),
+        ("LD", [VRange(x, y), IndirectI]) => Instruction::LdVxVyI(*x, *y),
+        ("LD", [V(x), Value(kk)]) => Instruction::LdVxByte(*x, byte(kk)?),
+        ("LD", [V(x), V(y)]) => Instruction::LdVxVy(*x, *y),
+        ("LD", [I, Value(a)]) => Instruction::LdIAddr(addr(a)?),
+        ("LD", [I, Long(a)]) => Instruction::LdILong(eval_bits(a, symbols, 16)?),
+        ("LD", [Audio, IndirectI]) => Instruction::LdAudioI,
+        ("LD", [V(x), Dt]) => Instruction::LdVxDt(*x),
+        ("LD", [V(x), K]) => Instruction::LdVxK(*x),
+        ("LD", [Dt, V(x)]) => Instruction::LdDtVx(*x),
+        ("LD", [St, V(x)]) => Instruction::LdStVx(*x),
+        ("LD", [F, V(x)]) => Instruction::LdFVx(*x),
+        ("LD", [Hf, V(x)]) => Instruction::LdHfVx(*x),
+        ("LD", [B, V(x)]) => Instruction::LdBVx(*x),
+        ("LD", [Pitch, V(x)]) => Instruction::LdPitchVx(*x),
+        ("LD", [IndirectI, V(x)]) => Instruction::LdIVx(*x),
+        ("LD", [V(x), IndirectI]) => Instruction::LdVxI(*x),
+        ("LD", [R, VRange(0, x)]) => Instruction::LdRVx(*x),
+        ("LD", [VRange(0, x), R]) => Instruction::LdVxR(*x),
+        ("ADD", [V(x), Value(kk)]) => Instruction::AddVxByte(*x, byte(kk)?),
+        ("ADD", [V(x), V(y)]) => Instruction::AddVxVy(*x, *y),
+        ("ADD", [I, V(x)]) => Instruction::AddIVx(*x),
+        ("OR", [V(x), V(y)]) => Instruction::OrVxVy(*x, *y),
+        ("AND", [V(x), V(y)]) => Instruction::AndVxVy(*x, *y),
+        ("XOR", [V(x), V(y)]) => Instruction::XorVxVy(*x, *y),
+        ("SUB", [V(x), V(y)]) => Instruction::SubVxVy(*x, *y),
+        ("SUBN", [V(x), V(y)]) => Instruction::SubnVxVy(*x, *y),
+        ("SHR", [V(x)]) => Instruction::ShrVxVy(*x, 0),
+        ("SHR", [V(x), V(y)]) => Instruction::ShrVxVy(*x, *y),
+        ("SHL", [V(x)]) => Instruction::ShlVxVy(*x, 0),
+        ("SHL", [V(x), V(y)]) => Instruction::ShlVxVy(*x, *y),
+        ("RND", [V(x), Value(kk)]) => Instruction::RndVxByte(*x, byte(kk)?),
+        ("DRW", [V(x), V(y), Value(n)]) => Instruction::Drw(*x, *y, nibble(n)?),
+        ("SKP", [V(x)]) => Instruction::SkpVx(*x),
+        ("SKNP", [V(x)]) => Instruction::SknpVx(*x),
+        ("PLANE", [Value(n)]) => Instruction::Plane(nibble(n)?),
+        _ => {
+            return Err(format!(
+                "invalid instruction or operands for '{}'",
+                mnemonic
+            ))
+        }
+    };
+    Ok(instruction)
+}
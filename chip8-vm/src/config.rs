@@ -1,8 +1,29 @@
-pub(crate) const CHIP8_MEM_SIZE: usize = 4096;
+pub(crate) const CHIP8_MEM_SIZE: usize = 0x10000;
 pub(crate) const CHIP8_MEM_RESEVED_LIMIT: usize = 0x1ff;
 pub(crate) const CHIP8_TOTAL_STACK_DEPTH: usize = 16;
 pub(crate) const CHIP8_PROGRAM_LOAD_ADDRESS: usize = 0x200;
 pub(crate) const CHIP8_TOTAL_KEYS: usize = 16;
 pub(crate) const CHIP8_SCREEN_WIDTH: usize = 64;
 pub(crate) const CHIP8_SCREEN_HEIGHT: usize = 32;
+pub(crate) const CHIP8_HIRES_SCREEN_WIDTH: usize = 128;
+pub(crate) const CHIP8_HIRES_SCREEN_HEIGHT: usize = 64;
+pub(crate) const CHIP8_BIG_CHARSET_ADDRESS: usize = 0x050;
+pub(crate) const CHIP8_TOTAL_RPL_FLAGS: usize = 8;
+pub(crate) const CHIP8_TOTAL_PLANES: usize = 2;
+pub(crate) const CHIP8_AUDIO_PATTERN_SIZE: usize = 16;
+pub(crate) const CHIP8_AUDIO_DEFAULT_PITCH: u8 = 64;
+pub(crate) const CHIP8_AUDIO_BUZZER_FREQUENCY: f32 = 440.0;
+pub(crate) const CHIP8_STATE_MAGIC: &[u8; 4] = b"C8ST";
+pub(crate) const CHIP8_STATE_VERSION: u8 = 6;
+pub(crate) const CHIP8_TRACE_MAGIC: &[u8; 4] = b"C8TR";
+pub(crate) const CHIP8_TRACE_VERSION: u8 = 1;
+pub(crate) const CHIP8_DEBUGGER_STEP_LIMIT: usize = 10_000_000;
+pub(crate) const CHIP8_DEFAULT_CYCLES_PER_FRAME: usize = 10;
 pub(crate) const CHIP8_TOTAL_STANDARD_OPCODES: usize = 35;
+pub(crate) const CHIP8_TOTAL_SUPER_CHIP_OPCODES: usize = 10;
+pub(crate) const CHIP8_TOTAL_XO_CHIP_OPCODES: usize = 6;
+pub(crate) const CHIP8_FRAME_MICROS: u64 = 16_667;
+pub(crate) const CHIP8_SPEED_STEP: usize = 2;
+pub(crate) const CHIP8_MAX_CYCLES_PER_FRAME: usize = 1000;
+pub(crate) const CHIP8_TOTAL_STATE_SLOTS: u8 = 10;
+pub(crate) const CHIP8_REWIND_FRAMES: usize = 600;
@@ -1,8 +1,11 @@
 mod opcodes;
 mod registers;
+mod rng;
 mod stack;
 
 pub use opcodes::Signal;
 pub(crate) use opcodes::{VMContext, OPCODES};
 pub(crate) use registers::Registers;
+pub(crate) use rng::Rng;
+pub use rng::RngAlgorithm;
 pub(crate) use stack::Stack;
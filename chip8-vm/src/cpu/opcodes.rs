@@ -1,13 +1,15 @@
-use std::cell::Cell;
-
 use crate::{
-    config::CHIP8_TOTAL_STANDARD_OPCODES,
+    config::{
+        CHIP8_AUDIO_PATTERN_SIZE, CHIP8_BIG_CHARSET_ADDRESS, CHIP8_TOTAL_STANDARD_OPCODES,
+        CHIP8_TOTAL_SUPER_CHIP_OPCODES, CHIP8_TOTAL_XO_CHIP_OPCODES,
+    },
     errors::VMError,
-    io::{Keyboard, Screen},
+    io::{Audio, Keyboard, Screen},
     memory::RAM,
+    quirks::Quirks,
 };
 
-use super::{Registers, Stack};
+use super::{Registers, Rng, Stack};
 
 /// Get the Vx index (0x0x00) from the opcode.
 macro_rules! vx_index {
@@ -66,6 +68,7 @@ pub enum Signal {
     NoSignal,
     DrawScreen,
     WaitKeyUp(u8),
+    Exit,
 }
 
 /// The `VMContext` represent the current state of the VM that the instructions of an opcode
@@ -77,23 +80,22 @@ pub struct VMContext<'a> {
     pub registers: &'a mut Registers,
     pub keyboard: &'a Keyboard,
     pub screen: &'a mut Screen,
-    pub pattern: &'a str,
+    pub audio: &'a mut Audio,
+    pub quirks: &'a Quirks,
+    pub rng: &'a mut Rng,
+    pub waiting_vblank: &'a mut bool,
+    pub waiting_keyup: &'a mut Option<u8>,
 }
 
 /// Opcode instructions function signature.
 type OpcodeInstructions = fn(cxt: &mut VMContext) -> Result<Signal, VMError>;
 
-/// Opcode dump function signature.
-type OpcodeDump = fn(ctx: &VMContext);
-
 /// The `OpcodeMatcher` holds a mapping between the matching info for a binary opcode and the
-/// opcode instructions. Also holds the debugging functions for the opcode.
+/// opcode instructions.
 pub struct OpcodeMatcher {
     bitmask: u16,
     match_value: u16,
     instructions: OpcodeInstructions,
-    pre_ex_dump: OpcodeDump,
-    post_ex_dump: OpcodeDump,
     desc: &'static str,
 }
 
@@ -107,66 +109,94 @@ impl OpcodeMatcher {
         self.instructions
     }
 
-    pub fn pre_ex_dump(&self) -> OpcodeDump {
-        self.pre_ex_dump
-    }
-
-    pub fn post_ex_dump(&self) -> OpcodeDump {
-        self.post_ex_dump
-    }
-
-    pub fn desc(&self) -> &'static str {
-        self.desc
+    /// Returns the instruction mnemonic with the operands of the opcode, e.g. `LD V3, 0x10`.
+    pub fn mnemonic(&self, binary_opcode: u16) -> String {
+        let (pattern, desc) = self.desc.split_once(';').unwrap_or(("", self.desc));
+        // The nibble operand is the lowest nibble except for Fn01.
+        let nibble_shift = 4 * (3 - pattern.find('n').unwrap_or(3).min(3));
+        desc.replace("Vx", &format!("V{:X}", (binary_opcode & 0x0F00) >> 8))
+            .replace("Vy", &format!("V{:X}", (binary_opcode & 0x00F0) >> 4))
+            .replace("addr", &format!("{:#05X}", binary_opcode & 0x0FFF))
+            .replace("byte", &format!("{:#04X}", binary_opcode & 0x00FF))
+            .replace(
+                "nibble",
+                &format!("{}", (binary_opcode >> nibble_shift) & 0x000F),
+            )
     }
 }
 
 /// Opcode matchers constant table, sets the configuration for each of the matchers.
-#[rustfmt::skip] const CLS:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00E0, instructions: cls,          pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "00E0;CLS",                         };
-#[rustfmt::skip] const RET:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00EE, instructions: ret,          pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "00EE;RET",                         };
-#[rustfmt::skip] const SYS:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x0000, instructions: sys,          pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "0nnn;SYS addr",                    };
-#[rustfmt::skip] const JP:           OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x1000, instructions: jp,           pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "1nnn;JP addr",                     };
-#[rustfmt::skip] const CALL:         OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x2000, instructions: call,         pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "2nnn;CALL addr",                   };
-#[rustfmt::skip] const SE_VX_BYTE:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x3000, instructions: se_vx_kk,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "3xkk;SE Vx, byte",                 };
-#[rustfmt::skip] const SNE_VX_KK:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x4000, instructions: sne_vx_kk,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "4xkk;SNE Vx, byte",                };
-#[rustfmt::skip] const SE_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x5000, instructions: se_vx_vy,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "5xy0;SE Vx, Vy",                   };
-#[rustfmt::skip] const LD_VX_BYTE:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x6000, instructions: ld_vx_kk,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "6xkk;LD Vx, byte",                 };
-#[rustfmt::skip] const ADD_VX_BYTE:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x7000, instructions: add_vx_kk,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "7xkk;ADD Vx, byte",                };
-#[rustfmt::skip] const LD_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8000, instructions: ld_vx_vy,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy0;LD Vx, Vy",                   };
-#[rustfmt::skip] const OR_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8001, instructions: or_vx_vy,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy1;OR Vx, Vy",                   };
-#[rustfmt::skip] const AND_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8002, instructions: and_vx_vy,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy2;AND Vx, Vy",                  };
-#[rustfmt::skip] const XOR_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8003, instructions: xor_vx_vy,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy3;XOR Vx, Vy",                  };
-#[rustfmt::skip] const ADD_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8004, instructions: add_vx_vy,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy4;ADD Vx, Vy",                  };
-#[rustfmt::skip] const SUB_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8005, instructions: sub_vx_vy,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy5;SUB Vx, Vy",                  };
-#[rustfmt::skip] const SHR_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8006, instructions: shr_vx,       pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy6;SHR Vx",                      };
-#[rustfmt::skip] const SUBN_VX_VY:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8007, instructions: subn_vx_vy,   pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xy7;SUBN Vx, Vy",                 };
-#[rustfmt::skip] const SHL_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x800E, instructions: shl_vx,       pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "8xyE;SHL Vx",                      };
-#[rustfmt::skip] const SNE_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x9000, instructions: sne_vx_vy,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "9xy0;SNE Vx, Vy ",                 };
-#[rustfmt::skip] const LD_I_ADDR:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xA000, instructions: ld_i_addr,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Annn;LD I, addr",                  };
-#[rustfmt::skip] const JP_V0_ADDR:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xB000, instructions: jp_v0_addr,   pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Bnnn;JP V0, addr",                 };
-#[rustfmt::skip] const RND_VX_BYTE:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xC000, instructions: rnd_vx_byte,  pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Cxkk;RND Vx, byte",                };
-#[rustfmt::skip] const DRW_VX_VY_NB: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xD000, instructions: drw_vx_vy_nb, pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Dxyn;DRW Vx, Vy, nibble",          };
-#[rustfmt::skip] const SKP_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xE09E, instructions: skp_vx,       pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Ex9E;SKP Vx",                      };
-#[rustfmt::skip] const SKNP_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xE0A1, instructions: sknp_vx,      pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "ExA1;SKNP Vx",                     };
-#[rustfmt::skip] const LD_VX_DTIMER: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF007, instructions: ld_vx_dt,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx07;LD Vx, DT",                   };
-#[rustfmt::skip] const LD_VX_K:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF00A, instructions: ld_vx_key,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx0A;LD Vx, K",                    };
-#[rustfmt::skip] const LD_DTIMER_VX: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF015, instructions: ld_dt_vx,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx15;LD DT, Vx,",                  };
-#[rustfmt::skip] const LD_STIMER_VX: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF018, instructions: ld_st_vx,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx18;LD ST, Vx",                   };
-#[rustfmt::skip] const ADD_I_VX:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF01E, instructions: add_i_vx,     pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx1E;ADD I, Vx",                   };
-#[rustfmt::skip] const LD_F_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF029, instructions: ld_f_vx,      pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx29;LD I, Vx (hex sprite value)", };
-#[rustfmt::skip] const LD_BCD_VX:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF033, instructions: ld_bcd_vx,    pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx33;LD [I] (BDC of Vx), Vx",      };
-#[rustfmt::skip] const LD_I_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF055, instructions: ld_i_vx,      pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx55;LD [I], V0-Vx",               };
-#[rustfmt::skip] const LD_VX_I:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF065, instructions: ld_vx_i,      pre_ex_dump: dft_pre_ex_dump, post_ex_dump: dft_post_ex_dump, desc: "Fx65;LD V0-Vx, [I]",               };
+#[rustfmt::skip] const CLS:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00E0, instructions: cls,          desc: "00E0;CLS",                         };
+#[rustfmt::skip] const RET:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00EE, instructions: ret,          desc: "00EE;RET",                         };
+#[rustfmt::skip] const SCD:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFF0, match_value: 0x00C0, instructions: scd_nibble,   desc: "00Cn;SCD nibble",                  };
+#[rustfmt::skip] const SCR:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00FB, instructions: scr,          desc: "00FB;SCR",                         };
+#[rustfmt::skip] const SCL:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00FC, instructions: scl,          desc: "00FC;SCL",                         };
+#[rustfmt::skip] const EXIT:         OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00FD, instructions: exit,         desc: "00FD;EXIT",                        };
+#[rustfmt::skip] const LOW:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00FE, instructions: low,          desc: "00FE;LOW",                         };
+#[rustfmt::skip] const HIGH:         OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0x00FF, instructions: high,         desc: "00FF;HIGH",                        };
+#[rustfmt::skip] const SYS:          OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x0000, instructions: sys,          desc: "0nnn;SYS addr",                    };
+#[rustfmt::skip] const JP:           OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x1000, instructions: jp,           desc: "1nnn;JP addr",                     };
+#[rustfmt::skip] const CALL:         OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x2000, instructions: call,         desc: "2nnn;CALL addr",                   };
+#[rustfmt::skip] const SE_VX_BYTE:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x3000, instructions: se_vx_kk,     desc: "3xkk;SE Vx, byte",                 };
+#[rustfmt::skip] const SNE_VX_KK:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x4000, instructions: sne_vx_kk,    desc: "4xkk;SNE Vx, byte",                };
+#[rustfmt::skip] const SE_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x5000, instructions: se_vx_vy,     desc: "5xy0;SE Vx, Vy",                   };
+#[rustfmt::skip] const LD_I_VX_VY:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x5002, instructions: ld_i_vx_vy,   desc: "5xy2;LD [I], Vx-Vy",               };
+#[rustfmt::skip] const LD_VX_VY_I:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x5003, instructions: ld_vx_vy_i,   desc: "5xy3;LD Vx-Vy, [I]",               };
+#[rustfmt::skip] const LD_VX_BYTE:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x6000, instructions: ld_vx_kk,     desc: "6xkk;LD Vx, byte",                 };
+#[rustfmt::skip] const ADD_VX_BYTE:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0x7000, instructions: add_vx_kk,    desc: "7xkk;ADD Vx, byte",                };
+#[rustfmt::skip] const LD_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8000, instructions: ld_vx_vy,     desc: "8xy0;LD Vx, Vy",                   };
+#[rustfmt::skip] const OR_VX_VY:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8001, instructions: or_vx_vy,     desc: "8xy1;OR Vx, Vy",                   };
+#[rustfmt::skip] const AND_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8002, instructions: and_vx_vy,    desc: "8xy2;AND Vx, Vy",                  };
+#[rustfmt::skip] const XOR_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8003, instructions: xor_vx_vy,    desc: "8xy3;XOR Vx, Vy",                  };
+#[rustfmt::skip] const ADD_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8004, instructions: add_vx_vy,    desc: "8xy4;ADD Vx, Vy",                  };
+#[rustfmt::skip] const SUB_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8005, instructions: sub_vx_vy,    desc: "8xy5;SUB Vx, Vy",                  };
+#[rustfmt::skip] const SHR_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8006, instructions: shr_vx,       desc: "8xy6;SHR Vx, Vy",                  };
+#[rustfmt::skip] const SUBN_VX_VY:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x8007, instructions: subn_vx_vy,   desc: "8xy7;SUBN Vx, Vy",                 };
+#[rustfmt::skip] const SHL_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x800E, instructions: shl_vx,       desc: "8xyE;SHL Vx, Vy",                  };
+#[rustfmt::skip] const SNE_VX_VY:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0x9000, instructions: sne_vx_vy,    desc: "9xy0;SNE Vx, Vy",                  };
+#[rustfmt::skip] const LD_I_ADDR:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xA000, instructions: ld_i_addr,    desc: "Annn;LD I, addr",                  };
+#[rustfmt::skip] const JP_V0_ADDR:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xB000, instructions: jp_v0_addr,   desc: "Bnnn;JP V0, addr",                 };
+#[rustfmt::skip] const RND_VX_BYTE:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xC000, instructions: rnd_vx_byte,  desc: "Cxkk;RND Vx, byte",                };
+#[rustfmt::skip] const DRW_VX_VY_0:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF00F, match_value: 0xD000, instructions: drw_vx_vy_0,  desc: "Dxy0;DRW Vx, Vy, 0",               };
+#[rustfmt::skip] const DRW_VX_VY_NB: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF000, match_value: 0xD000, instructions: drw_vx_vy_nb, desc: "Dxyn;DRW Vx, Vy, nibble",          };
+#[rustfmt::skip] const SKP_VX:       OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xE09E, instructions: skp_vx,       desc: "Ex9E;SKP Vx",                      };
+#[rustfmt::skip] const SKNP_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xE0A1, instructions: sknp_vx,      desc: "ExA1;SKNP Vx",                     };
+#[rustfmt::skip] const LD_I_LONG:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0xF000, instructions: ld_i_long,    desc: "F000;LD I, long",                  };
+#[rustfmt::skip] const PLANE:        OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF001, instructions: plane,        desc: "Fn01;PLANE nibble",                };
+#[rustfmt::skip] const LD_AUDIO_I:   OpcodeMatcher = OpcodeMatcher { bitmask: 0xFFFF, match_value: 0xF002, instructions: ld_audio_i,   desc: "F002;LD AUDIO, [I]",               };
+#[rustfmt::skip] const LD_VX_DTIMER: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF007, instructions: ld_vx_dt,     desc: "Fx07;LD Vx, DT",                   };
+#[rustfmt::skip] const LD_VX_K:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF00A, instructions: ld_vx_key,    desc: "Fx0A;LD Vx, K",                    };
+#[rustfmt::skip] const LD_DTIMER_VX: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF015, instructions: ld_dt_vx,     desc: "Fx15;LD DT, Vx",                   };
+#[rustfmt::skip] const LD_STIMER_VX: OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF018, instructions: ld_st_vx,     desc: "Fx18;LD ST, Vx",                   };
+#[rustfmt::skip] const ADD_I_VX:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF01E, instructions: add_i_vx,     desc: "Fx1E;ADD I, Vx",                   };
+#[rustfmt::skip] const LD_PITCH_VX:  OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF03A, instructions: ld_pitch_vx,  desc: "Fx3A;LD PITCH, Vx",                };
+#[rustfmt::skip] const LD_F_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF029, instructions: ld_f_vx,      desc: "Fx29;LD F, Vx",                    };
+#[rustfmt::skip] const LD_BCD_VX:    OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF033, instructions: ld_bcd_vx,    desc: "Fx33;LD B, Vx",                    };
+#[rustfmt::skip] const LD_HF_VX:     OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF030, instructions: ld_hf_vx,     desc: "Fx30;LD HF, Vx",                   };
+#[rustfmt::skip] const LD_I_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF055, instructions: ld_i_vx,      desc: "Fx55;LD [I], Vx",                  };
+#[rustfmt::skip] const LD_VX_I:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF065, instructions: ld_vx_i,      desc: "Fx65;LD Vx, [I]",                  };
+#[rustfmt::skip] const LD_R_VX:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF075, instructions: ld_r_vx,      desc: "Fx75;LD R, V0-Vx",                 };
+#[rustfmt::skip] const LD_VX_R:      OpcodeMatcher = OpcodeMatcher { bitmask: 0xF0FF, match_value: 0xF085, instructions: ld_vx_r,      desc: "Fx85;LD V0-Vx, R",                 };
 
 // Constant list of opcodes matchers.
-pub const OPCODES: [OpcodeMatcher; CHIP8_TOTAL_STANDARD_OPCODES] = [
+pub const OPCODES: [OpcodeMatcher;
+    CHIP8_TOTAL_STANDARD_OPCODES + CHIP8_TOTAL_SUPER_CHIP_OPCODES + CHIP8_TOTAL_XO_CHIP_OPCODES] = [
     CLS,
     RET,
+    SCD,
+    SCR,
+    SCL,
+    EXIT,
+    LOW,
+    HIGH,
     SYS,
     JP,
     CALL,
     SE_VX_BYTE,
     SNE_VX_KK,
     SE_VX_VY,
+    LD_I_VX_VY,
+    LD_VX_VY_I,
     LD_VX_BYTE,
     ADD_VX_BYTE,
     LD_VX_VY,
@@ -182,73 +212,39 @@ pub const OPCODES: [OpcodeMatcher; CHIP8_TOTAL_STANDARD_OPCODES] = [
     LD_I_ADDR,
     JP_V0_ADDR,
     RND_VX_BYTE,
+    DRW_VX_VY_0,
     DRW_VX_VY_NB,
     SKP_VX,
     SKNP_VX,
+    LD_I_LONG,
+    PLANE,
+    LD_AUDIO_I,
     LD_VX_DTIMER,
     LD_VX_K,
     LD_DTIMER_VX,
     LD_STIMER_VX,
     ADD_I_VX,
+    LD_PITCH_VX,
     LD_F_VX,
     LD_BCD_VX,
+    LD_HF_VX,
     LD_I_VX,
     LD_VX_I,
+    LD_R_VX,
+    LD_VX_R,
 ];
 
-/// Default pre instructions execution dump function.
-fn dft_pre_ex_dump(ctx: &VMContext) {
-    let mut pre_ex_dump = format!("{:#06X}: {:#06X} /", ctx.registers.get_pc(), ctx.opcode);
-    let split = ctx.pattern.split(";").collect::<Vec<&str>>();
-    let opcode_str = *split.get(0).unwrap();
-    let desc = format!(" {} /", *split.get(1).unwrap());
-
-    pre_ex_dump.push_str(&desc);
-
-    if opcode_str.contains("x") {
-        let vx_index = vx_index!(ctx);
-        let vx_value = vx_value!(ctx);
-        let vx = format!(" V{:X} = {:#04X}", vx_index, vx_value);
-        pre_ex_dump.push_str(&vx);
-    }
-
-    if opcode_str.contains("y") {
-        let vy_index = vy_index!(ctx);
-        let vy_value = vy_value!(ctx);
-        let vy = format!(", V{:X} = {:#04X},", vy_index, vy_value);
-        pre_ex_dump.push_str(&vy);
-    }
-
-    if opcode_str.contains("nnn") {
-        let address = nnn_value!(ctx);
-        let vy = format!(" NNN = {:#05X}", address);
-        pre_ex_dump.push_str(&vy);
-    }
-
-    if opcode_str.ends_with("n") && !opcode_str.ends_with("nnn") {
-        let nbytes = nbytes_value!(ctx);
-        let vy = format!(" N = {:#03X}", nbytes);
-        pre_ex_dump.push_str(&vy);
-    }
-
-    if opcode_str.contains("kk") {
-        let byte = kk_value!(ctx);
-        let vy = format!(" KK = {:#04X}", byte);
-        pre_ex_dump.push_str(&vy);
+/// Skip the next instruction, taking into account that the XO-CHIP F000 NNNN instruction is
+/// 4 bytes long.
+fn skip_next_instruction(ctx: &mut VMContext) -> Result<(), VMError> {
+    ctx.registers.inc_pc()?;
+    if ctx.memory.get_opcode(ctx.registers.get_pc() as usize)? == 0xF000 {
+        ctx.registers.inc_pc()?;
     }
-
-    println!("{}", pre_ex_dump);
-}
-
-/// Default post instructions execution dump function.
-fn dft_post_ex_dump(ctx: &VMContext) {
-    ctx.registers.dump();
-    ctx.stack.dump();
-    ctx.memory.dump();
-    ctx.screen.dump();
+    Ok(())
 }
 
-/// Instructions for opcode pattern 00E0. Clear the display.
+/// Instructions for opcode pattern 0nnn. Jump to a machine code routine at nnn.
 fn sys(ctx: &mut VMContext) -> Result<Signal, VMError> {
     // This instruction is only used on the old computers on which Chip-8 was originally implemented.
     // It is ignored by modern interpreters.
@@ -271,6 +267,47 @@ fn ret(ctx: &mut VMContext) -> Result<Signal, VMError> {
     Ok(Signal::NoSignal)
 }
 
+/// Instructions for opcode pattern 00Cn. Scroll the display down n lines.
+fn scd_nibble(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let lines = nbytes_value!(ctx);
+    ctx.screen.scroll_down(lines);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::DrawScreen)
+}
+
+/// Instructions for opcode pattern 00FB. Scroll the display right 4 pixels.
+fn scr(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    ctx.screen.scroll_right(4);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::DrawScreen)
+}
+
+/// Instructions for opcode pattern 00FC. Scroll the display left 4 pixels.
+fn scl(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    ctx.screen.scroll_left(4);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::DrawScreen)
+}
+
+/// Instructions for opcode pattern 00FD. Exit the interpreter.
+fn exit(_ctx: &mut VMContext) -> Result<Signal, VMError> {
+    Ok(Signal::Exit)
+}
+
+/// Instructions for opcode pattern 00FE. Disable the 128x64 high resolution mode.
+fn low(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    ctx.screen.set_hires(false);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::DrawScreen)
+}
+
+/// Instructions for opcode pattern 00FF. Enable the 128x64 high resolution mode.
+fn high(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    ctx.screen.set_hires(true);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::DrawScreen)
+}
+
 /// Instructions for opcode pattern 1nnn. Jump to location nnn.
 fn jp(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let jump_address = nnn_value!(ctx);
@@ -294,7 +331,7 @@ fn se_vx_kk(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     let kk_value = kk_value!(ctx);
     if vx_value == kk_value {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
@@ -305,7 +342,7 @@ fn sne_vx_kk(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     let kk_value: u8 = kk_value!(ctx);
     if vx_value != kk_value {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
@@ -316,12 +353,45 @@ fn se_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
     if vx_value == vy_value {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
+/// Instructions for opcode pattern 5xy2. Store registers Vx through Vy in memory starting at
+/// location I, in descending order if x > y. I is not modified.
+fn ld_i_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let base_addr = ctx.registers.get_i() as usize;
+    for (offset, v) in register_range(vx_index!(ctx), vy_index!(ctx)).enumerate() {
+        ctx.memory
+            .set(base_addr + offset, ctx.registers.get_v_register(v))?;
+    }
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Instructions for opcode pattern 5xy3. Read registers Vx through Vy from memory starting at
+/// location I, in descending order if x > y. I is not modified.
+fn ld_vx_vy_i(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let base_addr = ctx.registers.get_i() as usize;
+    for (offset, v) in register_range(vx_index!(ctx), vy_index!(ctx)).enumerate() {
+        let value = ctx.memory.get(base_addr + offset)?;
+        ctx.registers.set_v_register(v, value);
+    }
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Register indexes from `from` to `to` (both included), descending if `from` > `to`.
+fn register_range(from: usize, to: usize) -> Box<dyn Iterator<Item = usize>> {
+    if from <= to {
+        Box::new(from..=to)
+    } else {
+        Box::new((to..=from).rev())
+    }
+}
+
 /// Instructions for opcode pattern 6xkk. Set Vx = kk.
 fn ld_vx_kk(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
@@ -352,34 +422,43 @@ fn ld_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
 }
 
 /// Instructions for opcode pattern 8xy1. Set Vx = Vx OR Vy.
+/// With the VF reset quirk, VF = 0.
 fn or_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
     ctx.registers.set_v_register(vx_index, vx_value | vy_value);
-    ctx.registers.unset_vf();
+    if ctx.quirks.vf_reset {
+        ctx.registers.unset_vf();
+    }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
 /// Instructions for opcode pattern 8xy2. Set Vx = Vx AND Vy.
+/// With the VF reset quirk, VF = 0.
 fn and_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
     ctx.registers.set_v_register(vx_index, vx_value & vy_value);
-    ctx.registers.unset_vf();
+    if ctx.quirks.vf_reset {
+        ctx.registers.unset_vf();
+    }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
-/// Instructions for opcode pattern 8xy3. Set Vx = Vx OR Vy.
+/// Instructions for opcode pattern 8xy3. Set Vx = Vx XOR Vy.
+/// With the VF reset quirk, VF = 0.
 fn xor_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
     ctx.registers.set_v_register(vx_index, vx_value ^ vy_value);
-    ctx.registers.unset_vf();
+    if ctx.quirks.vf_reset {
+        ctx.registers.unset_vf();
+    }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
@@ -392,7 +471,7 @@ fn add_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let addition = vx_value + vy_value;
 
     ctx.registers.unset_vf();
-    if addition > std::u8::MAX as u16 {
+    if addition > u8::MAX as u16 {
         ctx.registers.set_vf();
     }
     ctx.registers.set_v_register(vx_index, addition as u8);
@@ -416,12 +495,14 @@ fn sub_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
 }
 
 /// Instructions for opcode pattern 8xy6. Set Vx = Vx SHR 1, (shift right) set VF if truncation occurs.
+/// With the shift quirk, Vx = Vy SHR 1.
 fn shr_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
-    // let vy_index = ((ctx.opcode & 0x00F0) >> 4) as usize;
-    // let vy_value = ctx.registers.get_v_register(vy_index);
-
     let vx_index = vx_index!(ctx);
-    let vx_value = vx_value!(ctx);
+    let vx_value = if ctx.quirks.shift_uses_vy {
+        vy_value!(ctx)
+    } else {
+        vx_value!(ctx)
+    };
     ctx.registers.set_v_register(vx_index, vx_value >> 1);
 
     let vx_lsb = vx_value & 0b0000_0001;
@@ -448,13 +529,15 @@ fn subn_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     Ok(Signal::NoSignal)
 }
 
-/// Instructions for opcode pattern 8xyE. Set Vx = Vx SHL 1, (shift right) set VF if truncation occurs.
+/// Instructions for opcode pattern 8xyE. Set Vx = Vx SHL 1, (shift left) set VF if truncation occurs.
+/// With the shift quirk, Vx = Vy SHL 1.
 fn shl_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
-    // let vy_index = ((ctx.opcode & 0x00F0) >> 4) as usize;
-    // let vy_value = ctx.registers.get_v_register(vy_index);
-
     let vx_index = vx_index!(ctx);
-    let vx_value = vx_value!(ctx);
+    let vx_value = if ctx.quirks.shift_uses_vy {
+        vy_value!(ctx)
+    } else {
+        vx_value!(ctx)
+    };
     ctx.registers.set_v_register(vx_index, vx_value << 1);
 
     let vx_msb = vx_value & 0b1000_0000;
@@ -471,7 +554,7 @@ fn sne_vx_vy(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
     if vx_value != vy_value {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
@@ -486,9 +569,14 @@ fn ld_i_addr(ctx: &mut VMContext) -> Result<Signal, VMError> {
 }
 
 /// Instructions for opcode pattern Bnnn. Jump to location nnn + V0.
+/// With the jump quirk, jump to location nnn + Vx (Bxnn).
 fn jp_v0_addr(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let jump_address = nnn_value!(ctx);
-    let offset = ctx.registers.get_v_register(0) as u16;
+    let offset = if ctx.quirks.jump_with_vx {
+        vx_value!(ctx) as u16
+    } else {
+        ctx.registers.get_v_register(0) as u16
+    };
     ctx.registers.set_pc(jump_address + offset);
     Ok(Signal::NoSignal)
 }
@@ -497,17 +585,44 @@ fn jp_v0_addr(ctx: &mut VMContext) -> Result<Signal, VMError> {
 fn rnd_vx_byte(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let kk_value = kk_value!(ctx);
-    let rnd = rand::random::<u8>();
+    let rnd = ctx.rng.next_byte(ctx.memory)?;
     ctx.registers.set_v_register(vx_index, rnd & kk_value);
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
+/// Instructions for opcode pattern Dxy0. Display 16x16 sprite starting at memory location I at (Vx, Vy), set VF = collision.
+/// Without the large sprites quirk, the sprite has no rows as with Dxyn.
+/// With the low resolution 8x16 sprites quirk, the sprite is 8x16 in low resolution.
+fn drw_vx_vy_0(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    if !ctx.quirks.large_sprites {
+        draw_sprite(ctx, 8, 0)
+    } else if ctx.quirks.lores_8x16_sprites && !ctx.screen.is_hires() {
+        draw_sprite(ctx, 8, 16)
+    } else {
+        draw_sprite(ctx, 16, 16)
+    }
+}
+
 /// Instructions for opcode pattern Dxyn. Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
 fn drw_vx_vy_nb(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let nbytes = nbytes_value!(ctx);
+    draw_sprite(ctx, 8, nbytes)
+}
+
+/// Shared instructions for the draw opcodes.
+/// With the clipping quirk, the sprite is clipped at the screen edges instead of wrapped around.
+/// With the display wait quirk, only one sprite is drawn per frame.
+fn draw_sprite(ctx: &mut VMContext, width: usize, rows: usize) -> Result<Signal, VMError> {
+    if ctx.quirks.display_wait {
+        if *ctx.waiting_vblank {
+            // Retry the same instruction until the front-end signals the next frame.
+            return Ok(Signal::NoSignal);
+        }
+        *ctx.waiting_vblank = true;
+    }
     let vx_value = vx_value!(ctx);
     let vy_value = vy_value!(ctx);
-    let nbytes = nbytes_value!(ctx);
     let offset = ctx.registers.get_i();
     ctx.registers.unset_vf();
     if ctx.screen.draw_sprite(
@@ -515,7 +630,9 @@ fn drw_vx_vy_nb(ctx: &mut VMContext) -> Result<Signal, VMError> {
         vy_value as usize,
         offset as usize,
         ctx.memory,
-        nbytes,
+        width,
+        rows,
+        ctx.quirks.clip_sprites,
     )? {
         ctx.registers.set_vf();
     }
@@ -527,7 +644,7 @@ fn drw_vx_vy_nb(ctx: &mut VMContext) -> Result<Signal, VMError> {
 fn skp_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     if ctx.keyboard.is_key_down(vx_value) {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
@@ -537,12 +654,43 @@ fn skp_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
 fn sknp_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
     if ctx.keyboard.is_key_up(vx_value) {
-        ctx.registers.inc_pc()?;
+        skip_next_instruction(ctx)?;
     }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
+/// Instructions for opcode pattern F000 NNNN. Set I = NNNN, the address is the next 2 bytes.
+fn ld_i_long(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    ctx.registers.inc_pc()?;
+    let address = ctx.memory.get_opcode(ctx.registers.get_pc() as usize)?;
+    ctx.registers.set_i(address);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Instructions for opcode pattern Fn01. Select the bit planes n used by the draw, scroll and
+/// clear instructions.
+fn plane(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let planes = vx_index!(ctx) as u8;
+    ctx.screen.select_planes(planes);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Instructions for opcode pattern F002. Load the 16 bytes audio pattern buffer from memory
+/// starting at location I.
+fn ld_audio_i(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let base_addr = ctx.registers.get_i() as usize;
+    let mut pattern = [0u8; CHIP8_AUDIO_PATTERN_SIZE];
+    for (offset, byte) in pattern.iter_mut().enumerate() {
+        *byte = ctx.memory.get(base_addr + offset)?;
+    }
+    ctx.audio.set_pattern(pattern);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
 /// Instructions for opcode pattern Fx07. Set Vx = delay timer value.
 fn ld_vx_dt(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
@@ -552,35 +700,29 @@ fn ld_vx_dt(ctx: &mut VMContext) -> Result<Signal, VMError> {
     Ok(Signal::NoSignal)
 }
 
-thread_local! {
-    static WAIT_KEYUP: Cell<Option<u8>> = Cell::new(None);
-}
-
 /// Instructions for opcode pattern Fx0A. Wait for a key press, store the value fo the key in Vx.
 fn ld_vx_key(ctx: &mut VMContext) -> Result<Signal, VMError> {
-    WAIT_KEYUP.with(|f| {
-        let mut signal = Ok(Signal::NoSignal);
-        match f.get() {
-            Some(key) => {
-                if ctx.keyboard.is_key_up(key) {
-                    let vx_index = vx_index!(ctx);
-                    ctx.registers.set_v_register(vx_index, key);
-                    ctx.registers.inc_pc()?;
-                    f.set(None);
-                }
+    let mut signal = Ok(Signal::NoSignal);
+    match *ctx.waiting_keyup {
+        Some(key) => {
+            if ctx.keyboard.is_key_up(key) {
+                let vx_index = vx_index!(ctx);
+                ctx.registers.set_v_register(vx_index, key);
+                ctx.registers.inc_pc()?;
+                *ctx.waiting_keyup = None;
             }
-            None => {
-                // check for key down through all keys
-                for key in 0x0..=0xF as u8 {
-                    if ctx.keyboard.is_key_down(key) {
-                        signal = Ok(Signal::WaitKeyUp(key));
-                        f.set(Some(key));
-                    }
+        }
+        None => {
+            // check for key down through all keys
+            for key in 0x0..=0xF_u8 {
+                if ctx.keyboard.is_key_down(key) {
+                    signal = Ok(Signal::WaitKeyUp(key));
+                    *ctx.waiting_keyup = Some(key);
                 }
             }
         }
-        signal
-    })
+    }
+    signal
 }
 
 /// Instructions for opcode pattern Fx15. Set delay timer = Vx.
@@ -602,7 +744,16 @@ fn ld_st_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
 /// Instructions for opcode pattern Fx1E. Set I = I + Vx.
 fn add_i_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_value = vx_value!(ctx);
-    ctx.registers.set_i(ctx.registers.get_i() + vx_value as u16);
+    ctx.registers
+        .set_i(ctx.registers.get_i().wrapping_add(vx_value as u16));
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Instructions for opcode pattern Fx3A. Set the audio pattern playback pitch = Vx.
+fn ld_pitch_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let vx_value = vx_value!(ctx);
+    ctx.audio.set_pitch(vx_value);
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
@@ -633,7 +784,7 @@ fn ld_bcd_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     // Double dabble algorithm
     let mut bcd = vx_value as u32;
     for i in 0..8 {
-        bcd = bcd << 1;
+        bcd <<= 1;
         ones = (bcd & ones_mask) >> 8;
         tens = (bcd & tens_mask) >> 12;
         hund = (bcd & hund_mask) >> 16;
@@ -649,17 +800,17 @@ fn ld_bcd_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
         }
 
         if tens >= 5 {
-            tens = tens + 3;
+            tens += 3;
         }
 
         if hund >= 5 {
-            hund = hund + 3;
+            hund += 3;
         }
 
         // Return units to its position in BCD number
-        ones = ones << 8;
-        tens = tens << 12;
-        hund = hund << 16;
+        ones <<= 8;
+        tens <<= 12;
+        hund <<= 16;
 
         // Reassemble BCD for next shift after carries
         bcd = (bcd & !(hund_mask | tens_mask | ones_mask)) | (hund | tens | ones);
@@ -675,7 +826,17 @@ fn ld_bcd_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     Ok(Signal::NoSignal)
 }
 
+/// Instructions for opcode pattern Fx30. Set I = location of the 10-byte big sprite for digit Vx.
+fn ld_hf_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let vx_value = vx_value!(ctx) as usize;
+    let char_addr = CHIP8_BIG_CHARSET_ADDRESS + vx_value * 10;
+    ctx.registers.set_i(char_addr as u16);
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
 /// Instructions for opcode pattern Fx55. Store registers V0 through Vx in memory starting at location I.
+/// With the load/store quirk, I is left at I + x + 1, or I + x for CHIP-48.
 fn ld_i_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let base_addr = ctx.registers.get_i() as usize;
@@ -683,15 +844,18 @@ fn ld_i_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
     for vx in 0..=vx_index {
         ctx.memory
             .set(base_addr + vx, ctx.registers.get_v_register(vx))?;
-        ctx.registers.inc_i();
     }
-    ctx.registers.inc_i();
+    if ctx.quirks.load_store_increments_i {
+        ctx.registers
+            .set_i(load_store_end(ctx.quirks, base_addr, vx_index));
+    }
 
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
 
 /// Instructions for opcode pattern Fx65. Read registers V0 through Vx from memory starting at location I.
+/// With the load/store quirk, I is left at I + x + 1, or I + x for CHIP-48.
 fn ld_vx_i(ctx: &mut VMContext) -> Result<Signal, VMError> {
     let vx_index = vx_index!(ctx);
     let base_addr = ctx.registers.get_i() as usize;
@@ -699,10 +863,43 @@ fn ld_vx_i(ctx: &mut VMContext) -> Result<Signal, VMError> {
     for vx in 0..=vx_index {
         let value = ctx.memory.get(base_addr + vx)?;
         ctx.registers.set_v_register(vx, value);
-        ctx.registers.inc_i();
     }
-    ctx.registers.inc_i();
+    if ctx.quirks.load_store_increments_i {
+        ctx.registers
+            .set_i(load_store_end(ctx.quirks, base_addr, vx_index));
+    }
+
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Value of I after `Fx55`/`Fx65` with the load/store quirk.
+fn load_store_end(quirks: &Quirks, base_addr: usize, vx_index: usize) -> u16 {
+    if quirks.load_store_increments_i_by_x {
+        (base_addr + vx_index) as u16
+    } else {
+        (base_addr + vx_index + 1) as u16
+    }
+}
 
+/// Instructions for opcode pattern Fx75. Store registers V0 through Vx in the RPL user flags.
+fn ld_r_vx(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let vx_index = vx_index!(ctx);
+    for vx in 0..=vx_index {
+        ctx.registers
+            .set_rpl_flag(vx, ctx.registers.get_v_register(vx))?;
+    }
+    ctx.registers.inc_pc()?;
+    Ok(Signal::NoSignal)
+}
+
+/// Instructions for opcode pattern Fx85. Read registers V0 through Vx from the RPL user flags.
+fn ld_vx_r(ctx: &mut VMContext) -> Result<Signal, VMError> {
+    let vx_index = vx_index!(ctx);
+    for vx in 0..=vx_index {
+        let value = ctx.registers.get_rpl_flag(vx)?;
+        ctx.registers.set_v_register(vx, value);
+    }
     ctx.registers.inc_pc()?;
     Ok(Signal::NoSignal)
 }
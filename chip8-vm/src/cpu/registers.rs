@@ -1,8 +1,7 @@
-use std::io::Write;
-
 use crate::{
-    config::{CHIP8_MEM_SIZE, CHIP8_TOTAL_STACK_DEPTH},
+    config::{CHIP8_TOTAL_RPL_FLAGS, CHIP8_TOTAL_STACK_DEPTH},
     errors::VMError,
+    state::{StateReader, StateWriter},
 };
 
 #[derive(Default)]
@@ -38,6 +37,9 @@ pub struct Registers {
 
     /// Stack pointer.
     sp: u8,
+
+    /// SUPER-CHIP RPL user flags (HP-48 calculator flags), saved and restored by Fx75/Fx85.
+    rpl: [u8; CHIP8_TOTAL_RPL_FLAGS],
 }
 
 impl Registers {
@@ -77,10 +79,11 @@ impl Registers {
     }
 
     pub(crate) fn inc_pc(&mut self) -> Result<(), VMError> {
-        if !(self.pc < CHIP8_MEM_SIZE as u16) {
-            return Err(VMError::ProgramCounterOverflow);
-        }
-        self.pc += 2; // The opcodes are 2 bytes long
+        // The opcodes are 2 bytes long
+        self.pc = self
+            .pc
+            .checked_add(2)
+            .ok_or(VMError::ProgramCounterOverflow)?;
         Ok(())
     }
 
@@ -122,6 +125,23 @@ impl Registers {
         ]
     }
 
+    pub(crate) fn set_rpl_flag(&mut self, index: usize, value: u8) -> Result<(), VMError> {
+        match self.rpl.get_mut(index) {
+            Some(flag) => {
+                *flag = value;
+                Ok(())
+            }
+            None => Err(VMError::RplFlagsOutOfBounds(index)),
+        }
+    }
+
+    pub(crate) fn get_rpl_flag(&self, index: usize) -> Result<u8, VMError> {
+        match self.rpl.get(index) {
+            Some(flag) => Ok(*flag),
+            None => Err(VMError::RplFlagsOutOfBounds(index)),
+        }
+    }
+
     pub(crate) fn set_vf(&mut self) {
         self.v_f = 1;
     }
@@ -138,10 +158,6 @@ impl Registers {
         self.i
     }
 
-    pub(crate) fn inc_i(&mut self) {
-        self.i += 1;
-    }
-
     pub(crate) fn get_dt(&self) -> u8 {
         self.dt
     }
@@ -158,33 +174,32 @@ impl Registers {
         self.st = value;
     }
 
-    pub(crate) fn dump(&self) {
-        for (i, register) in self.v_registers().iter().enumerate() {
-            print!(
-                "V{:X}: dec:   {:03}, hex:   {:02X}, bin:         {:08b}\n",
-                i, register, register, register
-            );
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        for register in self.v_registers() {
+            state.write_u8(*register);
+        }
+        state.write_u16(self.i);
+        state.write_u8(self.dt);
+        state.write_u8(self.st);
+        state.write_u16(self.pc);
+        state.write_u8(self.sp);
+        state.write_bytes(&self.rpl);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        for register in self.v_registers_mut() {
+            *register = state.read_u8()?;
         }
-        print!(
-            "DT: dec:   {:03}, hex:   {:02X}, bin:         {:08b}\n",
-            self.dt, self.dt, self.dt
-        );
-        print!(
-            "ST: dec:   {:03}, hex:   {:02X}, bin:         {:08b}\n",
-            self.st, self.st, self.st
-        );
-        print!(
-            "SP: dec:   {:03}, hex:   {:02X}, bin:         {:08b}\n",
-            self.sp, self.sp, self.sp
-        );
-        print!(
-            "I : dec: {:05}, hex: {:04X}, bin: {:016b}\n",
-            self.i, self.i, self.i
-        );
-        print!(
-            "PC: dec: {:05}, hex: {:04X}, bin: {:016b}\n",
-            self.pc, self.pc, self.pc
-        );
-        std::io::stdout().flush().unwrap();
+        self.i = state.read_u16()?;
+        self.dt = state.read_u8()?;
+        self.st = state.read_u8()?;
+        self.pc = state.read_u16()?;
+        self.sp = state.read_u8()?;
+        if self.sp as usize > CHIP8_TOTAL_STACK_DEPTH {
+            return Err(VMError::InvalidState);
+        }
+        self.rpl
+            .copy_from_slice(state.read_bytes(CHIP8_TOTAL_RPL_FLAGS)?);
+        Ok(())
     }
 }
@@ -0,0 +1,87 @@
+use crate::{
+    errors::VMError,
+    memory::RAM,
+    state::{StateReader, StateWriter},
+};
+
+/// Algorithm used by the `Cxkk` instruction to draw random bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RngAlgorithm {
+    /// xorshift64* generator, the sequence only depends on the seed.
+    #[default]
+    Xorshift,
+    /// Routine modelled on the COSMAC VIP interpreter: a pointer walks the first memory page
+    /// and the byte it points to is added to the previous random number. The numbers depend on
+    /// the memory contents, the seed only sets the initial pointer and number.
+    CosmacVip,
+}
+
+/// Random number generator of the VM, seedable so runs can be reproduced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Rng {
+    algorithm: RngAlgorithm,
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(RngAlgorithm::default(), rand::random())
+    }
+}
+
+impl Rng {
+    pub(crate) fn new(algorithm: RngAlgorithm, seed: u64) -> Self {
+        let state = match algorithm {
+            // Spread the seed bits, xorshift needs a non zero state.
+            RngAlgorithm::Xorshift => splitmix64(seed).max(1),
+            RngAlgorithm::CosmacVip => seed & 0xFFFF,
+        };
+        Rng { algorithm, state }
+    }
+
+    pub(crate) fn next_byte(&mut self, memory: &RAM) -> Result<u8, VMError> {
+        match self.algorithm {
+            RngAlgorithm::Xorshift => {
+                self.state ^= self.state >> 12;
+                self.state ^= self.state << 25;
+                self.state ^= self.state >> 27;
+                Ok((self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8)
+            }
+            RngAlgorithm::CosmacVip => {
+                let pointer = (self.state as u8).wrapping_add(1);
+                let previous = (self.state >> 8) as u8;
+                let value = previous.wrapping_add(memory.get(pointer as usize)?);
+                self.state = ((value as u64) << 8) | pointer as u64;
+                Ok(value)
+            }
+        }
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(match self.algorithm {
+            RngAlgorithm::Xorshift => 0,
+            RngAlgorithm::CosmacVip => 1,
+        });
+        state.write_u64(self.state);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        self.algorithm = match state.read_u8()? {
+            0 => RngAlgorithm::Xorshift,
+            1 => RngAlgorithm::CosmacVip,
+            _ => return Err(VMError::InvalidState),
+        };
+        self.state = state.read_u64()?;
+        if self.algorithm == RngAlgorithm::Xorshift && self.state == 0 {
+            return Err(VMError::InvalidState);
+        }
+        Ok(())
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
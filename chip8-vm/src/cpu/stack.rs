@@ -1,4 +1,8 @@
-use crate::{config::CHIP8_TOTAL_STACK_DEPTH, errors::VMError};
+use crate::{
+    config::CHIP8_TOTAL_STACK_DEPTH,
+    errors::VMError,
+    state::{StateReader, StateWriter},
+};
 
 pub struct Stack {
     stack: [u16; CHIP8_TOTAL_STACK_DEPTH],
@@ -22,11 +26,16 @@ impl Stack {
         Ok(self.stack[sp as usize])
     }
 
-    pub(crate) fn dump(&self) {
-        print!("[");
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
         for addr in self.stack {
-            print!("{:#06X} ", addr);
+            state.write_u16(addr);
         }
-        println!("]");
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        for addr in self.stack.iter_mut() {
+            *addr = state.read_u16()?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,295 @@
+use std::{fmt, str::FromStr};
+
+use crate::{errors::VMError, VM};
+
+/// Breakpoint condition, an expression over the VM registers and memory such as
+/// `V3 == 0x10 && I > 0x300` or `[I + 1] != 0`. The expression holds when it evaluates to a
+/// non zero value.
+///
+/// Operands are the registers `V0` to `VF`, `I`, `PC`, `SP`, `DT` and `ST`, decimal or `0x`
+/// hexadecimal numbers and memory bytes `[address]`. Operators, from lower to higher
+/// precedence, are `||`, `&&`, the comparisons `==`, `!=`, `<`, `<=`, `>`, `>=`, the sums `+`,
+/// `-` and the negation `!`. Parentheses group sub expressions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Register(Register),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 16] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "!", "(", ")", "[", "]", "=",
+];
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "'{}'", value),
+            Token::Register(Register::V(index)) => write!(f, "'V{:X}'", index),
+            Token::Register(register) => {
+                write!(f, "'{}'", format!("{:?}", register).to_uppercase())
+            }
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+impl Condition {
+    /// Evaluate the condition against the current VM state.
+    pub fn eval(&self, vm: &VM) -> Result<bool, VMError> {
+        Ok(self.expr.eval(vm)? != 0)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = VMError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(format!("unexpected {}", token)));
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl Expr {
+    fn eval(&self, vm: &VM) -> Result<i64, VMError> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => match register {
+                Register::V(index) => vm.registers_v(*index) as i64,
+                Register::I => vm.registers_i() as i64,
+                Register::Pc => vm.registers_pc() as i64,
+                Register::Sp => vm.registers_sp() as i64,
+                Register::Dt => vm.registers_dt() as i64,
+                Register::St => vm.registers_st() as i64,
+            },
+            Expr::Memory(addr) => {
+                // Negative addresses wrap around and fail the memory bounds check.
+                vm.memory_get(addr.eval(vm)? as usize)? as i64
+            }
+            Expr::Not(expr) => (expr.eval(vm)? == 0) as i64,
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(vm)?;
+                // Short circuit the logical operators.
+                match op {
+                    BinaryOp::Or if lhs != 0 => return Ok(1),
+                    BinaryOp::And if lhs == 0 => return Ok(0),
+                    _ => {}
+                }
+                let rhs = rhs.eval(vm)?;
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (rhs != 0) as i64,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                }
+            }
+        })
+    }
+}
+
+fn invalid(reason: String) -> VMError {
+    VMError::InvalidCondition(reason)
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, VMError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            if *op == "=" {
+                return Err(invalid("'=' is not an operator, use '=='".to_string()));
+            }
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err(invalid(format!("unexpected character in '{}'", rest)));
+            }
+            tokens.push(parse_word(&rest[..len])?);
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_word(word: &str) -> Result<Token, VMError> {
+    let upper = word.to_ascii_uppercase();
+    let register = match upper.as_str() {
+        "I" => Some(Register::I),
+        "PC" => Some(Register::Pc),
+        "SP" => Some(Register::Sp),
+        "DT" => Some(Register::Dt),
+        "ST" => Some(Register::St),
+        _ => match upper.strip_prefix('V') {
+            Some(index) if index.len() == 1 => {
+                usize::from_str_radix(index, 16).ok().map(Register::V)
+            }
+            _ => None,
+        },
+    };
+    if let Some(register) = register {
+        return Ok(Token::Register(register));
+    }
+    let number = match upper.strip_prefix("0X") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => upper.parse::<i64>(),
+    };
+    number
+        .map(Token::Number)
+        .map_err(|_| invalid(format!("unknown operand '{}'", word)))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next_op(&mut self, ops: &[(&str, BinaryOp)]) -> Option<BinaryOp> {
+        if let Some(Token::Op(token)) = self.tokens.get(self.pos) {
+            if let Some((_, op)) = ops.iter().find(|(op, _)| op == token) {
+                self.pos += 1;
+                return Some(*op);
+            }
+        }
+        None
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), VMError> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if *op == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(invalid(format!("expected '{}'", expected))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, VMError> {
+        let mut expr = self.parse_and()?;
+        while let Some(op) = self.next_op(&[("||", BinaryOp::Or)]) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, VMError> {
+        let mut expr = self.parse_comparison()?;
+        while let Some(op) = self.next_op(&[("&&", BinaryOp::And)]) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.parse_comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, VMError> {
+        let expr = self.parse_sum()?;
+        let comparisons = [
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+            ("<", BinaryOp::Lt),
+            ("<=", BinaryOp::Le),
+            (">", BinaryOp::Gt),
+            (">=", BinaryOp::Ge),
+        ];
+        match self.next_op(&comparisons) {
+            Some(op) => Ok(Expr::Binary(
+                op,
+                Box::new(expr),
+                Box::new(self.parse_sum()?),
+            )),
+            None => Ok(expr),
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expr, VMError> {
+        let mut expr = self.parse_unary()?;
+        while let Some(op) = self.next_op(&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)]) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, VMError> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Register(register)) => Ok(Expr::Register(register)),
+            Some(Token::Op("!")) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Op("(")) => {
+                let expr = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op("[")) => {
+                let expr = self.parse_or()?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            Some(token) => Err(invalid(format!("unexpected {}", token))),
+            None => Err(invalid("unexpected end of expression".to_string())),
+        }
+    }
+}
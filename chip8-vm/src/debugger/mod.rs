@@ -0,0 +1,346 @@
+mod condition;
+mod watchpoint;
+
+use std::collections::BTreeMap;
+
+use crate::{config::CHIP8_DEBUGGER_STEP_LIMIT, errors::VMError, vm::Signal, VM};
+
+pub use condition::Condition;
+pub use watchpoint::{WatchHit, WatchKind, Watchpoint};
+
+/// Reason why the `Debugger` gave the control back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested step (or step over, step out, run to address) completed.
+    Step,
+    /// The PC reached a breakpoint address, and its condition (if any) held.
+    Breakpoint(u16),
+    /// An instruction accessed the memory watched by a watchpoint.
+    Watchpoint(WatchHit),
+    /// The program executed the EXIT instruction.
+    Exit,
+    /// The step limit was reached before any other stop condition.
+    StepLimit,
+}
+
+/// `Debugger` drives the execution of a `VM` one instruction at a time, stopping at the PC
+/// breakpoints and memory watchpoints. Programs run without frame timing while debugging:
+/// every instruction starts a new frame for the display wait quirk, and the timers count down
+/// once every `VM::DEFAULT_CYCLES_PER_FRAME` instructions, as they would at the default speed.
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    step_limit: usize,
+    frame_cycle: usize,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            step_limit: CHIP8_DEBUGGER_STEP_LIMIT,
+            frame_cycle: 0,
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a PC breakpoint, returns false if it was already set.
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr, None).is_none()
+    }
+
+    /// Add a PC breakpoint that only stops when the condition holds, replacing the breakpoint
+    /// already set at the address. Returns false if a breakpoint was already set.
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: Condition) -> bool {
+        self.breakpoints.insert(addr, Some(condition)).is_none()
+    }
+
+    /// Remove a PC breakpoint, returns false if it was not set.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    pub fn has_breakpoint(&self, addr: u16) -> bool {
+        self.breakpoints.contains_key(&addr)
+    }
+
+    /// Returns the breakpoint addresses along with their conditions.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (&u16, Option<&Condition>)> {
+        self.breakpoints
+            .iter()
+            .map(|(addr, condition)| (addr, condition.as_ref()))
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Remove the watchpoint at `index` in the `watchpoints` list.
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        (index < self.watchpoints.len()).then(|| self.watchpoints.remove(index))
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Maximum number of instructions executed by a single continue, step over, step out or
+    /// run to address, so that a program looping forever gives the control back.
+    pub fn set_step_limit(&mut self, step_limit: usize) {
+        self.step_limit = step_limit;
+    }
+
+    /// Execute the next instruction.
+    pub fn step(&mut self, vm: &mut VM) -> Result<StopReason, VMError> {
+        match self.exec(vm)? {
+            Some(reason) => Ok(reason),
+            None => Ok(StopReason::Step),
+        }
+    }
+
+    /// Execute the next instruction, running a whole subroutine if it is a CALL.
+    pub fn step_over(&mut self, vm: &mut VM) -> Result<StopReason, VMError> {
+        let pc = vm.registers_pc();
+        if vm.memory_opcode(pc as usize)? & 0xF000 != 0x2000 {
+            return self.step(vm);
+        }
+        let sp = vm.registers_sp();
+        self.run_until(vm, |vm| {
+            vm.registers_pc() == pc.wrapping_add(2) && vm.registers_sp() == sp
+        })
+    }
+
+    /// Run until the current subroutine returns with RET.
+    pub fn step_out(&mut self, vm: &mut VM) -> Result<StopReason, VMError> {
+        let sp = vm.registers_sp();
+        self.run_until(vm, |vm| vm.registers_sp() < sp)
+    }
+
+    /// Run until a breakpoint, a watchpoint or the end of the program.
+    pub fn cont(&mut self, vm: &mut VM) -> Result<StopReason, VMError> {
+        self.run_until(vm, |_| false)
+    }
+
+    /// Run until the PC reaches `addr`, or a breakpoint, a watchpoint or the end of the
+    /// program.
+    pub fn run_to(&mut self, vm: &mut VM, addr: u16) -> Result<StopReason, VMError> {
+        self.run_until(vm, |vm| vm.registers_pc() == addr)
+    }
+
+    fn run_until(
+        &mut self,
+        vm: &mut VM,
+        done: impl Fn(&VM) -> bool,
+    ) -> Result<StopReason, VMError> {
+        for _ in 0..self.step_limit {
+            if let Some(reason) = self.exec(vm)? {
+                return Ok(reason);
+            }
+            if done(vm) {
+                return Ok(StopReason::Step);
+            }
+            let pc = vm.registers_pc();
+            match self.breakpoints.get(&pc) {
+                Some(None) => return Ok(StopReason::Breakpoint(pc)),
+                Some(Some(condition)) if condition.eval(vm)? => {
+                    return Ok(StopReason::Breakpoint(pc))
+                }
+                _ => {}
+            }
+        }
+        Ok(StopReason::StepLimit)
+    }
+
+    /// Execute the next instruction, returns the stop reason if it exits the program or
+    /// triggers a watchpoint.
+    fn exec(&mut self, vm: &mut VM) -> Result<Option<StopReason>, VMError> {
+        let pc = vm.registers_pc();
+        vm.set_memory_access_log(!self.watchpoints.is_empty());
+        vm.take_memory_accesses();
+        vm.vblank();
+        self.frame_cycle += 1;
+        if self.frame_cycle == VM::DEFAULT_CYCLES_PER_FRAME {
+            vm.tick_timers();
+            self.frame_cycle = 0;
+        }
+        if let Signal::Exit = vm.exec_next_opcode()? {
+            return Ok(Some(StopReason::Exit));
+        }
+        for access in vm.take_memory_accesses() {
+            for watchpoint in self.watchpoints.iter() {
+                if let Some(hit) = watchpoint.check(pc, &access) {
+                    return Ok(Some(StopReason::Watchpoint(hit)));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Condition, Debugger, Quirks, StopReason, WatchHit, WatchKind, Watchpoint, VM};
+
+    // 0x200: CALL 0x206, 0x202: LD V1, 1, 0x204: EXIT, 0x206: LD V0, 2, 0x208: RET
+    const PROGRAM: [u8; 10] = [0x22, 0x06, 0x61, 0x01, 0x00, 0xFD, 0x60, 0x02, 0x00, 0xEE];
+
+    fn load_vm() -> VM {
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8.load_program(&PROGRAM).expect("Load program");
+        chip8
+    }
+
+    #[test]
+    fn step_and_step_over() {
+        let mut chip8 = load_vm();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(debugger.step(&mut chip8).unwrap(), StopReason::Step);
+        assert_eq!(chip8.registers_pc(), 0x206);
+
+        let mut chip8 = load_vm();
+        assert_eq!(debugger.step_over(&mut chip8).unwrap(), StopReason::Step);
+        assert_eq!(chip8.registers_pc(), 0x202);
+        assert_eq!(chip8.registers_v(0), 2);
+    }
+
+    #[test]
+    fn step_out_and_run_to() {
+        let mut chip8 = load_vm();
+        let mut debugger = Debugger::new();
+
+        debugger.step(&mut chip8).unwrap();
+        assert_eq!(debugger.step_out(&mut chip8).unwrap(), StopReason::Step);
+        assert_eq!(chip8.registers_pc(), 0x202);
+        assert_eq!(chip8.registers_sp(), 0);
+
+        let mut chip8 = load_vm();
+        assert_eq!(
+            debugger.run_to(&mut chip8, 0x208).unwrap(),
+            StopReason::Step
+        );
+        assert_eq!(chip8.registers_pc(), 0x208);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut chip8 = load_vm();
+        let mut debugger = Debugger::new();
+        assert!(debugger.add_breakpoint(0x204));
+        assert!(!debugger.add_breakpoint(0x204));
+
+        assert_eq!(
+            debugger.cont(&mut chip8).unwrap(),
+            StopReason::Breakpoint(0x204)
+        );
+        assert_eq!(chip8.registers_v(1), 1);
+        assert_eq!(debugger.cont(&mut chip8).unwrap(), StopReason::Exit);
+
+        assert!(debugger.remove_breakpoint(0x204));
+        let mut chip8 = load_vm();
+        assert_eq!(debugger.cont(&mut chip8).unwrap(), StopReason::Exit);
+    }
+
+    #[test]
+    fn step_limit() {
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8.load_program(&[0x12, 0x00]).expect("Load program");
+        let mut debugger = Debugger::new();
+        debugger.set_step_limit(100);
+
+        assert_eq!(debugger.cont(&mut chip8).unwrap(), StopReason::StepLimit);
+    }
+
+    #[test]
+    fn watchpoints() {
+        // 0x200: LD I, 0x300, 0x202: LD V0, 5, 0x204: LD [I], V0, 0x206: LD [I], V0,
+        // 0x208: LD V0, [I], 0x20A: EXIT
+        let program = [
+            0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0xF0, 0x55, 0xF0, 0x65, 0x00, 0xFD,
+        ];
+        let mut chip8: VM = VM::new(Quirks::CHIP48);
+        chip8.load_program(&program).expect("Load program");
+        let mut debugger = Debugger::new();
+        debugger.add_watchpoint(Watchpoint::new(0x300..=0x30F, WatchKind::Change));
+        debugger.add_watchpoint(Watchpoint::new(0x300..=0x300, WatchKind::Read));
+
+        assert_eq!(
+            debugger.cont(&mut chip8).unwrap(),
+            StopReason::Watchpoint(WatchHit {
+                pc: 0x204,
+                addr: 0x300,
+                kind: WatchKind::Change,
+                old: 0,
+                new: 5
+            })
+        );
+        // The second store writes the same value.
+        assert_eq!(
+            debugger.cont(&mut chip8).unwrap(),
+            StopReason::Watchpoint(WatchHit {
+                pc: 0x208,
+                addr: 0x300,
+                kind: WatchKind::Read,
+                old: 5,
+                new: 5
+            })
+        );
+        assert_eq!(debugger.cont(&mut chip8).unwrap(), StopReason::Exit);
+
+        assert!(debugger.remove_watchpoint(1).is_some());
+        assert!(debugger.remove_watchpoint(1).is_none());
+        assert_eq!(debugger.watchpoints().len(), 1);
+    }
+
+    #[test]
+    fn conditional_breakpoints() {
+        // 0x200: ADD V3, 1, 0x202: JP 0x200
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8
+            .load_program(&[0x73, 0x01, 0x12, 0x00])
+            .expect("Load program");
+        let mut debugger = Debugger::new();
+        let condition: Condition = "V3 == 0x10 && !(I > 0x300)".parse().unwrap();
+        debugger.add_conditional_breakpoint(0x202, condition);
+
+        assert_eq!(
+            debugger.cont(&mut chip8).unwrap(),
+            StopReason::Breakpoint(0x202)
+        );
+        assert_eq!(chip8.registers_v(3), 0x10);
+    }
+
+    #[test]
+    fn conditions() {
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8
+            .load_program(&[0x12, 0x34, 0x56])
+            .expect("Load program");
+        let eval = |source: &str| source.parse::<Condition>().unwrap().eval(&chip8).unwrap();
+
+        assert!(eval("PC == 0x200 && SP == 0"));
+        assert!(eval("[PC + 2] == 0x56 || V0"));
+        assert!(eval("[0x201] - 0x34 == 0"));
+        assert!(eval("v0 < 1 && 2 >= 2 && 1 <= 2 && 3 > 2 && 1 != 2"));
+        assert!(!eval("DT || ST || I"));
+        assert!(eval("!(1 == 2 || 0)"));
+
+        assert!("V3 = 1".parse::<Condition>().is_err());
+        assert!("VG == 1".parse::<Condition>().is_err());
+        assert!("(V3 == 1".parse::<Condition>().is_err());
+        assert!("V3 == 1 V4".parse::<Condition>().is_err());
+        assert_eq!(
+            "V3==0x10 && I>0x300"
+                .parse::<Condition>()
+                .unwrap()
+                .to_string(),
+            "V3==0x10 && I>0x300"
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::RangeInclusive;
+
+use crate::memory::MemoryAccess;
+
+/// Memory accesses that trigger a watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Any instruction reading the memory.
+    Read,
+    /// Any instruction writing the memory, even if the value does not change.
+    Write,
+    /// Instructions writing a different value in the memory.
+    Change,
+}
+
+/// Watchpoint on a memory address range. Only the memory accessed by instructions is watched,
+/// the instruction fetch itself is not a read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addrs: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+/// Memory access that triggered a watchpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Address of the instruction that made the access.
+    pub pc: u16,
+    pub addr: u16,
+    pub kind: WatchKind,
+    /// Value before the access, the same as `new` for reads.
+    pub old: u8,
+    pub new: u8,
+}
+
+impl Watchpoint {
+    pub fn new(addrs: RangeInclusive<u16>, kind: WatchKind) -> Self {
+        Watchpoint { addrs, kind }
+    }
+
+    pub(crate) fn check(&self, pc: u16, access: &MemoryAccess) -> Option<WatchHit> {
+        let (addr, old, new) = match (self.kind, *access) {
+            (WatchKind::Read, MemoryAccess::Read { addr, value }) => (addr, value, value),
+            (WatchKind::Write, MemoryAccess::Write { addr, old, new }) => (addr, old, new),
+            (WatchKind::Change, MemoryAccess::Write { addr, old, new }) if old != new => {
+                (addr, old, new)
+            }
+            _ => return None,
+        };
+        let addr = addr as u16;
+        if !self.addrs.contains(&addr) {
+            return None;
+        }
+        Some(WatchHit {
+            pc,
+            addr,
+            kind: self.kind,
+            old,
+            new,
+        })
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt;
+
+use crate::{
+    errors::VMError,
+    trace::{TraceRegisters, TRACE_REGISTERS},
+    Headless, RunLength, Signal, TraceRecord, VM,
+};
+
+/// Memory differences listed by a `Divergence`, the rest are counted.
+const MAX_MEMORY_DIFFERENCES: usize = 8;
+
+/// First instruction after which two runs differ.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Instructions executed before the diverging one.
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// Differences of the machine state after the instruction, e.g. `V1: 0x04 != 0x08`.
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cycle {}: {:#06X} {:#06X} {}",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            VM::opcode_mnemonic(self.opcode).unwrap_or_else(|| "???".to_string())
+        )?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Instruction executed in lockstep by all the VMs of a diff.
+struct Step<'a> {
+    cycle: u64,
+    pc: u16,
+    opcode: u16,
+    before: &'a [TraceRegisters],
+    /// Outcome of the instruction in each VM: `ok`, `exit` or the error.
+    outcomes: &'a [String],
+}
+
+impl Headless {
+    /// Run two VMs side by side with the key script of the runner, e.g. the same ROM with two
+    /// quirk configurations, and find the first instruction after which their registers,
+    /// stack or memory differ.
+    pub fn diff(
+        &self,
+        a: &mut VM,
+        b: &mut VM,
+        length: RunLength,
+    ) -> Result<Option<Divergence>, VMError> {
+        self.lockstep(&mut [a, b], length, |step, vms| {
+            let differences = state_differences(vms[0], vms[1], step.outcomes);
+            (!differences.is_empty()).then_some(differences)
+        })
+    }
+
+    /// Run the VM with the key script of the runner and compare every instruction with the
+    /// trace record of the same cycle, e.g. from a trace of another emulator. Instructions
+    /// without a record, filtered out of the trace, are not compared.
+    pub fn diff_trace(
+        &self,
+        vm: &mut VM,
+        trace: &[TraceRecord],
+    ) -> Result<Option<Divergence>, VMError> {
+        let Some(last) = trace.last() else {
+            return Ok(None);
+        };
+        let mut records = trace.iter().peekable();
+        self.lockstep(&mut [vm], RunLength::Cycles(last.cycle + 1), |step, vms| {
+            while records
+                .next_if(|record| record.cycle < step.cycle)
+                .is_some()
+            {}
+            let expected = records.next_if(|record| record.cycle == step.cycle)?;
+            let actual = TraceRecord::new(
+                step.cycle,
+                step.pc,
+                step.opcode,
+                &step.before[0],
+                &vms[0].trace_registers(),
+            );
+            let mut differences = Vec::new();
+            if expected.pc != actual.pc {
+                differences.push(format!(
+                    "PC: expected {:#06X}, found {:#06X}",
+                    expected.pc, actual.pc
+                ));
+            }
+            if expected.opcode != actual.opcode {
+                differences.push(format!(
+                    "opcode: expected {:#06X}, found {:#06X}",
+                    expected.opcode, actual.opcode
+                ));
+            }
+            let mut expected_deltas = expected.deltas.clone();
+            expected_deltas.sort();
+            if expected_deltas != actual.deltas {
+                differences.push(format!(
+                    "registers: expected{}, found{}",
+                    deltas(&expected_deltas),
+                    deltas(&actual.deltas)
+                ));
+            }
+            if step.outcomes[0] != "ok" && step.outcomes[0] != "exit" {
+                differences.push(step.outcomes[0].clone());
+            }
+            (!differences.is_empty()).then_some(differences)
+        })
+    }
+
+    /// Execute the VMs one instruction at a time, with the frames and key events of `run`,
+    /// until `check` finds differences after an instruction.
+    fn lockstep(
+        &self,
+        vms: &mut [&mut VM],
+        length: RunLength,
+        mut check: impl FnMut(&Step, &[&mut VM]) -> Option<Vec<String>>,
+    ) -> Result<Option<Divergence>, VMError> {
+        let cycles_per_frame = self.cycles_per_frame() as u64;
+        let total_cycles = match length {
+            RunLength::Cycles(cycles) => cycles,
+            RunLength::Frames(frames) => frames * cycles_per_frame,
+        };
+        let mut events = self.key_script().events().iter().peekable();
+        let mut frame = 0;
+        let mut frame_cycle = cycles_per_frame;
+        for cycle in 0..total_cycles {
+            if frame_cycle == cycles_per_frame {
+                while let Some(event) = events.next_if(|event| event.frame <= frame) {
+                    for vm in vms.iter_mut() {
+                        vm.set_key(event.key, event.pressed);
+                    }
+                }
+                for vm in vms.iter_mut() {
+                    vm.vblank();
+                    vm.tick_timers();
+                }
+                frame += 1;
+                frame_cycle = 0;
+            }
+            frame_cycle += 1;
+
+            let pc = vms[0].registers_pc();
+            let opcode = vms[0].next_opcode()?;
+            let before = vms
+                .iter()
+                .map(|vm| vm.trace_registers())
+                .collect::<Vec<TraceRegisters>>();
+            let results = vms
+                .iter_mut()
+                .map(|vm| vm.exec_next_opcode())
+                .collect::<Vec<Result<Signal, VMError>>>();
+            let outcomes = results
+                .iter()
+                .map(|result| match result {
+                    Ok(Signal::Exit) => "exit".to_string(),
+                    Ok(_) => "ok".to_string(),
+                    Err(e) => format!("error: {}", e),
+                })
+                .collect::<Vec<String>>();
+            let step = Step {
+                cycle,
+                pc,
+                opcode,
+                before: &before,
+                outcomes: &outcomes,
+            };
+            if let Some(differences) = check(&step, vms) {
+                return Ok(Some(Divergence {
+                    cycle,
+                    pc,
+                    opcode,
+                    differences,
+                }));
+            }
+            // Without differences, the VMs failed with the same error and can't go on.
+            if let Some(Err(e)) = results.into_iter().next() {
+                return Err(e);
+            }
+            if outcomes.iter().any(|outcome| outcome != "ok") {
+                break;
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn deltas(deltas: &[(u8, u16)]) -> String {
+    if deltas.is_empty() {
+        return " no changes".to_string();
+    }
+    deltas
+        .iter()
+        .map(|(register, value)| {
+            format!(" {}={:#04X}", TraceRecord::register_name(*register), value)
+        })
+        .collect()
+}
+
+/// Differences between the registers, stack and memory of two VMs.
+fn state_differences(a: &VM, b: &VM, outcomes: &[String]) -> Vec<String> {
+    let mut differences = Vec::new();
+    if outcomes[0] != outcomes[1] {
+        differences.push(format!("outcome: {} != {}", outcomes[0], outcomes[1]));
+    }
+    if a.registers_pc() != b.registers_pc() {
+        differences.push(format!(
+            "PC: {:#06X} != {:#06X}",
+            a.registers_pc(),
+            b.registers_pc()
+        ));
+    }
+    let (registers_a, registers_b) = (a.trace_registers(), b.trace_registers());
+    for index in 0..TRACE_REGISTERS {
+        if registers_a[index] != registers_b[index] {
+            differences.push(format!(
+                "{}: {:#04X} != {:#04X}",
+                TraceRecord::register_name(index as u8),
+                registers_a[index],
+                registers_b[index]
+            ));
+        }
+    }
+    let depth = a.registers_sp().max(b.registers_sp());
+    for level in 0..depth {
+        let (value_a, value_b) = (a.stack_get(level).ok(), b.stack_get(level).ok());
+        if value_a != value_b {
+            differences.push(format!(
+                "stack level {}: {:#06X} != {:#06X}",
+                level,
+                value_a.unwrap_or_default(),
+                value_b.unwrap_or_default()
+            ));
+        }
+    }
+    let memory = a
+        .memory_bytes()
+        .iter()
+        .zip(b.memory_bytes())
+        .enumerate()
+        .filter(|(_, (value_a, value_b))| value_a != value_b)
+        .collect::<Vec<_>>();
+    for (address, (value_a, value_b)) in memory.iter().take(MAX_MEMORY_DIFFERENCES) {
+        differences.push(format!(
+            "memory {:#06X}: {:#04X} != {:#04X}",
+            address, value_a, value_b
+        ));
+    }
+    if memory.len() > MAX_MEMORY_DIFFERENCES {
+        differences.push(format!(
+            "and {} more memory bytes",
+            memory.len() - MAX_MEMORY_DIFFERENCES
+        ));
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Headless, Quirks, RunLength, TraceRecord, VM};
+
+    // 0x200: LD V1, 0x03, 0x202: LD V2, 0x08, 0x204: SHR V1, V2, 0x206: JP 0x200
+    const PROGRAM: [u8; 8] = [0x61, 0x03, 0x62, 0x08, 0x81, 0x26, 0x12, 0x00];
+
+    fn load_vm(quirks: Quirks) -> VM {
+        let mut chip8: VM = VM::new(quirks);
+        chip8.load_program(&PROGRAM).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn diff_quirks() {
+        let headless = Headless::new();
+        let divergence = headless
+            .diff(
+                &mut load_vm(Quirks::COSMAC_VIP),
+                &mut load_vm(Quirks::CHIP48),
+                RunLength::Frames(10),
+            )
+            .unwrap()
+            .expect("The shift quirk makes the runs diverge");
+        assert_eq!((divergence.cycle, divergence.pc), (2, 0x204));
+        assert_eq!(
+            divergence.differences,
+            ["V1: 0x04 != 0x01", "VF: 0x00 != 0x01"]
+        );
+        assert_eq!(
+            divergence.to_string(),
+            "cycle 2: 0x0204 0x8126 SHR V1, V2\n  V1: 0x04 != 0x01\n  VF: 0x00 != 0x01"
+        );
+
+        // 0x200: LD I, 0x300, 0x202: LD [I], V1, 0x204: JP 0x200
+        let store = [0xA3, 0x00, 0xF1, 0x55, 0x12, 0x00];
+        let load_store_vm = |quirks| {
+            let mut chip8: VM = VM::new(quirks);
+            chip8.load_program(&store).unwrap();
+            chip8
+        };
+        let divergence = headless
+            .diff(
+                &mut load_store_vm(Quirks::CHIP48),
+                &mut load_store_vm(Quirks::SUPER_CHIP),
+                RunLength::Frames(10),
+            )
+            .unwrap()
+            .expect("CHIP-48 increments I after Fx55");
+        assert_eq!((divergence.cycle, divergence.pc), (1, 0x202));
+        assert_eq!(divergence.differences, ["I: 0x301 != 0x300"]);
+
+        let same = headless
+            .diff(
+                &mut load_vm(Quirks::CHIP48),
+                &mut load_vm(Quirks::SUPER_CHIP),
+                RunLength::Frames(10),
+            )
+            .unwrap();
+        assert_eq!(same, None);
+
+        // 0x200: invalid instruction
+        let mut a: VM = VM::new(Quirks::CHIP48);
+        a.load_program(&[0x50, 0x01]).unwrap();
+        let mut b: VM = VM::new(Quirks::SUPER_CHIP);
+        b.load_program(&[0x50, 0x01]).unwrap();
+        assert!(headless.diff(&mut a, &mut b, RunLength::Frames(1)).is_err());
+    }
+
+    #[test]
+    fn diff_trace() {
+        let trace = "0 0x0200 0x6103 LD V1, 0x03 ; V1=0x03\n\
+                     2 0x0204 0x8126 SHR V1, V2 ; V1=0x01 VF=0x01\n\
+                     3 0x0206 0x1200 JP 0x200 ;"
+            .lines()
+            .map(|line| line.parse::<TraceRecord>().unwrap())
+            .collect::<Vec<TraceRecord>>();
+        let headless = Headless::new();
+        assert_eq!(
+            headless
+                .diff_trace(&mut load_vm(Quirks::CHIP48), &trace)
+                .unwrap(),
+            None
+        );
+        let divergence = headless
+            .diff_trace(&mut load_vm(Quirks::COSMAC_VIP), &trace)
+            .unwrap()
+            .unwrap();
+        assert_eq!(divergence.cycle, 2);
+        assert_eq!(
+            divergence.differences,
+            ["registers: expected V1=0x01 VF=0x01, found V1=0x04"]
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt;
+
+/// CHIP-8, SUPER-CHIP and XO-CHIP instructions. Register operands are indexes (0x0 to 0xF)
+/// and address operands are absolute memory addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    Cls,
+    /// 00EE
+    Ret,
+    /// 00Cn
+    Scd(u8),
+    /// 00FB
+    Scr,
+    /// 00FC
+    Scl,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Low,
+    /// 00FF
+    High,
+    /// 0nnn
+    Sys(u16),
+    /// 1nnn
+    Jp(u16),
+    /// 2nnn
+    Call(u16),
+    /// 3xkk
+    SeVxByte(u8, u8),
+    /// 4xkk
+    SneVxByte(u8, u8),
+    /// 5xy0
+    SeVxVy(u8, u8),
+    /// 5xy2
+    LdIVxVy(u8, u8),
+    /// 5xy3
+    LdVxVyI(u8, u8),
+    /// 6xkk
+    LdVxByte(u8, u8),
+    /// 7xkk
+    AddVxByte(u8, u8),
+    /// 8xy0
+    LdVxVy(u8, u8),
+    /// 8xy1
+    OrVxVy(u8, u8),
+    /// 8xy2
+    AndVxVy(u8, u8),
+    /// 8xy3
+    XorVxVy(u8, u8),
+    /// 8xy4
+    AddVxVy(u8, u8),
+    /// 8xy5
+    SubVxVy(u8, u8),
+    /// 8xy6
+    ShrVxVy(u8, u8),
+    /// 8xy7
+    SubnVxVy(u8, u8),
+    /// 8xyE
+    ShlVxVy(u8, u8),
+    /// 9xy0
+    SneVxVy(u8, u8),
+    /// Annn
+    LdIAddr(u16),
+    /// Bnnn
+    JpV0Addr(u16),
+    /// Cxkk
+    RndVxByte(u8, u8),
+    /// Dxyn
+    Drw(u8, u8, u8),
+    /// Ex9E
+    SkpVx(u8),
+    /// ExA1
+    SknpVx(u8),
+    /// F000 nnnn
+    LdILong(u16),
+    /// Fn01
+    Plane(u8),
+    /// F002
+    LdAudioI,
+    /// Fx07
+    LdVxDt(u8),
+    /// Fx0A
+    LdVxK(u8),
+    /// Fx15
+    LdDtVx(u8),
+    /// Fx18
+    LdStVx(u8),
+    /// Fx1E
+    AddIVx(u8),
+    /// Fx29
+    LdFVx(u8),
+    /// Fx30
+    LdHfVx(u8),
+    /// Fx33
+    LdBVx(u8),
+    /// Fx3A
+    LdPitchVx(u8),
+    /// Fx55
+    LdIVx(u8),
+    /// Fx65
+    LdVxI(u8),
+    /// Fx75
+    LdRVx(u8),
+    /// Fx85
+    LdVxR(u8),
+}
+
+impl Instruction {
+    /// Decode an opcode, `next` being the word that follows it, only used by the 4 bytes long
+    /// `F000 nnnn` instruction. Returns `None` if the opcode is not a valid instruction.
+    pub fn decode(opcode: u16, next: Option<u16>) -> Option<Instruction> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let instruction = match opcode {
+            0x00E0 => Instruction::Cls,
+            0x00EE => Instruction::Ret,
+            0x00FB => Instruction::Scr,
+            0x00FC => Instruction::Scl,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::Low,
+            0x00FF => Instruction::High,
+            0x00C0..=0x00CF => Instruction::Scd(n),
+            0x0000..=0x0FFF => Instruction::Sys(nnn),
+            0x1000..=0x1FFF => Instruction::Jp(nnn),
+            0x2000..=0x2FFF => Instruction::Call(nnn),
+            0x3000..=0x3FFF => Instruction::SeVxByte(x, kk),
+            0x4000..=0x4FFF => Instruction::SneVxByte(x, kk),
+            0x5000..=0x5FFF => match n {
+                0x0 => Instruction::SeVxVy(x, y),
+                0x2 => Instruction::LdIVxVy(x, y),
+                0x3 => Instruction::LdVxVyI(x, y),
+                _ => return None,
+            },
+            0x6000..=0x6FFF => Instruction::LdVxByte(x, kk),
+            0x7000..=0x7FFF => Instruction::AddVxByte(x, kk),
+            0x8000..=0x8FFF => match n {
+                0x0 => Instruction::LdVxVy(x, y),
+                0x1 => Instruction::OrVxVy(x, y),
+                0x2 => Instruction::AndVxVy(x, y),
+                0x3 => Instruction::XorVxVy(x, y),
+                0x4 => Instruction::AddVxVy(x, y),
+                0x5 => Instruction::SubVxVy(x, y),
+                0x6 => Instruction::ShrVxVy(x, y),
+                0x7 => Instruction::SubnVxVy(x, y),
+                0xE => Instruction::ShlVxVy(x, y),
+                _ => return None,
+            },
+            0x9000..=0x9FFF if n == 0 => Instruction::SneVxVy(x, y),
+            0xA000..=0xAFFF => Instruction::LdIAddr(nnn),
+            0xB000..=0xBFFF => Instruction::JpV0Addr(nnn),
+            0xC000..=0xCFFF => Instruction::RndVxByte(x, kk),
+            0xD000..=0xDFFF => Instruction::Drw(x, y, n),
+            0xE000..=0xEFFF => match kk {
+                0x9E => Instruction::SkpVx(x),
+                0xA1 => Instruction::SknpVx(x),
+                _ => return None,
+            },
+            0xF000..=0xFFFF => match kk {
+                0x00 if x == 0 => Instruction::LdILong(next?),
+                0x01 => Instruction::Plane(x),
+                0x02 if x == 0 => Instruction::LdAudioI,
+                0x07 => Instruction::LdVxDt(x),
+                0x0A => Instruction::LdVxK(x),
+                0x15 => Instruction::LdDtVx(x),
+                0x18 => Instruction::LdStVx(x),
+                0x1E => Instruction::AddIVx(x),
+                0x29 => Instruction::LdFVx(x),
+                0x30 => Instruction::LdHfVx(x),
+                0x33 => Instruction::LdBVx(x),
+                0x3A => Instruction::LdPitchVx(x),
+                0x55 => Instruction::LdIVx(x),
+                0x65 => Instruction::LdVxI(x),
+                0x75 => Instruction::LdRVx(x),
+                0x85 => Instruction::LdVxR(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// Encode the instruction in its 2 bytes opcode, followed by the address word for
+    /// `F000 nnnn`. Operands wider than their opcode field are truncated.
+    pub fn encode(&self) -> Vec<u8> {
+        let xkk = |base: u16, x: u8, kk: u8| base | ((x as u16 & 0xF) << 8) | kk as u16;
+        let xyn = |base: u16, x: u8, y: u8, n: u8| {
+            base | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | (n as u16 & 0xF)
+        };
+        let nnn = |base: u16, addr: u16| base | (addr & 0x0FFF);
+        let opcode = match *self {
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Scd(n) => xyn(0x0000, 0, 0xC, n),
+            Instruction::Scr => 0x00FB,
+            Instruction::Scl => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Sys(addr) => nnn(0x0000, addr),
+            Instruction::Jp(addr) => nnn(0x1000, addr),
+            Instruction::Call(addr) => nnn(0x2000, addr),
+            Instruction::SeVxByte(x, kk) => xkk(0x3000, x, kk),
+            Instruction::SneVxByte(x, kk) => xkk(0x4000, x, kk),
+            Instruction::SeVxVy(x, y) => xyn(0x5000, x, y, 0x0),
+            Instruction::LdIVxVy(x, y) => xyn(0x5000, x, y, 0x2),
+            Instruction::LdVxVyI(x, y) => xyn(0x5000, x, y, 0x3),
+            Instruction::LdVxByte(x, kk) => xkk(0x6000, x, kk),
+            Instruction::AddVxByte(x, kk) => xkk(0x7000, x, kk),
+            Instruction::LdVxVy(x, y) => xyn(0x8000, x, y, 0x0),
+            Instruction::OrVxVy(x, y) => xyn(0x8000, x, y, 0x1),
+            Instruction::AndVxVy(x, y) => xyn(0x8000, x, y, 0x2),
+            Instruction::XorVxVy(x, y) => xyn(0x8000, x, y, 0x3),
+            Instruction::AddVxVy(x, y) => xyn(0x8000, x, y, 0x4),
+            Instruction::SubVxVy(x, y) => xyn(0x8000, x, y, 0x5),
+            Instruction::ShrVxVy(x, y) => xyn(0x8000, x, y, 0x6),
+            Instruction::SubnVxVy(x, y) => xyn(0x8000, x, y, 0x7),
+            Instruction::ShlVxVy(x, y) => xyn(0x8000, x, y, 0xE),
+            Instruction::SneVxVy(x, y) => xyn(0x9000, x, y, 0x0),
+            Instruction::LdIAddr(addr) => nnn(0xA000, addr),
+            Instruction::JpV0Addr(addr) => nnn(0xB000, addr),
+            Instruction::RndVxByte(x, kk) => xkk(0xC000, x, kk),
+            Instruction::Drw(x, y, n) => xyn(0xD000, x, y, n),
+            Instruction::SkpVx(x) => xkk(0xE000, x, 0x9E),
+            Instruction::SknpVx(x) => xkk(0xE000, x, 0xA1),
+            Instruction::LdILong(addr) => return vec![0xF0, 0x00, (addr >> 8) as u8, addr as u8],
+            Instruction::Plane(n) => xkk(0xF000, n, 0x01),
+            Instruction::LdAudioI => 0xF002,
+            Instruction::LdVxDt(x) => xkk(0xF000, x, 0x07),
+            Instruction::LdVxK(x) => xkk(0xF000, x, 0x0A),
+            Instruction::LdDtVx(x) => xkk(0xF000, x, 0x15),
+            Instruction::LdStVx(x) => xkk(0xF000, x, 0x18),
+            Instruction::AddIVx(x) => xkk(0xF000, x, 0x1E),
+            Instruction::LdFVx(x) => xkk(0xF000, x, 0x29),
+            Instruction::LdHfVx(x) => xkk(0xF000, x, 0x30),
+            Instruction::LdBVx(x) => xkk(0xF000, x, 0x33),
+            Instruction::LdPitchVx(x) => xkk(0xF000, x, 0x3A),
+            Instruction::LdIVx(x) => xkk(0xF000, x, 0x55),
+            Instruction::LdVxI(x) => xkk(0xF000, x, 0x65),
+            Instruction::LdRVx(x) => xkk(0xF000, x, 0x75),
+            Instruction::LdVxR(x) => xkk(0xF000, x, 0x85),
+        };
+        u16::to_be_bytes(opcode).to_vec()
+    }
+
+    /// Instruction size in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Memory address operand of the instruction, if any.
+    pub fn addr(&self) -> Option<u16> {
+        match *self {
+            Instruction::Sys(addr)
+            | Instruction::Jp(addr)
+            | Instruction::Call(addr)
+            | Instruction::LdIAddr(addr)
+            | Instruction::JpV0Addr(addr)
+            | Instruction::LdILong(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Returns true for the instructions that may skip the next one.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::SeVxByte(..)
+                | Instruction::SneVxByte(..)
+                | Instruction::SeVxVy(..)
+                | Instruction::SneVxVy(..)
+                | Instruction::SkpVx(_)
+                | Instruction::SknpVx(_)
+        )
+    }
+
+    /// Format the instruction, using `addr_name` to format the address operand.
+    pub fn format_with(&self, addr_name: impl Fn(u16) -> String) -> String {
+        match *self {
+            Instruction::Cls => "CLS".to_string(),
+            Instruction::Ret => "RET".to_string(),
+            Instruction::Scd(n) => format!("SCD {}", n),
+            Instruction::Scr => "SCR".to_string(),
+            Instruction::Scl => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::Low => "LOW".to_string(),
+            Instruction::High => "HIGH".to_string(),
+            Instruction::Sys(addr) => format!("SYS {}", addr_name(addr)),
+            Instruction::Jp(addr) => format!("JP {}", addr_name(addr)),
+            Instruction::Call(addr) => format!("CALL {}", addr_name(addr)),
+            Instruction::SeVxByte(x, kk) => format!("SE V{:X}, {:#04X}", x, kk),
+            Instruction::SneVxByte(x, kk) => format!("SNE V{:X}, {:#04X}", x, kk),
+            Instruction::SeVxVy(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            Instruction::LdIVxVy(x, y) => format!("LD [I], V{:X}-V{:X}", x, y),
+            Instruction::LdVxVyI(x, y) => format!("LD V{:X}-V{:X}, [I]", x, y),
+            Instruction::LdVxByte(x, kk) => format!("LD V{:X}, {:#04X}", x, kk),
+            Instruction::AddVxByte(x, kk) => format!("ADD V{:X}, {:#04X}", x, kk),
+            Instruction::LdVxVy(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Instruction::OrVxVy(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            Instruction::AndVxVy(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Instruction::XorVxVy(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            Instruction::AddVxVy(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            Instruction::SubVxVy(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            Instruction::ShrVxVy(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            Instruction::SubnVxVy(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShlVxVy(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            Instruction::SneVxVy(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            Instruction::LdIAddr(addr) => format!("LD I, {}", addr_name(addr)),
+            Instruction::JpV0Addr(addr) => format!("JP V0, {}", addr_name(addr)),
+            Instruction::RndVxByte(x, kk) => format!("RND V{:X}, {:#04X}", x, kk),
+            Instruction::Drw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkpVx(x) => format!("SKP V{:X}", x),
+            Instruction::SknpVx(x) => format!("SKNP V{:X}", x),
+            Instruction::LdILong(addr) => format!("LD I, long {}", addr_name(addr)),
+            Instruction::Plane(n) => format!("PLANE {}", n),
+            Instruction::LdAudioI => "LD AUDIO, [I]".to_string(),
+            Instruction::LdVxDt(x) => format!("LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => format!("LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => format!("LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => format!("LD ST, V{:X}", x),
+            Instruction::AddIVx(x) => format!("ADD I, V{:X}", x),
+            Instruction::LdFVx(x) => format!("LD F, V{:X}", x),
+            Instruction::LdHfVx(x) => format!("LD HF, V{:X}", x),
+            Instruction::LdBVx(x) => format!("LD B, V{:X}", x),
+            Instruction::LdPitchVx(x) => format!("LD PITCH, V{:X}", x),
+            Instruction::LdIVx(x) => format!("LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => format!("LD V{:X}, [I]", x),
+            Instruction::LdRVx(x) => format!("LD R, V0-V{:X}", x),
+            Instruction::LdVxR(x) => format!("LD V0-V{:X}, R", x),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = self.format_with(|addr| match self {
+            Instruction::LdILong(_) => format!("{:#06X}", addr),
+            _ => format!("{:#05X}", addr),
+        });
+        write!(f, "{}", text)
+    }
+}
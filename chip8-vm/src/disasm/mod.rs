@@ -0,0 +1,294 @@
+//! ROM disassembler. The code is told apart from the data by following the control flow of
+//! the program from its entry point: the bytes reached as instructions are code and the rest
+//! are data. The listing uses the same syntax as the assembler, so it can be assembled back.
+
+mod instruction;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+pub use instruction::Instruction;
+
+/// Maximum number of bytes per data line.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// Disassembled ROM line, either an instruction or data bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    /// The decoded instruction, `None` for data.
+    pub instruction: Option<Instruction>,
+}
+
+/// Listing of a ROM with the labels of the jump, call and I register targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly {
+    pub origin: u16,
+    pub lines: Vec<Line>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl Disassembly {
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (&u16, &String)> {
+        self.labels.iter()
+    }
+
+    /// Text of the line in assembler syntax, using the labels for the address operands.
+    pub fn line_text(&self, line: &Line) -> String {
+        match line.instruction {
+            Some(instruction) => instruction.format_with(|addr| match self.labels.get(&addr) {
+                Some(label) => label.clone(),
+                None => match instruction {
+                    Instruction::LdILong(_) => format!("{:#06X}", addr),
+                    _ => format!("{:#05X}", addr),
+                },
+            }),
+            None => {
+                let bytes = line
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{:#04X}", byte))
+                    .collect::<Vec<String>>();
+                format!("db {}", bytes.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines.iter() {
+            if let Some(label) = self.label(line.addr) {
+                writeln!(f, "{}:", label)?;
+            }
+            let bytes = line
+                .bytes
+                .iter()
+                .map(|byte| format!("{:02X}", byte))
+                .collect::<String>();
+            writeln!(
+                f,
+                "    {:<32} ; {:#05X}: {}",
+                self.line_text(line),
+                line.addr,
+                bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassemble a ROM loaded at `origin`, which is also its entry point.
+pub fn disassemble(rom: &[u8], origin: u16) -> Disassembly {
+    let end = origin as usize + rom.len();
+    let in_rom = |addr: u16| (origin as usize..end).contains(&(addr as usize));
+    let decode_at = |addr: u16| {
+        let offset = addr.checked_sub(origin)? as usize;
+        let word = |offset: usize| -> Option<u16> {
+            Some(u16::from_be_bytes([
+                *rom.get(offset)?,
+                *rom.get(offset + 1)?,
+            ]))
+        };
+        Instruction::decode(word(offset)?, word(offset + 2))
+    };
+
+    // Follow the control flow from the entry point.
+    let mut code: BTreeMap<u16, Instruction> = BTreeMap::new();
+    let mut targets: BTreeSet<u16> = BTreeSet::new();
+    let mut pending = vec![origin];
+    while let Some(addr) = pending.pop() {
+        if code.contains_key(&addr) || !in_rom(addr) {
+            continue;
+        }
+        let Some(instruction) = decode_at(addr) else {
+            continue;
+        };
+        code.insert(addr, instruction);
+        let next = addr.wrapping_add(instruction.size() as u16);
+        match instruction {
+            Instruction::Ret | Instruction::Exit => {}
+            Instruction::Jp(target) => {
+                targets.insert(target);
+                pending.push(target);
+            }
+            Instruction::Call(target) => {
+                targets.insert(target);
+                pending.extend([target, next]);
+            }
+            // Jump tables are usually indexed from their first entry.
+            Instruction::JpV0Addr(target) => {
+                targets.insert(target);
+                pending.push(target);
+            }
+            Instruction::LdIAddr(target) | Instruction::LdILong(target) => {
+                targets.insert(target);
+                pending.push(next);
+            }
+            _ if instruction.is_skip() => {
+                let skipped = decode_at(next).map_or(2, |skipped| skipped.size());
+                pending.extend([next, next.wrapping_add(skipped as u16)]);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    let labels: BTreeMap<u16, String> = targets
+        .into_iter()
+        .filter(|addr| in_rom(*addr))
+        .map(|addr| (addr, format!("L{:03X}", addr)))
+        .collect();
+
+    // An instruction is only listed if no other line has to start inside its bytes.
+    let starts_inside = |addr: u16, size: usize| {
+        (1..size as u16).any(|offset| {
+            let addr = addr.wrapping_add(offset);
+            code.contains_key(&addr) || labels.contains_key(&addr)
+        })
+    };
+
+    let mut lines: Vec<Line> = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let addr = origin.wrapping_add(offset as u16);
+        if let Some(instruction) = code.get(&addr) {
+            if !starts_inside(addr, instruction.size()) {
+                lines.push(Line {
+                    addr,
+                    bytes: rom[offset..offset + instruction.size()].to_vec(),
+                    instruction: Some(*instruction),
+                });
+                offset += instruction.size();
+                continue;
+            }
+        }
+        // Extend the previous data line unless a label or an instruction starts here.
+        match lines.last_mut() {
+            Some(line)
+                if line.instruction.is_none()
+                    && line.bytes.len() < DATA_BYTES_PER_LINE
+                    && !labels.contains_key(&addr)
+                    && !code.contains_key(&addr) =>
+            {
+                line.bytes.push(rom[offset]);
+            }
+            _ => lines.push(Line {
+                addr,
+                bytes: vec![rom[offset]],
+                instruction: None,
+            }),
+        }
+        offset += 1;
+    }
+
+    Disassembly {
+        origin,
+        lines,
+        labels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, Instruction};
+    use crate::VM;
+
+    #[test]
+    fn decode_matches_vm_opcodes() {
+        for opcode in 0..=0xFFFF {
+            if opcode == 0xF000 {
+                continue;
+            }
+            assert_eq!(
+                Instruction::decode(opcode, None).map(|instruction| instruction.to_string()),
+                VM::opcode_mnemonic(opcode),
+                "opcode {:04X}",
+                opcode
+            );
+        }
+        assert_eq!(
+            Instruction::decode(0xF000, Some(0x1234)),
+            Some(Instruction::LdILong(0x1234))
+        );
+        assert_eq!(Instruction::decode(0xF000, None), None);
+    }
+
+    #[test]
+    fn encode_decoded_opcodes() {
+        for opcode in 0..=0xFFFFu16 {
+            if let Some(instruction) = Instruction::decode(opcode, Some(0x1234)) {
+                let bytes = instruction.encode();
+                assert_eq!(bytes.len(), instruction.size());
+                assert_eq!(bytes[..2], opcode.to_be_bytes(), "opcode {:04X}", opcode);
+            }
+        }
+    }
+
+    #[test]
+    fn code_data_and_labels() {
+        let rom = [
+            0xA2, 0x0C, // 0x200: LD I, 0x20C
+            0x22, 0x08, // 0x202: CALL 0x208
+            0x3F, 0x01, // 0x204: SE VF, 0x01
+            0x12, 0x00, // 0x206: JP 0x200
+            0xD0, 0x12, // 0x208: DRW V0, V1, 2
+            0x00, 0xEE, // 0x20A: RET
+            0xFF, 0x81, // 0x20C: sprite data
+        ];
+        let disassembly = disassemble(&rom, 0x200);
+
+        let text = disassembly
+            .lines
+            .iter()
+            .map(|line| disassembly.line_text(line))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            text,
+            [
+                "LD I, L20C",
+                "CALL L208",
+                "SE VF, 0x01",
+                "JP L200",
+                "DRW V0, V1, 2",
+                "RET",
+                "db 0xFF, 0x81"
+            ]
+        );
+        assert_eq!(disassembly.label(0x208), Some("L208"));
+        assert_eq!(disassembly.label(0x204), None);
+        assert!(disassembly.to_string().starts_with("L200:\n    LD I, L20C"));
+    }
+
+    #[test]
+    fn skip_over_long_instruction() {
+        let rom = [
+            0x30, 0x00, // 0x200: SE V0, 0x00
+            0xF0, 0x00, 0x12, 0x34, // 0x202: LD I, 0x1234
+            0x00, 0xFD, // 0x206: EXIT
+            0x12, 0x34, // 0x208: data, not reachable
+        ];
+        let disassembly = disassemble(&rom, 0x200);
+
+        let instructions = disassembly
+            .lines
+            .iter()
+            .map(|line| line.instruction)
+            .collect::<Vec<Option<Instruction>>>();
+        assert_eq!(
+            instructions,
+            [
+                Some(Instruction::SeVxByte(0, 0)),
+                Some(Instruction::LdILong(0x1234)),
+                Some(Instruction::Exit),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{
+        CHIP8_FRAME_MICROS, CHIP8_MAX_CYCLES_PER_FRAME, CHIP8_REWIND_FRAMES, CHIP8_SPEED_STEP,
+        CHIP8_TOTAL_STATE_SLOTS,
+    },
+    errors::VMError,
+    rewind::RewindBuffer,
+    Audio, Capture, ChipKey, Host, HostEvent, Movie, RunLength, RunSummary, VM,
+};
+
+/// Movie recorded or replayed by a `Driver`.
+pub enum MovieMode {
+    None,
+    /// Record the session, the movie is written to the file at the end of the run.
+    Record(Movie, String),
+    /// Replay the movie, the key input of the host is ignored until the end of the movie.
+    Replay(Movie),
+}
+
+impl MovieMode {
+    fn replaying(&self, frame: u64) -> bool {
+        matches!(self, MovieMode::Replay(movie) if frame < movie.frames())
+    }
+
+    /// Rewind, state loading and speed changes would make the movie impossible to replay, so
+    /// they are disabled while recording or replaying.
+    fn locked(&self, frame: u64) -> bool {
+        matches!(self, MovieMode::Record(..)) || self.replaying(frame)
+    }
+
+    fn record_key(&mut self, frame: u64, key: ChipKey, pressed: bool) {
+        if let MovieMode::Record(movie, _) = self {
+            movie.record_key(frame, key, pressed);
+        }
+    }
+}
+
+/// `Driver` is the emulation loop shared by the hosts: every 60 Hz frame it applies the host
+/// input, runs the VM for a fixed number of instructions, presents the screen and updates the
+/// sound. It also handles what doesn't depend on the host: the speed, the quick save states,
+/// the rewind buffer and the movies.
+pub struct Driver {
+    cycles_per_frame: usize,
+    realtime: bool,
+    length: Option<RunLength>,
+    state_file_prefix: Option<String>,
+    state_slot: u8,
+    rewind: Option<RewindBuffer>,
+    movie: MovieMode,
+    capture: Option<Box<dyn Capture>>,
+    audio_sink: Option<Box<dyn Audio>>,
+}
+
+impl Driver {
+    /// A driver for interactive hosts, executing `cycles_per_frame` instructions per frame in
+    /// real time, with a 10 seconds rewind buffer and without save states.
+    pub fn new(cycles_per_frame: usize) -> Self {
+        Driver {
+            cycles_per_frame: cycles_per_frame.max(1),
+            realtime: true,
+            length: None,
+            state_file_prefix: None,
+            state_slot: 0,
+            rewind: Some(RewindBuffer::new(CHIP8_REWIND_FRAMES)),
+            movie: MovieMode::None,
+            capture: None,
+            audio_sink: None,
+        }
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    /// Wait for the end of each 60 Hz frame, or run as fast as possible.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.realtime = realtime;
+    }
+
+    /// Stop the run after the given length, otherwise it runs until the host quits or the
+    /// program exits.
+    pub fn set_length(&mut self, length: Option<RunLength>) {
+        self.length = length;
+    }
+
+    /// Enable the quick save states, stored next to `state_file_prefix`, one file per slot.
+    pub fn set_state_file_prefix(&mut self, state_file_prefix: Option<String>) {
+        self.state_file_prefix = state_file_prefix;
+    }
+
+    /// Enable or disable rewinding.
+    pub fn set_rewind(&mut self, rewind: bool) {
+        self.rewind = rewind.then(|| RewindBuffer::new(CHIP8_REWIND_FRAMES));
+    }
+
+    pub fn set_movie(&mut self, movie: MovieMode) {
+        self.movie = movie;
+    }
+
+    /// Capture the presented frames and take the screenshots requested by the host.
+    pub fn set_capture(&mut self, capture: Option<Box<dyn Capture>>) {
+        self.capture = capture;
+    }
+
+    /// Render the sound of every frame to `audio_sink` as well as to the host.
+    pub fn set_audio_sink(&mut self, audio_sink: Option<Box<dyn Audio>>) {
+        self.audio_sink = audio_sink;
+    }
+
+    /// Run the VM, with the ROM already loaded, on the host.
+    pub fn run(&mut self, vm: &mut VM, host: &mut impl Host) -> Result<RunSummary, VMError> {
+        let mut summary = RunSummary {
+            cycles: 0,
+            frames: 0,
+            exited: false,
+        };
+        let mut rewinding = false;
+
+        'running: loop {
+            let frame_start = Instant::now();
+            if self.frame_cycles(&summary) == 0 {
+                break;
+            }
+
+            let frame = summary.frames;
+            for event in host.poll(frame).map_err(VMError::HostFailed)? {
+                match event {
+                    HostEvent::Quit => break 'running,
+                    HostEvent::Rewind(true)
+                    | HostEvent::LoadState
+                    | HostEvent::SpeedDown
+                    | HostEvent::SpeedUp
+                        if self.movie.locked(frame) =>
+                    {
+                        host.message(
+                            "Rewind, state loading and speed changes are disabled with movies",
+                        )
+                    }
+                    HostEvent::Key(key, pressed) => {
+                        if !self.movie.replaying(frame) {
+                            self.movie.record_key(frame, key, pressed);
+                            vm.set_key(key, pressed);
+                        }
+                    }
+                    HostEvent::SpeedDown => {
+                        self.cycles_per_frame = self
+                            .cycles_per_frame
+                            .saturating_sub(CHIP8_SPEED_STEP)
+                            .max(1);
+                        self.print_speed(host);
+                    }
+                    HostEvent::SpeedUp => {
+                        self.cycles_per_frame = (self.cycles_per_frame + CHIP8_SPEED_STEP)
+                            .min(CHIP8_MAX_CYCLES_PER_FRAME.max(self.cycles_per_frame));
+                        self.print_speed(host);
+                    }
+                    HostEvent::SaveState => self.quick_save(vm, host),
+                    HostEvent::LoadState => self.quick_load(vm, host),
+                    HostEvent::PreviousSlot => {
+                        self.state_slot = (self.state_slot + CHIP8_TOTAL_STATE_SLOTS - 1)
+                            % CHIP8_TOTAL_STATE_SLOTS;
+                        host.message(&format!("State slot {}", self.state_slot));
+                    }
+                    HostEvent::NextSlot => {
+                        self.state_slot = (self.state_slot + 1) % CHIP8_TOTAL_STATE_SLOTS;
+                        host.message(&format!("State slot {}", self.state_slot));
+                    }
+                    HostEvent::Rewind(rewind) => rewinding = rewind && self.rewind.is_some(),
+                    HostEvent::Screenshot => self.screenshot(vm, host),
+                    HostEvent::Colors(colors) => {
+                        if let Some(capture) = &mut self.capture {
+                            capture.set_colors(colors);
+                        }
+                    }
+                }
+            }
+
+            let cycles = self.frame_cycles(&summary);
+            if rewinding {
+                if let Some(state) = self.rewind.as_mut().and_then(|buffer| buffer.pop()) {
+                    vm.load_state(&state)?;
+                    self.present(vm, host, true)?;
+                }
+                self.update_sound(vm, host, false)?;
+            } else {
+                if let Some(buffer) = &mut self.rewind {
+                    buffer.push(&vm.save_state());
+                }
+                self.apply_movie_events(vm, host, frame);
+                let frame_summary = vm.run_frame(cycles as usize)?;
+                summary.cycles += frame_summary.cycles as u64;
+                if frame_summary.cycles == self.cycles_per_frame {
+                    summary.frames += 1;
+                }
+                self.present(vm, host, frame_summary.draw_screen)?;
+                if frame_summary.exit {
+                    summary.exited = true;
+                    break 'running;
+                }
+                self.update_sound(vm, host, vm.registers_st() > 0)?;
+            }
+
+            // Wait for the end of the frame.
+            if self.realtime {
+                let frame_duration = Duration::from_micros(CHIP8_FRAME_MICROS);
+                if let Some(remaining) = frame_duration.checked_sub(frame_start.elapsed()) {
+                    thread::sleep(remaining);
+                }
+            }
+        }
+
+        // The sink only gets the frames that ran, so the sound stops on the host alone.
+        host.update(vm, false).map_err(VMError::HostFailed)?;
+        host.finish().map_err(VMError::HostFailed)?;
+        if let Some(audio_sink) = &mut self.audio_sink {
+            audio_sink.finish().map_err(VMError::HostFailed)?;
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.finish(vm).map_err(VMError::HostFailed)?;
+        }
+        self.finish_movie(host, summary.frames)?;
+        Ok(summary)
+    }
+
+    /// Instructions to execute in the next frame, less than a frame at the end of a run of a
+    /// given number of cycles, 0 when the run is over.
+    fn frame_cycles(&self, summary: &RunSummary) -> u64 {
+        match self.length {
+            Some(RunLength::Cycles(cycles)) => {
+                (cycles - summary.cycles).min(self.cycles_per_frame as u64)
+            }
+            Some(RunLength::Frames(frames)) if summary.frames >= frames => 0,
+            _ => self.cycles_per_frame as u64,
+        }
+    }
+
+    fn present(&mut self, vm: &VM, host: &mut impl Host, changed: bool) -> Result<(), VMError> {
+        host.present(vm, changed).map_err(VMError::HostFailed)?;
+        if let Some(capture) = &mut self.capture {
+            capture.frame(vm, changed).map_err(VMError::HostFailed)?;
+        }
+        Ok(())
+    }
+
+    fn update_sound(
+        &mut self,
+        vm: &VM,
+        host: &mut impl Host,
+        playing: bool,
+    ) -> Result<(), VMError> {
+        host.update(vm, playing).map_err(VMError::HostFailed)?;
+        if let Some(audio_sink) = &mut self.audio_sink {
+            audio_sink
+                .update(vm, playing)
+                .map_err(VMError::HostFailed)?;
+        }
+        Ok(())
+    }
+
+    fn screenshot(&mut self, vm: &VM, host: &mut impl Host) {
+        let Some(capture) = &mut self.capture else {
+            return host.message("Screenshots are disabled");
+        };
+        match capture.screenshot(vm) {
+            Ok(file_name) => host.message(&format!("Screenshot saved to {}", file_name)),
+            Err(e) => host.message(&format!("Unable to save screenshot: {}", e)),
+        }
+    }
+
+    fn print_speed(&self, host: &mut impl Host) {
+        host.message(&format!(
+            "Speed {} instructions per frame ({} per second)",
+            self.cycles_per_frame,
+            self.cycles_per_frame * 60
+        ));
+    }
+
+    fn state_file_name(&self) -> Option<String> {
+        let prefix = self.state_file_prefix.as_ref()?;
+        Some(format!("{}.state{}", prefix, self.state_slot))
+    }
+
+    fn quick_save(&self, vm: &VM, host: &mut impl Host) {
+        let Some(file_name) = self.state_file_name() else {
+            return host.message("Save states are disabled");
+        };
+        match std::fs::write(&file_name, vm.save_state()) {
+            Ok(()) => host.message(&format!("State saved to {}", file_name)),
+            Err(e) => host.message(&format!("Unable to save state to {}: {}", file_name, e)),
+        }
+    }
+
+    fn quick_load(&self, vm: &mut VM, host: &mut impl Host) {
+        let Some(file_name) = self.state_file_name() else {
+            return host.message("Save states are disabled");
+        };
+        let result = std::fs::read(&file_name)
+            .map_err(|e| e.to_string())
+            .and_then(|state| vm.load_state(&state).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => host.message(&format!("State loaded from {}", file_name)),
+            Err(e) => host.message(&format!("Unable to load state from {}: {}", file_name, e)),
+        }
+    }
+
+    fn apply_movie_events(&self, vm: &mut VM, host: &mut impl Host, frame: u64) {
+        if let MovieMode::Replay(movie) = &self.movie {
+            movie.apply_events(vm, frame);
+            if frame + 1 == movie.frames() {
+                host.message("Replay finished");
+            }
+        }
+    }
+
+    fn finish_movie(&mut self, host: &mut impl Host, frames: u64) -> Result<(), VMError> {
+        if let MovieMode::Record(movie, file_name) = &mut self.movie {
+            movie.set_frames(frames);
+            std::fs::write(&*file_name, movie.to_string()).map_err(|e| {
+                VMError::HostFailed(format!("unable to save movie to {}: {}", file_name, e))
+            })?;
+            host.message(&format!("Movie saved to {}", file_name));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        state_dump, Audio, Capture, ChipKey, Driver, HostEvent, Input, Movie, MovieMode, Quirks,
+        RngAlgorithm, Video, VM,
+    };
+
+    // 0x200: LD V0, K, 0x202: LD ST, V0, 0x204: LD F, V0, 0x206: DRW V1, V1, 5, 0x208: JP 0x208
+    const PROGRAM: [u8; 10] = [0xF0, 0x0A, 0xF0, 0x18, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x08];
+
+    /// Host sending scripted events and counting what the driver presents.
+    #[derive(Default)]
+    struct TestHost {
+        events: Vec<(u64, HostEvent)>,
+        frames_presented: u64,
+        screens_drawn: u64,
+        sound_frames: u64,
+        messages: Vec<String>,
+    }
+
+    impl Video for TestHost {
+        fn present(&mut self, _vm: &VM, screen_changed: bool) -> Result<(), String> {
+            self.frames_presented += 1;
+            self.screens_drawn += screen_changed as u64;
+            Ok(())
+        }
+
+        fn message(&mut self, text: &str) {
+            self.messages.push(text.to_string());
+        }
+    }
+
+    impl Audio for TestHost {
+        fn update(&mut self, _vm: &VM, playing: bool) -> Result<(), String> {
+            self.sound_frames += playing as u64;
+            Ok(())
+        }
+    }
+
+    impl Input for TestHost {
+        fn poll(&mut self, frame: u64) -> Result<Vec<HostEvent>, String> {
+            let (events, next) = self.events.iter().partition(|(at, _)| *at == frame);
+            self.events = next;
+            Ok(events.into_iter().map(|(_, event)| event).collect())
+        }
+    }
+
+    fn driver(movie: MovieMode) -> (Driver, VM) {
+        let mut chip8 = VM::new(Quirks::CHIP48);
+        chip8.load_program(&PROGRAM).unwrap();
+        let mut driver = Driver::new(4);
+        driver.set_realtime(false);
+        driver.set_movie(movie);
+        (driver, chip8)
+    }
+
+    #[test]
+    fn drive_host() {
+        let (mut driver, mut chip8) = driver(MovieMode::None);
+        let mut host = TestHost {
+            events: vec![
+                (0, HostEvent::SpeedUp),
+                (2, HostEvent::Key(ChipKey::Key6, true)),
+                (3, HostEvent::Key(ChipKey::Key6, false)),
+                (20, HostEvent::Quit),
+            ],
+            ..TestHost::default()
+        };
+        let summary = driver.run(&mut chip8, &mut host).unwrap();
+        assert_eq!((summary.frames, summary.cycles), (20, 120));
+        assert_eq!(host.frames_presented, 20);
+        assert_eq!(host.screens_drawn, 1);
+        assert_eq!(host.sound_frames, 6);
+        assert_eq!(
+            host.messages,
+            ["Speed 6 instructions per frame (360 per second)"]
+        );
+        assert_eq!(driver.cycles_per_frame(), 6);
+        assert_eq!(chip8.registers_v(0), 6);
+    }
+
+    #[test]
+    fn replay_ignores_host_keys() {
+        let mut movie = Movie::new(&PROGRAM, 0, RngAlgorithm::Xorshift, Quirks::CHIP48, 4);
+        movie.record_key(2, ChipKey::Key9, true);
+        movie.record_key(3, ChipKey::Key9, false);
+        movie.set_frames(10);
+        let (mut driver, mut chip8) = driver(MovieMode::Replay(movie));
+        let mut host = TestHost {
+            events: vec![
+                (1, HostEvent::Key(ChipKey::Key6, true)),
+                (1, HostEvent::SpeedUp),
+                (2, HostEvent::Key(ChipKey::Key6, false)),
+                (10, HostEvent::Quit),
+            ],
+            ..TestHost::default()
+        };
+        driver.run(&mut chip8, &mut host).unwrap();
+        assert_eq!(chip8.registers_v(0), 9);
+        assert_eq!(driver.cycles_per_frame(), 4);
+        assert_eq!(
+            host.messages,
+            [
+                "Rewind, state loading and speed changes are disabled with movies",
+                "Replay finished"
+            ]
+        );
+        assert!(state_dump(&chip8).unwrap().starts_with("PC=0x208"));
+    }
+
+    /// Capture keeping the colours of every frame.
+    struct ColorsCapture {
+        colors: [[u8; 3]; 4],
+        frames: Rc<RefCell<Vec<[[u8; 3]; 4]>>>,
+    }
+
+    impl Capture for ColorsCapture {
+        fn frame(&mut self, _vm: &VM, _screen_changed: bool) -> Result<(), String> {
+            self.frames.borrow_mut().push(self.colors);
+            Ok(())
+        }
+
+        fn screenshot(&mut self, _vm: &VM) -> Result<String, String> {
+            Ok(String::new())
+        }
+
+        fn finish(&mut self, _vm: &VM) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn set_colors(&mut self, colors: [[u8; 3]; 4]) {
+            self.colors = colors;
+        }
+    }
+
+    #[test]
+    fn capture_follows_host_colors() {
+        let (mut driver, mut chip8) = driver(MovieMode::None);
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        driver.set_capture(Some(Box::new(ColorsCapture {
+            colors: [[0; 3]; 4],
+            frames: frames.clone(),
+        })));
+        let green = [[0, 32, 0], [51, 255, 51], [0, 136, 68], [204, 255, 153]];
+        let mut host = TestHost {
+            events: vec![(2, HostEvent::Colors(green)), (4, HostEvent::Quit)],
+            ..TestHost::default()
+        };
+        driver.run(&mut chip8, &mut host).unwrap();
+        assert_eq!(*frames.borrow(), [[[0; 3]; 4], [[0; 3]; 4], green, green]);
+    }
+}
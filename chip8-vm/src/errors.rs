@@ -6,13 +6,22 @@ pub enum VMError {
     MemoryOutOfBounds(usize),
     StackOutOfBounds(usize),
     StackOverflow,
-    KeyMapNotFound,
     ReservedMemoryWriteAttempt,
     ScreenOutOfBounds(usize, usize),
     ProgramSizeOverflow,
     ProgramCounterOverflow,
     ProgramCounterUnderflow,
     InvalidOpcode(u16),
+    RplFlagsOutOfBounds(usize),
+    InvalidState,
+    UnsupportedStateVersion(u8),
+    InvalidCondition(String),
+    InvalidAssembly(String, usize, String),
+    InvalidKeyScript(usize, String),
+    InvalidMovie(usize, String),
+    InvalidTrace(usize, String),
+    TraceWriteFailed(String),
+    HostFailed(String),
 }
 
 impl Error for VMError {}
@@ -29,9 +38,6 @@ impl fmt::Display for VMError {
             VMError::StackOverflow => {
                 write!(f, "impossible to increment SP register, stackoverflow")
             }
-            VMError::KeyMapNotFound => {
-                write!(f, "key map not found")
-            }
             VMError::ReservedMemoryWriteAttempt => {
                 write!(f, "reserved memory write attempt (0x000 to 0x1FF)")
             }
@@ -50,6 +56,43 @@ impl fmt::Display for VMError {
             VMError::InvalidOpcode(binary_opcode) => {
                 write!(f, "invalid opcode instruction: {:#02X}", binary_opcode)
             }
+            VMError::RplFlagsOutOfBounds(index) => {
+                write!(f, "invalid RPL user flag index: {}", index)
+            }
+            VMError::InvalidState => {
+                write!(f, "invalid or corrupted save state")
+            }
+            VMError::UnsupportedStateVersion(version) => {
+                write!(f, "unsupported save state version: {}", version)
+            }
+            VMError::InvalidCondition(ref reason) => {
+                write!(f, "invalid condition: {}", reason)
+            }
+            VMError::InvalidAssembly(ref file, 0, ref reason) => {
+                write!(f, "{}: {}", file, reason)
+            }
+            VMError::InvalidAssembly(ref file, line, ref reason) => {
+                write!(f, "{}:{}: {}", file, line, reason)
+            }
+            VMError::InvalidKeyScript(line, ref reason) => {
+                write!(f, "invalid key script line {}: {}", line, reason)
+            }
+            VMError::InvalidMovie(0, ref reason) => {
+                write!(f, "invalid movie: {}", reason)
+            }
+            VMError::InvalidMovie(line, ref reason) => {
+                write!(f, "invalid movie line {}: {}", line, reason)
+            }
+            VMError::InvalidTrace(0, ref reason) => {
+                write!(f, "invalid trace: {}", reason)
+            }
+            VMError::InvalidTrace(line, ref reason) => {
+                write!(f, "invalid trace line {}: {}", line, reason)
+            }
+            VMError::TraceWriteFailed(ref reason) => {
+                write!(f, "unable to write the trace: {}", reason)
+            }
+            VMError::HostFailed(ref reason) => write!(f, "front-end error: {}", reason),
         }
     }
 }
@@ -0,0 +1,352 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    config::CHIP8_DEFAULT_CYCLES_PER_FRAME, errors::VMError, Audio, Capture, ChipKey, Driver,
+    HostEvent, Input, Video, VM,
+};
+
+/// How long a headless run lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunLength {
+    /// Number of executed instructions.
+    Cycles(u64),
+    /// Number of 60 Hz frames.
+    Frames(u64),
+}
+
+/// Key press or release applied at the start of a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub key: ChipKey,
+    pub pressed: bool,
+}
+
+/// Scripted key input for headless runs. The text format has one `FRAME KEY down|up` event
+/// per line, the frame in decimal and the key in hexadecimal, and `#` comments:
+///
+/// ```text
+/// # press 5 for half a second
+/// 60 5 down
+/// 90 5 up
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    events: Vec<KeyEvent>,
+}
+
+impl KeyScript {
+    pub fn new(mut events: Vec<KeyEvent>) -> Self {
+        events.sort_by_key(|event| event.frame);
+        KeyScript { events }
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+}
+
+impl fmt::Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let action = if self.pressed { "down" } else { "up" };
+        write!(f, "{} {:X} {}", self.frame, self.key.value(), action)
+    }
+}
+
+impl KeyEvent {
+    /// Parse a `FRAME KEY down|up` key script line, without comments.
+    pub(crate) fn parse(line: &str) -> Result<KeyEvent, &'static str> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let [frame, key, action] = words[..] else {
+            return Err("expected 'FRAME KEY down|up'");
+        };
+        let frame = frame.parse::<u64>().map_err(|_| "invalid frame number")?;
+        let key = u8::from_str_radix(key.trim_start_matches("0x"), 16)
+            .ok()
+            .and_then(ChipKey::from_value)
+            .ok_or("invalid key, expected 0 to F")?;
+        let pressed = match action.to_ascii_lowercase().as_str() {
+            "down" => true,
+            "up" => false,
+            _ => return Err("invalid action, expected 'down' or 'up'"),
+        };
+        Ok(KeyEvent {
+            frame,
+            key,
+            pressed,
+        })
+    }
+}
+
+impl FromStr for KeyScript {
+    type Err = VMError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let event = KeyEvent::parse(line)
+                .map_err(|reason| VMError::InvalidKeyScript(index + 1, reason.to_string()))?;
+            events.push(event);
+        }
+        Ok(KeyScript::new(events))
+    }
+}
+
+/// Summary of a headless run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunSummary {
+    pub cycles: u64,
+    pub frames: u64,
+    /// True if the program executed the EXIT instruction before the end of the run.
+    pub exited: bool,
+}
+
+/// `Headless` runs a `VM` without any video, audio or input device, so ROMs can be tested
+/// automatically. Time is emulated: every frame executes a fixed number of instructions and
+/// the key script events are applied at the start of their frame.
+pub struct Headless {
+    cycles_per_frame: usize,
+    keys: KeyScript,
+}
+
+impl Default for Headless {
+    fn default() -> Self {
+        Headless {
+            cycles_per_frame: CHIP8_DEFAULT_CYCLES_PER_FRAME,
+            keys: KeyScript::default(),
+        }
+    }
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Instructions executed per frame.
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: usize) {
+        self.cycles_per_frame = cycles_per_frame.max(1);
+    }
+
+    pub fn set_key_script(&mut self, keys: KeyScript) {
+        self.keys = keys;
+    }
+
+    pub(crate) fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    pub(crate) fn key_script(&self) -> &KeyScript {
+        &self.keys
+    }
+
+    /// Run the program loaded in the VM for the given length, or until it exits.
+    pub fn run(&self, vm: &mut VM, length: RunLength) -> Result<RunSummary, VMError> {
+        self.driver(length).run(
+            vm,
+            &mut ScriptHost {
+                events: self.keys.events(),
+            },
+        )
+    }
+
+    /// Run the program like `run`, with every frame captured and the sound rendered to
+    /// `audio_sink`.
+    pub fn run_with_sinks(
+        &self,
+        vm: &mut VM,
+        length: RunLength,
+        capture: Option<Box<dyn Capture>>,
+        audio_sink: Option<Box<dyn Audio>>,
+    ) -> Result<RunSummary, VMError> {
+        let mut driver = self.driver(length);
+        driver.set_capture(capture);
+        driver.set_audio_sink(audio_sink);
+        driver.run(
+            vm,
+            &mut ScriptHost {
+                events: self.keys.events(),
+            },
+        )
+    }
+
+    fn driver(&self, length: RunLength) -> Driver {
+        let mut driver = Driver::new(self.cycles_per_frame);
+        driver.set_realtime(false);
+        driver.set_rewind(false);
+        driver.set_length(Some(length));
+        driver
+    }
+}
+
+/// Host without video and audio, with the key script as input.
+struct ScriptHost<'a> {
+    /// Events of the next frames.
+    events: &'a [KeyEvent],
+}
+
+impl Video for ScriptHost<'_> {
+    fn present(&mut self, _vm: &VM, _screen_changed: bool) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Audio for ScriptHost<'_> {
+    fn update(&mut self, _vm: &VM, _playing: bool) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Input for ScriptHost<'_> {
+    fn poll(&mut self, frame: u64) -> Result<Vec<HostEvent>, String> {
+        let count = self.events.partition_point(|event| event.frame <= frame);
+        let (events, next) = self.events.split_at(count);
+        self.events = next;
+        Ok(events
+            .iter()
+            .map(|event| HostEvent::Key(event.key, event.pressed))
+            .collect())
+    }
+}
+
+/// Text dump of the registers, the stack and the framebuffer. Pixels are `.` when clear, and
+/// `#`, `+` or `*` for the first, second or both XO-CHIP bit planes.
+pub fn state_dump(vm: &VM) -> Result<String, VMError> {
+    let registers = (0..16)
+        .map(|index| format!("V{:X}={:#04X}", index, vm.registers_v(index)))
+        .collect::<Vec<String>>();
+    let stack = (0..vm.registers_sp())
+        .map(|level| vm.stack_get(level).map(|addr| format!("{:#05X}", addr)))
+        .collect::<Result<Vec<String>, VMError>>()?;
+    let mut lines = vec![
+        format!(
+            "PC={:#05X} I={:#05X} SP={} DT={} ST={}",
+            vm.registers_pc(),
+            vm.registers_i(),
+            vm.registers_sp(),
+            vm.registers_dt(),
+            vm.registers_st()
+        ),
+        registers.join(" "),
+        format!("STACK=[{}]", stack.join(", ")),
+        format!("SCREEN={}x{}", vm.screen_width(), vm.screen_height()),
+    ];
+    for y in 0..vm.screen_height() {
+        let row = (0..vm.screen_width())
+            .map(|x| {
+                vm.screen_pixel_planes(x, y)
+                    .map(|planes| ['.', '#', '+', '*'][planes as usize])
+            })
+            .collect::<Result<String, VMError>>()?;
+        lines.push(row);
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Plain PBM (P1) image of the framebuffer, a pixel is set when any bit plane is set.
+pub fn screen_pbm(vm: &VM) -> Result<String, VMError> {
+    let mut lines = vec![
+        "P1".to_string(),
+        format!("{} {}", vm.screen_width(), vm.screen_height()),
+    ];
+    for y in 0..vm.screen_height() {
+        let row = (0..vm.screen_width())
+            .map(|x| {
+                vm.screen_is_pixel_set(x, y)
+                    .map(|set| if set { '1' } else { '0' })
+            })
+            .collect::<Result<String, VMError>>()?;
+        lines.push(row);
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{screen_pbm, state_dump, Headless, KeyEvent, KeyScript, RunLength};
+    use crate::{ChipKey, Quirks, VM};
+
+    // 0x200: LD V0, K, 0x202: LD F, V0, 0x204: DRW V1, V1, 5, 0x206: EXIT
+    const PROGRAM: [u8; 8] = [0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15, 0x00, 0xFD];
+
+    fn load_vm() -> VM {
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8.load_program(&PROGRAM).expect("Load program");
+        chip8
+    }
+
+    #[test]
+    fn parse_key_script() {
+        let script: KeyScript = "# comment\n20 a up\n\n10 0xA down # press\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            script.events(),
+            [
+                KeyEvent {
+                    frame: 10,
+                    key: ChipKey::KeyA,
+                    pressed: true
+                },
+                KeyEvent {
+                    frame: 20,
+                    key: ChipKey::KeyA,
+                    pressed: false
+                }
+            ]
+        );
+        assert_eq!(
+            "10 G down".parse::<KeyScript>().unwrap_err().to_string(),
+            "invalid key script line 1: invalid key, expected 0 to F"
+        );
+        assert!("10 1 press".parse::<KeyScript>().is_err());
+        assert!("10 1".parse::<KeyScript>().is_err());
+    }
+
+    #[test]
+    fn run_for_cycles_and_frames() {
+        let mut chip8 = load_vm();
+        let mut headless = Headless::new();
+        headless.set_cycles_per_frame(4);
+        // Without key input the program waits in the first instruction.
+        let summary = headless.run(&mut chip8, RunLength::Cycles(10)).unwrap();
+        assert_eq!((summary.cycles, summary.frames), (10, 2));
+        assert!(!summary.exited);
+        assert_eq!(chip8.registers_pc(), 0x200);
+
+        let summary = headless.run(&mut chip8, RunLength::Frames(3)).unwrap();
+        assert_eq!((summary.cycles, summary.frames), (12, 3));
+    }
+
+    #[test]
+    fn run_with_key_script() {
+        let mut chip8 = load_vm();
+        let mut headless = Headless::new();
+        headless.set_cycles_per_frame(4);
+        headless.set_key_script("2 7 down\n3 7 up".parse().unwrap());
+        let summary = headless.run(&mut chip8, RunLength::Frames(100)).unwrap();
+        assert!(summary.exited);
+        assert_eq!((summary.cycles, summary.frames), (16, 4));
+        assert_eq!(chip8.registers_v(0), 0x7);
+
+        let text = state_dump(&chip8).unwrap();
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "PC=0x206 I=0x023 SP=0 DT=0 ST=0");
+        assert!(lines[1].starts_with("V0=0x07 V1=0x00"));
+        assert_eq!(lines[2], "STACK=[]");
+        assert_eq!(lines[3], "SCREEN=64x32");
+        // Top row of the "7" font sprite.
+        assert!(lines[4].starts_with("####...."));
+        assert_eq!(lines.len(), 4 + 32);
+
+        let pbm = screen_pbm(&chip8).unwrap();
+        let lines = pbm.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[..2], ["P1", "64 32"]);
+        assert!(lines[2].starts_with("11110000"));
+        assert_eq!(lines.len(), 2 + 32);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{ChipKey, VM};
+
+/// Action of the user of a host, applied by the `Driver` at the start of the next frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostEvent {
+    /// A CHIP-8 key was pressed or released.
+    Key(ChipKey, bool),
+    Quit,
+    /// Run fewer instructions per frame.
+    SpeedDown,
+    /// Run more instructions per frame.
+    SpeedUp,
+    /// Save the state in the current slot.
+    SaveState,
+    /// Load the state of the current slot.
+    LoadState,
+    PreviousSlot,
+    NextSlot,
+    /// Start or stop rewinding, one frame back per frame.
+    Rewind(bool),
+    /// Save a screenshot with the `Capture` of the driver.
+    Screenshot,
+    /// The host switched to new colours for the bit plane combinations (none, first, second and
+    /// both), e.g. another palette, passed on to the `Capture`.
+    Colors([[u8; 3]; 4]),
+}
+
+/// Video output of a host.
+pub trait Video {
+    /// Show the VM screen at the end of a frame. `screen_changed` is false when the program
+    /// didn't draw during the frame, so the previous picture is still up to date.
+    fn present(&mut self, vm: &VM, screen_changed: bool) -> Result<(), String>;
+
+    /// Show a status message, e.g. the speed or the saved state file.
+    fn message(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Audio output of a host.
+pub trait Audio {
+    /// Play the VM sound, or stop it when `playing` is false, for the frame that just ran.
+    /// Called once per frame.
+    fn update(&mut self, vm: &VM, playing: bool) -> Result<(), String>;
+
+    /// Complete the output at the end of the run, e.g. the file of a recording.
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Input device of a host.
+pub trait Input {
+    /// The events since the last poll, called before running `frame`.
+    fn poll(&mut self, frame: u64) -> Result<Vec<HostEvent>, String>;
+}
+
+/// Capture of the frames presented by the `Driver`, whatever the host: screenshots and
+/// recordings.
+pub trait Capture {
+    /// Called with every presented frame, like `Video::present`.
+    fn frame(&mut self, vm: &VM, screen_changed: bool) -> Result<(), String>;
+
+    /// Save a screenshot of the VM screen and return the file name.
+    fn screenshot(&mut self, vm: &VM) -> Result<String, String>;
+
+    /// Use the new colours of the host for the next frames and screenshots.
+    fn set_colors(&mut self, _colors: [[u8; 3]; 4]) {}
+
+    /// Complete the files at the end of the run.
+    fn finish(&mut self, vm: &VM) -> Result<(), String>;
+}
+
+/// A front-end running the VM with the `Driver` loop: an SDL window, a terminal or no device
+/// at all for headless runs and tests.
+pub trait Host: Video + Audio + Input {}
+
+impl<T: Video + Audio + Input> Host for T {}
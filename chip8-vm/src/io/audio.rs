@@ -0,0 +1,65 @@
+use crate::{
+    config::{CHIP8_AUDIO_BUZZER_FREQUENCY, CHIP8_AUDIO_DEFAULT_PITCH, CHIP8_AUDIO_PATTERN_SIZE},
+    errors::VMError,
+    state::{StateReader, StateWriter},
+};
+
+/// XO-CHIP audio state: a 128 bits pattern played in a loop while the sound timer is active,
+/// at a playback rate given by the pitch register.
+pub struct Audio {
+    pattern: [u8; CHIP8_AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    /// True once the program set the pattern or the pitch. Until then the sound timer beeps at
+    /// 440 Hz like the classic interpreters.
+    programmed: bool,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self {
+            // Square wave of 8 bits period, 500 Hz at the default pitch.
+            pattern: [0xF0; CHIP8_AUDIO_PATTERN_SIZE],
+            pitch: CHIP8_AUDIO_DEFAULT_PITCH,
+            programmed: false,
+        }
+    }
+}
+
+impl Audio {
+    pub(crate) fn pattern(&self) -> &[u8; CHIP8_AUDIO_PATTERN_SIZE] {
+        &self.pattern
+    }
+
+    pub(crate) fn set_pattern(&mut self, pattern: [u8; CHIP8_AUDIO_PATTERN_SIZE]) {
+        self.pattern = pattern;
+        self.programmed = true;
+    }
+
+    pub(crate) fn set_pitch(&mut self, pitch: u8) {
+        self.pitch = pitch;
+        self.programmed = true;
+    }
+
+    /// Pattern playback rate in bits per second, 4000 * 2 ^ ((pitch - 64) / 48), or the rate
+    /// playing the default pattern at 440 Hz if the program didn't set the pattern or the pitch.
+    pub(crate) fn playback_rate(&self) -> f32 {
+        if !self.programmed {
+            return CHIP8_AUDIO_BUZZER_FREQUENCY * 8.0;
+        }
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.pattern);
+        state.write_u8(self.pitch);
+        state.write_bool(self.programmed);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        self.pattern
+            .copy_from_slice(state.read_bytes(CHIP8_AUDIO_PATTERN_SIZE)?);
+        self.pitch = state.read_u8()?;
+        self.programmed = state.read_bool()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,76 @@
-use crate::{config::CHIP8_TOTAL_KEYS, errors::VMError};
+use crate::{
+    config::CHIP8_TOTAL_KEYS,
+    errors::VMError,
+    state::{StateReader, StateWriter},
+};
+
+/// Key of the CHIP-8 hexadecimal keypad, 0x0 to 0xF.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChipKey {
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    KeyA,
+    KeyB,
+    KeyC,
+    KeyD,
+    KeyE,
+    KeyF,
+}
+
+impl ChipKey {
+    /// All the keys, in value order.
+    pub const ALL: [ChipKey; CHIP8_TOTAL_KEYS] = [
+        ChipKey::Key0,
+        ChipKey::Key1,
+        ChipKey::Key2,
+        ChipKey::Key3,
+        ChipKey::Key4,
+        ChipKey::Key5,
+        ChipKey::Key6,
+        ChipKey::Key7,
+        ChipKey::Key8,
+        ChipKey::Key9,
+        ChipKey::KeyA,
+        ChipKey::KeyB,
+        ChipKey::KeyC,
+        ChipKey::KeyD,
+        ChipKey::KeyE,
+        ChipKey::KeyF,
+    ];
+
+    /// Layout of the keypad, row by row:
+    ///
+    /// ```text
+    /// 1 2 3 C
+    /// 4 5 6 D
+    /// 7 8 9 E
+    /// A 0 B F
+    /// ```
+    pub const KEYPAD: [[ChipKey; 4]; 4] = [
+        [ChipKey::Key1, ChipKey::Key2, ChipKey::Key3, ChipKey::KeyC],
+        [ChipKey::Key4, ChipKey::Key5, ChipKey::Key6, ChipKey::KeyD],
+        [ChipKey::Key7, ChipKey::Key8, ChipKey::Key9, ChipKey::KeyE],
+        [ChipKey::KeyA, ChipKey::Key0, ChipKey::KeyB, ChipKey::KeyF],
+    ];
+
+    /// The key with the given value, `None` if it is greater than 0xF.
+    pub fn from_value(value: u8) -> Option<ChipKey> {
+        ChipKey::ALL.get(value as usize).copied()
+    }
+
+    /// The key value, 0x0 to 0xF.
+    pub fn value(self) -> u8 {
+        self as u8
+    }
+}
 
 pub struct Keyboard {
     keyboard: [bool; CHIP8_TOTAL_KEYS],
@@ -13,16 +85,8 @@ impl Default for Keyboard {
 }
 
 impl Keyboard {
-    pub(crate) fn key_down(&mut self, key: i32, keymap: &[(i32, usize)]) {
-        self.map_to_vkey(key, keymap)
-            .map(|key| self.keyboard[key as usize] = true)
-            .ok();
-    }
-
-    pub(crate) fn key_up(&mut self, key: i32, keymap: &[(i32, usize)]) {
-        self.map_to_vkey(key, keymap)
-            .map(|key| self.keyboard[key as usize] = false)
-            .ok();
+    pub(crate) fn set_key(&mut self, key: ChipKey, down: bool) {
+        self.keyboard[key as usize] = down;
     }
 
     pub(crate) fn is_key_down(&self, vkey: u8) -> bool {
@@ -33,12 +97,16 @@ impl Keyboard {
         !self.keyboard[vkey as usize]
     }
 
-    pub(crate) fn map_to_vkey(&self, key: i32, keymap: &[(i32, usize)]) -> Result<usize, VMError> {
-        for (sdl_k, ch8_k) in keymap.into_iter() {
-            if key == *sdl_k {
-                return Ok(*ch8_k);
-            }
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        for key in self.keyboard {
+            state.write_bool(key);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        for key in self.keyboard.iter_mut() {
+            *key = state.read_bool()?;
         }
-        Err(VMError::KeyMapNotFound)
+        Ok(())
     }
 }
@@ -1,5 +1,8 @@
+mod audio;
 mod keyboard;
 mod screen;
 
+pub(crate) use audio::Audio;
+pub use keyboard::ChipKey;
 pub(crate) use keyboard::Keyboard;
 pub(crate) use screen::Screen;
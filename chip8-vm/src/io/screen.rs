@@ -1,81 +1,205 @@
-use std::io::Write;
-
 use crate::{
-    config::{CHIP8_SCREEN_HEIGHT, CHIP8_SCREEN_WIDTH},
+    config::{
+        CHIP8_HIRES_SCREEN_HEIGHT, CHIP8_HIRES_SCREEN_WIDTH, CHIP8_SCREEN_HEIGHT,
+        CHIP8_SCREEN_WIDTH, CHIP8_TOTAL_PLANES,
+    },
     errors::VMError,
     memory::RAM,
+    state::{StateReader, StateWriter},
 };
 
+/// The screen pixels hold one bit per XO-CHIP bit plane, bit 0 for the first plane and bit 1
+/// for the second one. Classic CHIP-8 and SUPER-CHIP programs only use the first plane.
 pub struct Screen {
-    pixels: [[bool; CHIP8_SCREEN_HEIGHT]; CHIP8_SCREEN_WIDTH],
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec<u8>>,
+    planes: u8,
 }
 
 impl Default for Screen {
     fn default() -> Self {
         Self {
-            pixels: [[false; CHIP8_SCREEN_HEIGHT]; CHIP8_SCREEN_WIDTH],
+            width: CHIP8_SCREEN_WIDTH,
+            height: CHIP8_SCREEN_HEIGHT,
+            pixels: vec![vec![0; CHIP8_SCREEN_HEIGHT]; CHIP8_SCREEN_WIDTH],
+            planes: 0b01,
         }
     }
 }
 
 impl Screen {
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn is_hires(&self) -> bool {
+        self.width == CHIP8_HIRES_SCREEN_WIDTH
+    }
+
+    /// Switch between the 64x32 low resolution and the 128x64 high resolution modes, the
+    /// screen is cleared after the switch.
+    pub(crate) fn set_hires(&mut self, hires: bool) {
+        if hires {
+            self.width = CHIP8_HIRES_SCREEN_WIDTH;
+            self.height = CHIP8_HIRES_SCREEN_HEIGHT;
+        } else {
+            self.width = CHIP8_SCREEN_WIDTH;
+            self.height = CHIP8_SCREEN_HEIGHT;
+        }
+        self.pixels = vec![vec![0; self.height]; self.width];
+    }
+
+    /// Select the bit planes (bitmask) affected by the draw, scroll and clear instructions.
+    pub(crate) fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & ((1 << CHIP8_TOTAL_PLANES) - 1);
+    }
+
     pub(crate) fn is_pixel_set(&self, x: usize, y: usize) -> Result<bool, VMError> {
+        Ok(self.pixel_planes(x, y)? != 0)
+    }
+
+    /// Returns the bit planes set for the pixel, which is also its colour index.
+    pub(crate) fn pixel_planes(&self, x: usize, y: usize) -> Result<u8, VMError> {
         if !self.check_bounds(x, y) {
             return Err(VMError::ScreenOutOfBounds(x, y));
         }
         Ok(self.pixels[x][y])
     }
 
+    /// Draw a sprite `width` pixels wide (8 or 16) and `rows` pixels tall, read from memory
+    /// starting at `offset`, on each selected plane. When both planes are selected the sprite
+    /// data for the second plane follows the data for the first one. Returns true if any pixel
+    /// was turned off.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn draw_sprite(
         &mut self,
         x: usize,
         y: usize,
         offset: usize,
         ram: &RAM,
-        tbytes: usize,
+        width: usize,
+        rows: usize,
+        clip: bool,
     ) -> Result<bool, VMError> {
         let mut pixel_collision = false;
+        let bytes_per_row = width / 8;
+        let mut offset = offset;
 
-        for ly in 0..tbytes {
-            let sprite_byte = ram.get(offset + ly)?;
-            for lx in 0..8 {
-                // if pixel byte is zero, nothing to draw
-                if sprite_byte & (0b1000_0000 >> lx) == 0b0000_0000 {
-                    continue;
-                }
+        // The starting position always wraps around, clipping only applies to the sprite body.
+        let x = x % self.width;
+        let y = y % self.height;
 
-                // Collision detection.
-                if self.pixels[(lx + x) % CHIP8_SCREEN_WIDTH][(ly + y) % CHIP8_SCREEN_HEIGHT] {
-                    pixel_collision = true;
-                }
+        for plane in 0..CHIP8_TOTAL_PLANES {
+            let plane_bit = 1 << plane;
+            if self.planes & plane_bit == 0 {
+                continue;
+            }
+            for ly in 0..rows {
+                for row_byte in 0..bytes_per_row {
+                    let sprite_byte = ram.get(offset + ly * bytes_per_row + row_byte)?;
+                    for bit in 0..8 {
+                        // if pixel byte is zero, nothing to draw
+                        if sprite_byte & (0b1000_0000 >> bit) == 0b0000_0000 {
+                            continue;
+                        }
+
+                        let lx = row_byte * 8 + bit;
+                        if clip && !self.check_bounds(lx + x, ly + y) {
+                            continue;
+                        }
+                        let px = (lx + x) % self.width;
+                        let py = (ly + y) % self.height;
 
-                // XOR pixels, if pixel is already "on" then go "off".
-                self.pixels[(lx + x) % CHIP8_SCREEN_WIDTH][(ly + y) % CHIP8_SCREEN_HEIGHT] ^= true;
+                        // Collision detection.
+                        if self.pixels[px][py] & plane_bit != 0 {
+                            pixel_collision = true;
+                        }
+
+                        // XOR pixels, if pixel is already "on" then go "off".
+                        self.pixels[px][py] ^= plane_bit;
+                    }
+                }
             }
+            offset += rows * bytes_per_row;
         }
         Ok(pixel_collision)
     }
 
+    /// Scroll the screen content down `lines` pixels, the top lines are left blank.
+    pub(crate) fn scroll_down(&mut self, lines: usize) {
+        self.scroll(0, lines as isize);
+    }
+
+    /// Scroll the screen content right `columns` pixels, the left columns are left blank.
+    pub(crate) fn scroll_right(&mut self, columns: usize) {
+        self.scroll(columns as isize, 0);
+    }
+
+    /// Scroll the screen content left `columns` pixels, the right columns are left blank.
+    pub(crate) fn scroll_left(&mut self, columns: usize) {
+        self.scroll(-(columns as isize), 0);
+    }
+
+    /// Move the content of the selected planes by (dx, dy) pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let mut pixels = vec![vec![0; self.height]; self.width];
+        for (x, column) in pixels.iter_mut().enumerate() {
+            for (y, pixel) in column.iter_mut().enumerate() {
+                // Negative source coordinates wrap to huge values and fail the bounds check.
+                let src_x = (x as isize - dx) as usize;
+                let src_y = (y as isize - dy) as usize;
+                let moved = if self.check_bounds(src_x, src_y) {
+                    self.pixels[src_x][src_y]
+                } else {
+                    0
+                };
+                *pixel = (moved & self.planes) | (self.pixels[x][y] & !self.planes);
+            }
+        }
+        self.pixels = pixels;
+    }
+
     fn check_bounds(&self, x: usize, y: usize) -> bool {
-        x < CHIP8_SCREEN_WIDTH && y < CHIP8_SCREEN_HEIGHT
+        x < self.width && y < self.height
     }
 
+    /// Clear the selected planes.
     pub fn clear(&mut self) -> Result<(), VMError> {
-        self.pixels = [[false; CHIP8_SCREEN_HEIGHT]; CHIP8_SCREEN_WIDTH];
+        for column in self.pixels.iter_mut() {
+            for pixel in column.iter_mut() {
+                *pixel &= !self.planes;
+            }
+        }
         Ok(())
     }
 
-    pub(crate) fn dump(&self) {
-        for x in 0..CHIP8_SCREEN_HEIGHT {
-            for y in 0..CHIP8_SCREEN_WIDTH {
-                if self.pixels[y][x] {
-                    print!("{},", "X");
-                } else {
-                    print!("{},", ".");
-                }
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.width == CHIP8_HIRES_SCREEN_WIDTH);
+        state.write_u8(self.planes);
+        for column in self.pixels.iter() {
+            state.write_bytes(column);
+        }
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        self.set_hires(state.read_bool()?);
+        let planes = state.read_u8()?;
+        if planes >= 1 << CHIP8_TOTAL_PLANES {
+            return Err(VMError::InvalidState);
+        }
+        self.select_planes(planes);
+        for column in self.pixels.iter_mut() {
+            let bytes = state.read_bytes(self.height)?;
+            if bytes.iter().any(|pixel| *pixel >= 1 << CHIP8_TOTAL_PLANES) {
+                return Err(VMError::InvalidState);
             }
-            println!();
+            column.copy_from_slice(bytes);
         }
-        std::io::stdout().flush().unwrap();
+        Ok(())
     }
 }
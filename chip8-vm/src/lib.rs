@@ -1,9 +1,33 @@
+pub mod asm;
 mod config;
 mod cpu;
+mod debugger;
+mod diff;
+pub mod disasm;
+mod driver;
 mod errors;
+mod headless;
+mod host;
 mod io;
 mod memory;
+mod movie;
+mod quirks;
+mod rewind;
+mod sound;
+mod state;
+mod trace;
 mod vm;
 
-pub use vm::Signal;
+pub use cpu::RngAlgorithm;
+pub use debugger::{Condition, Debugger, StopReason, WatchHit, WatchKind, Watchpoint};
+pub use diff::Divergence;
+pub use driver::{Driver, MovieMode};
+pub use headless::{screen_pbm, state_dump, Headless, KeyEvent, KeyScript, RunLength, RunSummary};
+pub use host::{Audio, Capture, Host, HostEvent, Input, Video};
+pub use io::ChipKey;
+pub use movie::Movie;
+pub use quirks::Quirks;
+pub use sound::{PatternWave, WavWriter};
+pub use trace::{read_trace, Trace, TraceFormat, TraceRecord};
 pub use vm::VM;
+pub use vm::{FrameSummary, Signal};
@@ -1,3 +1,3 @@
 mod ram;
 
-pub(crate) use ram::RAM;
+pub(crate) use ram::{MemoryAccess, RAM};
@@ -1,10 +1,11 @@
-use std::io::Write;
+use std::cell::RefCell;
 
 use crate::errors::VMError;
+use crate::state::{StateReader, StateWriter};
 
 use crate::config::*;
 
-const CHARSET: &'static [u8] = &[
+const CHARSET: &[u8] = &[
     0xf0, 0x90, 0x90, 0x90, 0xf0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xf0, 0x10, 0xf0, 0x80, 0xf0, 0xf0,
     0x10, 0xf0, 0x10, 0xf0, 0x90, 0x90, 0xf0, 0x10, 0x10, 0xf0, 0x80, 0xf0, 0x10, 0xf0, 0xf0, 0x80,
     0xf0, 0x90, 0xf0, 0xf0, 0x10, 0x20, 0x40, 0x40, 0xf0, 0x90, 0xf0, 0x90, 0xf0, 0xf0, 0x90, 0xf0,
@@ -12,30 +13,41 @@ const CHARSET: &'static [u8] = &[
     0xf0, 0xe0, 0x90, 0x90, 0x90, 0xe0, 0xf0, 0x80, 0xf0, 0x80, 0xf0, 0xf0, 0x80, 0xf0, 0x80, 0x80,
 ];
 
-const COLUMNS_PER_LINE: u32 = 8;
+const BIG_CHARSET: &[u8] = &[
+    0x3c, 0x7e, 0xe7, 0xc3, 0xc3, 0xc3, 0xc3, 0xe7, 0x7e, 0x3c, 0x18, 0x38, 0x58, 0x18, 0x18, 0x18,
+    0x18, 0x18, 0x18, 0x3c, 0x3e, 0x7f, 0xc3, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xff, 0xff, 0x3c, 0x7e,
+    0xc3, 0x03, 0x0e, 0x0e, 0x03, 0xc3, 0x7e, 0x3c, 0x06, 0x0e, 0x1e, 0x36, 0x66, 0xc6, 0xff, 0xff,
+    0x06, 0x06, 0xff, 0xff, 0xc0, 0xc0, 0xfc, 0xfe, 0x03, 0xc3, 0x7e, 0x3c, 0x3e, 0x7c, 0xe0, 0xc0,
+    0xfc, 0xfe, 0xc3, 0xc3, 0x7e, 0x3c, 0xff, 0xff, 0x03, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3c, 0x7e, 0xc3, 0xc3, 0x7e, 0x7e, 0xc3, 0xc3, 0x7e, 0x3c, 0x3c, 0x7e, 0xc3, 0xc3, 0x7f, 0x3f,
+    0x03, 0x03, 0x3e, 0x7c,
+];
 
-macro_rules! memaddr_pattern {
-    () => {
-        "{:#06X}: "
-    };
-}
-macro_rules! byte_pattern {
-    () => {
-        "{:#04X} "
-    };
+/// Memory access made by an instruction, recorded while the access log is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MemoryAccess {
+    Read { addr: usize, value: u8 },
+    Write { addr: usize, old: u8, new: u8 },
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub struct RAM {
-    memory: [u8; CHIP8_MEM_SIZE],
+    memory: Vec<u8>,
+    /// Reads and writes made through `get` and `set`, used by the debugger watchpoints.
+    access_log: Option<RefCell<Vec<MemoryAccess>>>,
 }
 
 impl Default for RAM {
     fn default() -> Self {
         let mut ram = Self {
-            memory: [0x00; CHIP8_MEM_SIZE],
+            memory: vec![0x00; CHIP8_MEM_SIZE],
+            access_log: None,
         };
         // Set the default chatset at the beginning of reserved memory.
         ram.memory[..CHARSET.len()].copy_from_slice(CHARSET);
+        // Set the SUPER-CHIP big charset right after it.
+        ram.memory[CHIP8_BIG_CHARSET_ADDRESS..CHIP8_BIG_CHARSET_ADDRESS + BIG_CHARSET.len()]
+            .copy_from_slice(BIG_CHARSET);
 
         ram
     }
@@ -49,6 +61,13 @@ impl RAM {
         if index <= CHIP8_MEM_RESEVED_LIMIT {
             return Err(VMError::ReservedMemoryWriteAttempt);
         }
+        if let Some(log) = &self.access_log {
+            log.borrow_mut().push(MemoryAccess::Write {
+                addr: index,
+                old: self.memory[index],
+                new: value,
+            });
+        }
         self.memory[index] = value;
         Ok(())
     }
@@ -56,13 +75,38 @@ impl RAM {
     pub(crate) fn get(&self, index: usize) -> Result<u8, VMError> {
         let value = self.memory.get(index);
         match value {
-            Some(value) => Ok(*value),
+            Some(value) => {
+                if let Some(log) = &self.access_log {
+                    log.borrow_mut().push(MemoryAccess::Read {
+                        addr: index,
+                        value: *value,
+                    });
+                }
+                Ok(*value)
+            }
             None => Err(VMError::MemoryOutOfBounds(index)),
         }
     }
 
+    /// Enable or disable the log of memory accesses made through `get` and `set`.
+    pub(crate) fn set_access_log(&mut self, enabled: bool) {
+        if !enabled {
+            self.access_log = None;
+        } else if self.access_log.is_none() {
+            self.access_log = Some(RefCell::new(Vec::new()));
+        }
+    }
+
+    /// Returns and clears the logged memory accesses.
+    pub(crate) fn take_accesses(&mut self) -> Vec<MemoryAccess> {
+        match &mut self.access_log {
+            Some(log) => log.get_mut().drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub(crate) fn load_program(&mut self, buffer: &[u8]) -> Result<(), VMError> {
-        if !(buffer.len() + CHIP8_PROGRAM_LOAD_ADDRESS < CHIP8_MEM_SIZE) {
+        if buffer.len() + CHIP8_PROGRAM_LOAD_ADDRESS >= CHIP8_MEM_SIZE {
             return Err(VMError::ProgramSizeOverflow);
         }
         self.memory[CHIP8_PROGRAM_LOAD_ADDRESS..CHIP8_PROGRAM_LOAD_ADDRESS + buffer.len()]
@@ -71,27 +115,26 @@ impl RAM {
     }
 
     pub(crate) fn get_opcode(&self, index: usize) -> Result<u16, VMError> {
-        if !(index + 1 < CHIP8_MEM_SIZE) {
+        if index + 1 >= CHIP8_MEM_SIZE {
             return Err(VMError::MemoryOutOfBounds(index));
         }
         let instruction = ((self.memory[index] as u16) << 8) | self.memory[index + 1] as u16;
         Ok(instruction)
     }
 
-    pub(crate) fn dump(&self) {
-        let mut colums_count = 1;
-        print!(memaddr_pattern!(), 0);
-        for (memaddr, byte) in self.memory.iter().enumerate() {
-            if colums_count > COLUMNS_PER_LINE {
-                colums_count = 1;
-                println!();
-                print!(memaddr_pattern!(), memaddr);
-            }
-            print!(byte_pattern!(), byte);
-            colums_count += 1;
-        }
-        println!();
-        std::io::stdout().flush().unwrap();
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.memory);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        self.memory
+            .copy_from_slice(state.read_bytes(CHIP8_MEM_SIZE)?);
+        Ok(())
+    }
+
+    /// The whole memory, without logging the access.
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.memory
     }
 
     #[cfg(test)]
@@ -0,0 +1,358 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    config::CHIP8_DEFAULT_CYCLES_PER_FRAME, errors::VMError, ChipKey, KeyEvent, KeyScript, Quirks,
+    RngAlgorithm, VM,
+};
+
+/// Version of the movie file format.
+const MOVIE_VERSION: u32 = 1;
+
+/// Names of the quirk flags in the movie files.
+const QUIRK_NAMES: [&str; 9] = [
+    "shift-uses-vy",
+    "load-store-increments-i",
+    "load-store-increments-i-by-x",
+    "jump-with-vx",
+    "large-sprites",
+    "lores-8x16-sprites",
+    "clip-sprites",
+    "vf-reset",
+    "display-wait",
+];
+
+/// `Movie` is a recording of a play session: the ROM it was recorded with, the VM configuration
+/// (quirks, speed and random number generator seed) and every key press and release with the
+/// frame where it happened. Replaying it with the same ROM gives exactly the same run.
+///
+/// The `.c8m` text format has one `NAME VALUE` header line per setting followed by the key
+/// events in the key script format:
+///
+/// ```text
+/// version 1
+/// rom 8a6e3c1f2b4d5a97
+/// seed 42
+/// rng xorshift
+/// quirks shift-uses-vy load-store-increments-i clip-sprites vf-reset display-wait
+/// cycles-per-frame 10
+/// frames 300
+/// 60 5 down
+/// 90 5 up
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    rom_hash: u64,
+    seed: u64,
+    rng: RngAlgorithm,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    frames: u64,
+    events: Vec<KeyEvent>,
+}
+
+impl Movie {
+    /// Start the recording of a session of `rom` with the given VM configuration.
+    pub fn new(
+        rom: &[u8],
+        seed: u64,
+        rng: RngAlgorithm,
+        quirks: Quirks,
+        cycles_per_frame: usize,
+    ) -> Self {
+        Movie {
+            rom_hash: rom_hash(rom),
+            seed,
+            rng,
+            quirks,
+            cycles_per_frame: cycles_per_frame.max(1),
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rng(&self) -> RngAlgorithm {
+        self.rng
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        self.cycles_per_frame
+    }
+
+    /// Length of the recording in frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn set_frames(&mut self, frames: u64) {
+        self.frames = frames;
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    /// Record a key press or release at the start of `frame`. Events must be recorded in frame
+    /// order.
+    pub fn record_key(&mut self, frame: u64, key: ChipKey, pressed: bool) {
+        self.events.push(KeyEvent {
+            frame,
+            key,
+            pressed,
+        });
+        self.frames = self.frames.max(frame);
+    }
+
+    /// Key events of the movie, to replay it with `Headless`.
+    pub fn key_script(&self) -> KeyScript {
+        KeyScript::new(self.events.clone())
+    }
+
+    /// Create a VM configured like the recorded one, with `rom` loaded. Fails if `rom` is not the
+    /// recorded ROM.
+    pub fn vm(&self, rom: &[u8]) -> Result<VM, VMError> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(VMError::InvalidMovie(
+                0,
+                "the movie was recorded with another ROM".to_string(),
+            ));
+        }
+        let mut vm = VM::new(self.quirks);
+        vm.set_rng(self.rng, self.seed);
+        vm.load_program(rom)?;
+        Ok(vm)
+    }
+
+    /// Apply the key events of `frame` to the VM, to be called before running the frame.
+    pub fn apply_events(&self, vm: &mut VM, frame: u64) {
+        let start = self.events.partition_point(|event| event.frame < frame);
+        for event in self.events[start..]
+            .iter()
+            .take_while(|event| event.frame == frame)
+        {
+            vm.set_key(event.key, event.pressed);
+        }
+    }
+}
+
+/// FNV-1a hash identifying the ROM of a movie.
+fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+fn rng_name(rng: RngAlgorithm) -> &'static str {
+    match rng {
+        RngAlgorithm::Xorshift => "xorshift",
+        RngAlgorithm::CosmacVip => "cosmac-vip",
+    }
+}
+
+fn quirk_flags(quirks: &mut Quirks) -> [&mut bool; 9] {
+    [
+        &mut quirks.shift_uses_vy,
+        &mut quirks.load_store_increments_i,
+        &mut quirks.load_store_increments_i_by_x,
+        &mut quirks.jump_with_vx,
+        &mut quirks.large_sprites,
+        &mut quirks.lores_8x16_sprites,
+        &mut quirks.clip_sprites,
+        &mut quirks.vf_reset,
+        &mut quirks.display_wait,
+    ]
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut quirks = self.quirks;
+        let quirks = quirk_flags(&mut quirks)
+            .into_iter()
+            .zip(QUIRK_NAMES)
+            .filter(|(flag, _)| **flag)
+            .map(|(_, name)| name)
+            .collect::<Vec<&str>>();
+        writeln!(f, "version {}", MOVIE_VERSION)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "rng {}", rng_name(self.rng))?;
+        writeln!(f, "quirks {}", quirks.join(" "))?;
+        writeln!(f, "cycles-per-frame {}", self.cycles_per_frame)?;
+        writeln!(f, "frames {}", self.frames)?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Movie {
+    type Err = VMError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut movie = Movie {
+            rom_hash: 0,
+            seed: 0,
+            rng: RngAlgorithm::default(),
+            quirks: Quirks::default(),
+            cycles_per_frame: CHIP8_DEFAULT_CYCLES_PER_FRAME,
+            frames: 0,
+            events: Vec::new(),
+        };
+        let mut version = None;
+        let mut rom_hash = None;
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| VMError::InvalidMovie(index + 1, reason.to_string());
+            if line.starts_with(|c: char| c.is_ascii_digit()) {
+                let event = KeyEvent::parse(line).map_err(invalid)?;
+                if movie
+                    .events
+                    .last()
+                    .is_some_and(|last| last.frame > event.frame)
+                {
+                    return Err(invalid("key events out of frame order"));
+                }
+                movie.events.push(event);
+                continue;
+            }
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            match name {
+                "version" => {
+                    version = Some(
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| invalid("invalid version"))?,
+                    )
+                }
+                "rom" => {
+                    rom_hash = Some(
+                        u64::from_str_radix(value, 16).map_err(|_| invalid("invalid ROM hash"))?,
+                    )
+                }
+                "seed" => movie.seed = value.parse().map_err(|_| invalid("invalid seed"))?,
+                "rng" => {
+                    movie.rng = match value {
+                        "xorshift" => RngAlgorithm::Xorshift,
+                        "cosmac-vip" => RngAlgorithm::CosmacVip,
+                        _ => return Err(invalid("invalid random number generator")),
+                    }
+                }
+                "quirks" => {
+                    let names = value.split_whitespace().collect::<Vec<&str>>();
+                    if let Some(name) = names.iter().find(|name| !QUIRK_NAMES.contains(name)) {
+                        return Err(invalid(&format!("unknown quirk '{}'", name)));
+                    }
+                    for (flag, name) in quirk_flags(&mut movie.quirks).into_iter().zip(QUIRK_NAMES)
+                    {
+                        *flag = names.contains(&name);
+                    }
+                }
+                "cycles-per-frame" => {
+                    movie.cycles_per_frame = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|cycles| *cycles > 0)
+                        .ok_or_else(|| invalid("invalid cycles per frame"))?
+                }
+                "frames" => {
+                    movie.frames = value.parse().map_err(|_| invalid("invalid frame count"))?
+                }
+                _ => return Err(invalid(&format!("unknown setting '{}'", name))),
+            }
+        }
+        match version {
+            Some(MOVIE_VERSION) => {}
+            Some(version) => {
+                return Err(VMError::InvalidMovie(
+                    0,
+                    format!("unsupported movie version {}", version),
+                ))
+            }
+            None => return Err(VMError::InvalidMovie(0, "missing version".to_string())),
+        }
+        movie.rom_hash =
+            rom_hash.ok_or_else(|| VMError::InvalidMovie(0, "missing ROM hash".to_string()))?;
+        Ok(movie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Movie;
+    use crate::{state_dump, ChipKey, Headless, Quirks, RngAlgorithm, RunLength};
+
+    // 0x200: LD V0, K, 0x202: RND V1, 0xFF, 0x204: LD F, V0, 0x206: DRW V1, V1, 5, 0x208: EXIT
+    const PROGRAM: [u8; 10] = [0xF0, 0x0A, 0xC1, 0xFF, 0xF0, 0x29, 0xD1, 0x15, 0x00, 0xFD];
+
+    fn record() -> Movie {
+        let mut movie = Movie::new(&PROGRAM, 7, RngAlgorithm::Xorshift, Quirks::CHIP48, 4);
+        movie.record_key(2, ChipKey::KeyB, true);
+        movie.record_key(3, ChipKey::KeyB, false);
+        movie.set_frames(10);
+        movie
+    }
+
+    #[test]
+    fn save_and_load_movie() {
+        let movie = record();
+        let text = movie.to_string();
+        assert!(text.starts_with("version 1\nrom "));
+        assert!(text.contains("\nquirks load-store-increments-i load-store-increments-i-by-x jump-with-vx clip-sprites\n"));
+        assert!(text.ends_with("frames 10\n2 B down\n3 B up\n"));
+        assert_eq!(text.parse::<Movie>().unwrap(), movie);
+
+        assert_eq!(
+            "version 2\nrom 0".parse::<Movie>().unwrap_err().to_string(),
+            "invalid movie: unsupported movie version 2"
+        );
+        assert_eq!(
+            "version 1\nrom 0\n5 1 down\n4 1 up"
+                .parse::<Movie>()
+                .unwrap_err()
+                .to_string(),
+            "invalid movie line 4: key events out of frame order"
+        );
+        assert!("version 1".parse::<Movie>().is_err());
+        assert!("version 1\nrom 0\nspeed 3".parse::<Movie>().is_err());
+    }
+
+    #[test]
+    fn replay_movie() {
+        let movie = record();
+        let run = |movie: &Movie| {
+            let mut chip8 = movie.vm(&PROGRAM).unwrap();
+            for frame in 0..movie.frames() {
+                movie.apply_events(&mut chip8, frame);
+                chip8.run_frame(movie.cycles_per_frame()).unwrap();
+            }
+            state_dump(&chip8).unwrap()
+        };
+        let dump = run(&movie);
+        assert_eq!(run(&movie.to_string().parse().unwrap()), dump);
+
+        // Headless runs replay the same session.
+        let mut chip8 = movie.vm(&PROGRAM).unwrap();
+        let mut headless = Headless::new();
+        headless.set_cycles_per_frame(movie.cycles_per_frame());
+        headless.set_key_script(movie.key_script());
+        headless
+            .run(&mut chip8, RunLength::Frames(movie.frames()))
+            .unwrap();
+        assert_eq!(state_dump(&chip8).unwrap(), dump);
+        assert!(dump.starts_with("PC=0x208"));
+
+        assert!(movie.vm(&PROGRAM[..8]).is_err());
+    }
+}
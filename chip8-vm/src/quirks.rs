@@ -0,0 +1,117 @@
+use crate::{
+    errors::VMError,
+    state::{StateReader, StateWriter},
+};
+
+/// `Quirks` holds the behaviour differences between the CHIP-8 interpreters that ROMs were
+/// written for. Each flag selects how one ambiguous instruction (or group of instructions) is
+/// executed by the VM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift Vy into Vx instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// `Fx55`/`Fx65` leave I pointing past the last register stored or loaded (I = I + x + 1).
+    pub load_store_increments_i: bool,
+    /// With `load_store_increments_i`, `Fx55`/`Fx65` leave I at I + x instead, pointing at the
+    /// last register stored or loaded.
+    pub load_store_increments_i_by_x: bool,
+    /// `Bnnn` jumps to nnn + Vx (x being the highest nibble of nnn) instead of nnn + V0.
+    pub jump_with_vx: bool,
+    /// `Dxy0` draws 16x16 sprites instead of sprites with no rows, as SUPER-CHIP and XO-CHIP.
+    pub large_sprites: bool,
+    /// With `large_sprites`, `Dxy0` draws 8x16 sprites in low resolution, as SUPER-CHIP 1.1.
+    pub lores_8x16_sprites: bool,
+    /// `Dxyn` clips sprites at the screen edges instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset VF to zero.
+    pub vf_reset: bool,
+    /// `Dxyn` waits for the vertical blank interrupt before drawing, limiting draws to one per frame.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Behaviour of the original interpreter of the RCA COSMAC VIP.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        load_store_increments_i_by_x: false,
+        jump_with_vx: false,
+        large_sprites: false,
+        lores_8x16_sprites: false,
+        clip_sprites: true,
+        vf_reset: true,
+        display_wait: true,
+    };
+
+    /// Behaviour of the CHIP-48 interpreter for the HP-48 calculators.
+    pub const CHIP48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: true,
+        load_store_increments_i_by_x: true,
+        jump_with_vx: true,
+        large_sprites: false,
+        lores_8x16_sprites: false,
+        clip_sprites: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    /// Behaviour of the SUPER-CHIP 1.1 interpreter for the HP-48 calculators.
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        load_store_increments_i_by_x: false,
+        jump_with_vx: true,
+        large_sprites: true,
+        lores_8x16_sprites: true,
+        clip_sprites: true,
+        vf_reset: false,
+        display_wait: false,
+    };
+
+    /// Behaviour of the XO-CHIP extension as implemented by Octo.
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        load_store_increments_i_by_x: false,
+        jump_with_vx: false,
+        large_sprites: true,
+        lores_8x16_sprites: false,
+        clip_sprites: false,
+        vf_reset: false,
+        display_wait: false,
+    };
+}
+
+impl Quirks {
+    pub(crate) fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.shift_uses_vy);
+        state.write_bool(self.load_store_increments_i);
+        state.write_bool(self.load_store_increments_i_by_x);
+        state.write_bool(self.jump_with_vx);
+        state.write_bool(self.large_sprites);
+        state.write_bool(self.lores_8x16_sprites);
+        state.write_bool(self.clip_sprites);
+        state.write_bool(self.vf_reset);
+        state.write_bool(self.display_wait);
+    }
+
+    pub(crate) fn load_state(&mut self, state: &mut StateReader) -> Result<(), VMError> {
+        self.shift_uses_vy = state.read_bool()?;
+        self.load_store_increments_i = state.read_bool()?;
+        self.load_store_increments_i_by_x = state.read_bool()?;
+        self.jump_with_vx = state.read_bool()?;
+        self.large_sprites = state.read_bool()?;
+        self.lores_8x16_sprites = state.read_bool()?;
+        self.clip_sprites = state.read_bool()?;
+        self.vf_reset = state.read_bool()?;
+        self.display_wait = state.read_bool()?;
+        Ok(())
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::COSMAC_VIP
+    }
+}
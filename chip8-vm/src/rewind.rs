@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+/// Ring buffer of the VM save states of the last frames, kept compressed since most of the
+/// memory and screen of a CHIP-8 program are runs of the same byte.
+pub(crate) struct RewindBuffer {
+    states: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            states: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Push the state of the current frame, dropping the oldest one when the buffer is full.
+    pub(crate) fn push(&mut self, state: &[u8]) {
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(compress(state));
+    }
+
+    /// Pop the state of the most recent frame.
+    pub(crate) fn pop(&mut self) -> Option<Vec<u8>> {
+        self.states.pop_back().map(|state| decompress(&state))
+    }
+}
+
+/// PackBits run-length encoding: a header byte `n` is followed either by `n + 1` literal bytes
+/// (0 to 127) or by a single byte repeated `257 - n` times (129 to 255).
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(128)
+            .take_while(|&&byte| byte == data[i])
+            .count();
+        if run > 1 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        // Literal bytes up to the start of the next run.
+        let start = i;
+        while i < data.len() && i - start < 128 && (i + 1 >= data.len() || data[i] != data[i + 1]) {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out
+}
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let header = data[i] as usize;
+        if header < 128 {
+            out.extend_from_slice(&data[i + 1..i + 2 + header]);
+            i += header + 2;
+        } else {
+            out.resize(out.len() + 257 - header, data[i + 1]);
+            i += 2;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RewindBuffer;
+
+    #[test]
+    fn rewind_states() {
+        let mut buffer = RewindBuffer::new(2);
+        let states = [
+            vec![0; 300],
+            (0..=255).chain([7; 5]).chain(0..3).collect::<Vec<u8>>(),
+            vec![1, 2, 2, 3, 3, 3, 4],
+        ];
+        for state in &states {
+            buffer.push(state);
+        }
+        assert_eq!(buffer.pop().as_ref(), Some(&states[2]));
+        assert_eq!(buffer.pop().as_ref(), Some(&states[1]));
+        assert_eq!(buffer.pop(), None);
+    }
+}
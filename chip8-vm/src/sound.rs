@@ -0,0 +1,203 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::{config::CHIP8_AUDIO_PATTERN_SIZE, Audio, VM};
+
+/// Amplitude of the sound, out of 1.
+const VOLUME: f32 = 0.25;
+/// Sample rate of the WAV files.
+const WAV_SAMPLE_RATE: u32 = 44100;
+/// Size of the RIFF and format headers of a WAV file, before the samples.
+const WAV_HEADER_SIZE: u32 = 44;
+
+/// Synthesizer of the VM sound: the 1-bit audio pattern played in a loop at the pattern
+/// playback rate, as square wave samples.
+pub struct PatternWave {
+    pattern: [u8; CHIP8_AUDIO_PATTERN_SIZE],
+    sample_rate: f32,
+    /// Pattern bits played per sample.
+    bit_inc: f32,
+    /// Position in the pattern, in bits.
+    phase: f32,
+}
+
+impl PatternWave {
+    pub fn new(vm: &VM, sample_rate: u32) -> Self {
+        let mut wave = PatternWave {
+            pattern: vm.audio_pattern(),
+            sample_rate: sample_rate as f32,
+            bit_inc: 0.0,
+            phase: 0.0,
+        };
+        wave.update(vm);
+        wave
+    }
+
+    /// Play the current pattern and pitch of the VM.
+    pub fn update(&mut self, vm: &VM) {
+        self.pattern = vm.audio_pattern();
+        self.bit_inc = vm.audio_playback_rate() / self.sample_rate;
+    }
+
+    /// Fill `out` with the next samples.
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let total_bits = (self.pattern.len() * 8) as f32;
+        for x in out.iter_mut() {
+            let bit = self.phase as usize;
+            let set = self.pattern[bit / 8] & (0b1000_0000 >> (bit % 8)) != 0;
+            *x = if set { VOLUME } else { -VOLUME };
+            self.phase = (self.phase + self.bit_inc) % total_bits;
+        }
+    }
+}
+
+/// `WavWriter` renders the VM sound to a 16-bit mono WAV file, in emulated time: every frame
+/// adds exactly a 60th of a second of samples, the sound or silence, so the file can be compared
+/// between runs, e.g. in headless mode.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    wave: Option<PatternWave>,
+    /// Frames rendered, to give each frame its share of the samples.
+    frames: u64,
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W) -> Result<Self, String> {
+        // The sizes of the header are written at the end.
+        out.write_all(&wav_header(0)).map_err(|e| e.to_string())?;
+        Ok(WavWriter {
+            out,
+            wave: None,
+            frames: 0,
+            samples: 0,
+        })
+    }
+
+    /// Samples written so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// The underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.out
+    }
+}
+
+impl<W: Write + Seek> Audio for WavWriter<W> {
+    fn update(&mut self, vm: &VM, playing: bool) -> Result<(), String> {
+        let rate = WAV_SAMPLE_RATE as u64;
+        let count = ((self.frames + 1) * rate / 60 - self.frames * rate / 60) as usize;
+        self.frames += 1;
+        let mut samples = vec![0.0; count];
+        if playing {
+            let wave = self
+                .wave
+                .get_or_insert_with(|| PatternWave::new(vm, WAV_SAMPLE_RATE));
+            wave.update(vm);
+            wave.fill(&mut samples);
+        } else {
+            // The next sound starts at the beginning of the pattern.
+            self.wave = None;
+        }
+        let bytes = samples
+            .iter()
+            .flat_map(|sample| ((sample * i16::MAX as f32) as i16).to_le_bytes())
+            .collect::<Vec<u8>>();
+        self.out.write_all(&bytes).map_err(|e| e.to_string())?;
+        self.samples += count as u32;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.out
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| self.out.write_all(&wav_header(self.samples)))
+            .and_then(|_| self.out.seek(SeekFrom::End(0)))
+            .and_then(|_| self.out.flush())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// RIFF header of a 16-bit mono PCM WAV file with the given number of samples.
+fn wav_header(samples: u32) -> Vec<u8> {
+    let data_size = samples * 2;
+    let mut header = Vec::with_capacity(WAV_HEADER_SIZE as usize);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, 1 channel
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&WAV_SAMPLE_RATE.to_le_bytes());
+    // Byte rate, block alignment and bits per sample
+    header.extend_from_slice(&(WAV_SAMPLE_RATE * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_size.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Cursor},
+    };
+
+    use super::WavWriter;
+    use crate::{Audio, Headless, Quirks, RunLength, VM};
+
+    // 0x200: LD V0, 0x06, 0x202: LD ST, V0, 0x204: JP 0x204
+    const PROGRAM: [u8; 6] = [0x60, 0x06, 0xF0, 0x18, 0x12, 0x04];
+
+    #[test]
+    fn render_wav() {
+        let mut chip8 = VM::new(Quirks::CHIP48);
+        chip8.load_program(&PROGRAM).unwrap();
+        let mut wav = WavWriter::new(Cursor::new(Vec::new())).unwrap();
+        let mut sound_frames = Vec::new();
+        for _ in 0..10 {
+            chip8.run_frame(10).unwrap();
+            sound_frames.push(chip8.registers_st() > 0);
+            wav.update(&chip8, chip8.registers_st() > 0).unwrap();
+        }
+        wav.finish().unwrap();
+        assert_eq!(wav.samples(), 7350);
+
+        let bytes = wav.get_ref().get_ref();
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &(36 + 14700u32).to_le_bytes());
+        assert_eq!(&bytes[40..44], &14700u32.to_le_bytes());
+        assert_eq!(bytes.len(), 44 + 14700);
+        let samples = bytes[44..]
+            .chunks(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect::<Vec<i16>>();
+        // The sound timer is set in the first frame and beeps for 6 frames, the default
+        // beep is a 440 Hz square wave: about 50 samples high, 50 low.
+        assert_eq!(
+            sound_frames,
+            [true, true, true, true, true, true, false, false, false, false]
+        );
+        assert!(samples[..51].iter().all(|sample| *sample == 8191));
+        assert!(samples[51..101].iter().all(|sample| *sample == -8191));
+        assert!(samples[6 * 735..].iter().all(|sample| *sample == 0));
+
+        // Headless runs render the same file.
+        let mut chip8 = VM::new(Quirks::CHIP48);
+        chip8.load_program(&PROGRAM).unwrap();
+        let mut headless = Headless::new();
+        headless.set_cycles_per_frame(10);
+        let file_name = std::env::temp_dir().join(format!("chip8-wav-{}.wav", std::process::id()));
+        let wav = WavWriter::new(BufWriter::new(File::create(&file_name).unwrap())).unwrap();
+        headless
+            .run_with_sinks(&mut chip8, RunLength::Frames(10), None, Some(Box::new(wav)))
+            .unwrap();
+        let rendered = std::fs::read(&file_name).unwrap();
+        std::fs::remove_file(&file_name).unwrap();
+        assert_eq!(&rendered, bytes);
+    }
+}
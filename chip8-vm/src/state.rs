@@ -0,0 +1,102 @@
+use crate::{
+    config::{CHIP8_STATE_MAGIC, CHIP8_STATE_VERSION},
+    errors::VMError,
+};
+
+/// `StateWriter` builds a save state: the format magic and version followed by the state of
+/// each VM component, written in a fixed order with big endian integers.
+pub(crate) struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new() -> Self {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(CHIP8_STATE_MAGIC);
+        buf.push(CHIP8_STATE_VERSION);
+        StateWriter { buf }
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub(crate) fn write_u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn write_bool(&mut self, value: bool) {
+        self.buf.push(value as u8);
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// `StateReader` reads back a save state written by `StateWriter`, in the same order.
+pub(crate) struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Result<Self, VMError> {
+        let mut reader = StateReader { buf, pos: 0 };
+        if reader.read_bytes(CHIP8_STATE_MAGIC.len())? != CHIP8_STATE_MAGIC {
+            return Err(VMError::InvalidState);
+        }
+        let version = reader.read_u8()?;
+        if version != CHIP8_STATE_VERSION {
+            return Err(VMError::UnsupportedStateVersion(version));
+        }
+        Ok(reader)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, VMError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, VMError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, VMError> {
+        let bytes = self.read_bytes(8)?;
+        Ok(u64::from_be_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, VMError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(VMError::InvalidState),
+        }
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], VMError> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(VMError::InvalidState)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Check that the whole save state was consumed.
+    pub(crate) fn finish(self) -> Result<(), VMError> {
+        if self.pos != self.buf.len() {
+            return Err(VMError::InvalidState);
+        }
+        Ok(())
+    }
+}
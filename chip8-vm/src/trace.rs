@@ -0,0 +1,386 @@
+use std::{
+    fmt,
+    io::Write,
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+};
+
+use crate::{
+    config::{CHIP8_TRACE_MAGIC, CHIP8_TRACE_VERSION},
+    errors::VMError,
+    VM,
+};
+
+/// Registers recorded by the traces: V0 to VF, I, SP, DT and ST.
+pub(crate) const TRACE_REGISTERS: usize = 20;
+
+const REGISTER_NAMES: [&str; TRACE_REGISTERS] = [
+    "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF",
+    "I", "SP", "DT", "ST",
+];
+
+/// Index of the I register in the trace registers, the only 16 bits register.
+const I_REGISTER: u8 = 16;
+
+/// Values of the trace registers before or after an instruction.
+pub(crate) type TraceRegisters = [u16; TRACE_REGISTERS];
+
+/// File format of the traces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line per instruction: `CYCLE PC OPCODE MNEMONIC ; REGISTER=VALUE...`.
+    #[default]
+    Text,
+    /// Compact big endian records after the `C8TR` magic and the format version.
+    Binary,
+}
+
+/// An executed instruction: the cycle (instructions executed since the trace started), the
+/// address and opcode, and the registers it changed with their new values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// Changed registers, as index in V0 to VF, I, SP, DT, ST order and new value.
+    pub deltas: Vec<(u8, u16)>,
+}
+
+impl TraceRecord {
+    /// Record of the instruction executed at `pc`, given the registers before and after it.
+    pub(crate) fn new(
+        cycle: u64,
+        pc: u16,
+        opcode: u16,
+        before: &TraceRegisters,
+        after: &TraceRegisters,
+    ) -> Self {
+        TraceRecord {
+            cycle,
+            pc,
+            opcode,
+            deltas: (0..TRACE_REGISTERS)
+                .filter(|index| before[*index] != after[*index])
+                .map(|index| (index as u8, after[index]))
+                .collect(),
+        }
+    }
+
+    /// Name of a register of the deltas, e.g. `VA` or `DT`.
+    pub fn register_name(index: u8) -> &'static str {
+        REGISTER_NAMES.get(index as usize).copied().unwrap_or("?")
+    }
+
+    /// Mnemonic of the instruction, e.g. `LD V3, 0x10`.
+    pub fn mnemonic(&self) -> String {
+        VM::opcode_mnemonic(self.opcode).unwrap_or_else(|| "???".to_string())
+    }
+
+    fn write_binary(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.cycle.to_be_bytes());
+        buf.extend_from_slice(&self.pc.to_be_bytes());
+        buf.extend_from_slice(&self.opcode.to_be_bytes());
+        buf.push(self.deltas.len() as u8);
+        for (register, value) in &self.deltas {
+            buf.push(*register);
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:#06X} {:#06X} {} ;",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            self.mnemonic()
+        )?;
+        for (register, value) in &self.deltas {
+            if *register == I_REGISTER {
+                write!(f, " I={:#06X}", value)?;
+            } else {
+                write!(
+                    f,
+                    " {}={:#04X}",
+                    TraceRecord::register_name(*register),
+                    value
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TraceRecord {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parse_number = |number: &str| {
+            number
+                .strip_prefix("0x")
+                .or_else(|| number.strip_prefix("0X"))
+                .map(|hex| u16::from_str_radix(hex, 16))
+                .unwrap_or_else(|| number.parse::<u16>())
+                .map_err(|_| format!("invalid number '{}'", number))
+        };
+        let (instruction, deltas) = line
+            .rsplit_once(';')
+            .ok_or("expected 'CYCLE PC OPCODE MNEMONIC ; REGISTER=VALUE...'")?;
+        // The mnemonic is decoded from the opcode, so it is not parsed.
+        let mut fields = instruction.split_whitespace();
+        let (Some(cycle), Some(pc), Some(opcode)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err("expected 'CYCLE PC OPCODE'".to_string());
+        };
+        let cycle = cycle
+            .parse::<u64>()
+            .map_err(|_| format!("invalid cycle '{}'", cycle))?;
+        let deltas = deltas
+            .split_whitespace()
+            .map(|delta| {
+                let (name, value) = delta
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected 'REGISTER=VALUE', found '{}'", delta))?;
+                let register = REGISTER_NAMES
+                    .iter()
+                    .position(|register| register.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("unknown register '{}'", name))?;
+                Ok((register as u8, parse_number(value)?))
+            })
+            .collect::<Result<Vec<(u8, u16)>, String>>()?;
+        Ok(TraceRecord {
+            cycle,
+            pc: parse_number(pc)?,
+            opcode: parse_number(opcode)?,
+            deltas,
+        })
+    }
+}
+
+/// Read a trace written in any of the `TraceFormat` formats.
+pub fn read_trace(buf: &[u8]) -> Result<Vec<TraceRecord>, VMError> {
+    if buf.starts_with(CHIP8_TRACE_MAGIC) {
+        return read_binary_trace(&buf[CHIP8_TRACE_MAGIC.len()..]);
+    }
+    let text = std::str::from_utf8(buf)
+        .map_err(|_| VMError::InvalidTrace(0, "not a text or binary trace".to_string()))?;
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            line.parse::<TraceRecord>()
+                .map_err(|reason| VMError::InvalidTrace(index + 1, reason))
+        })
+        .collect()
+}
+
+fn read_binary_trace(mut buf: &[u8]) -> Result<Vec<TraceRecord>, VMError> {
+    fn read<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], VMError> {
+        if buf.len() < len {
+            return Err(VMError::InvalidTrace(
+                0,
+                "truncated binary trace".to_string(),
+            ));
+        }
+        let (bytes, rest) = buf.split_at(len);
+        *buf = rest;
+        Ok(bytes)
+    }
+    let read_u16 = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]);
+
+    let version = read(&mut buf, 1)?[0];
+    if version != CHIP8_TRACE_VERSION {
+        return Err(VMError::InvalidTrace(
+            0,
+            format!("unsupported binary trace version {}", version),
+        ));
+    }
+    let mut records = Vec::new();
+    while !buf.is_empty() {
+        let header = read(&mut buf, 13)?;
+        let mut record = TraceRecord {
+            cycle: u64::from_be_bytes(header[..8].try_into().expect("8 bytes")),
+            pc: read_u16(&header[8..10]),
+            opcode: read_u16(&header[10..12]),
+            deltas: Vec::new(),
+        };
+        for _ in 0..header[12] {
+            let delta = read(&mut buf, 3)?;
+            if delta[0] as usize >= TRACE_REGISTERS {
+                return Err(VMError::InvalidTrace(
+                    0,
+                    format!("invalid register index {}", delta[0]),
+                ));
+            }
+            record.deltas.push((delta[0], read_u16(&delta[1..])));
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// `Trace` is a sink for the instructions executed by the VM, see `VM::set_trace`. Each
+/// instruction is written with its address, opcode, mnemonic and the registers it changed,
+/// unless it is filtered out by the address or cycle range.
+pub struct Trace {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    addresses: RangeInclusive<u16>,
+    cycles: Range<u64>,
+    cycle: u64,
+}
+
+impl Trace {
+    /// Start a trace written to `writer`, usually a buffered file.
+    pub fn new(mut writer: Box<dyn Write>, format: TraceFormat) -> Result<Self, VMError> {
+        if format == TraceFormat::Binary {
+            writer
+                .write_all(CHIP8_TRACE_MAGIC)
+                .and_then(|_| writer.write_all(&[CHIP8_TRACE_VERSION]))
+                .map_err(|e| VMError::TraceWriteFailed(e.to_string()))?;
+        }
+        Ok(Trace {
+            writer,
+            format,
+            addresses: 0..=u16::MAX,
+            cycles: 0..u64::MAX,
+            cycle: 0,
+        })
+    }
+
+    /// Only trace the instructions at these addresses.
+    pub fn set_address_range(&mut self, addresses: RangeInclusive<u16>) {
+        self.addresses = addresses;
+    }
+
+    /// Only trace the instructions executed in these cycles, counted from the start of the
+    /// trace.
+    pub fn set_cycle_range(&mut self, cycles: Range<u64>) {
+        self.cycles = cycles;
+    }
+
+    pub fn flush(&mut self) -> Result<(), VMError> {
+        self.writer
+            .flush()
+            .map_err(|e| VMError::TraceWriteFailed(e.to_string()))
+    }
+
+    /// Trace the instruction executed at `pc`, given the registers before and after it.
+    pub(crate) fn record(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        before: &TraceRegisters,
+        after: &TraceRegisters,
+    ) -> Result<(), VMError> {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        if !self.cycles.contains(&cycle) || !self.addresses.contains(&pc) {
+            return Ok(());
+        }
+        let record = TraceRecord::new(cycle, pc, opcode, before, after);
+        let result = match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", record),
+            TraceFormat::Binary => {
+                let mut buf = Vec::new();
+                record.write_binary(&mut buf);
+                self.writer.write_all(&buf)
+            }
+        };
+        result.map_err(|e| VMError::TraceWriteFailed(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{read_trace, Trace, TraceFormat, TraceRecord};
+    use crate::{Quirks, VM};
+
+    /// Writer shared with the test, to read back the trace.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // 0x200: LD V3, 0x10, 0x202: LD I, 0x300, 0x204: ADD V3, 0xF0, 0x206: JP 0x200
+    const PROGRAM: [u8; 8] = [0x63, 0x10, 0xA3, 0x00, 0x73, 0xF0, 0x12, 0x00];
+
+    fn trace_program(format: TraceFormat, filter: impl Fn(&mut Trace)) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let mut trace = Trace::new(Box::new(buffer.clone()), format).unwrap();
+        filter(&mut trace);
+        let mut chip8: VM = VM::new(Quirks::default());
+        chip8.load_program(&PROGRAM).unwrap();
+        chip8.set_trace(Some(trace));
+        for _ in 0..6 {
+            chip8.exec_next_opcode().unwrap();
+        }
+        let bytes = buffer.0.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn text_trace() {
+        let text = String::from_utf8(trace_program(TraceFormat::Text, |_| {})).unwrap();
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            lines[..4],
+            [
+                "0 0x0200 0x6310 LD V3, 0x10 ; V3=0x10",
+                "1 0x0202 0xA300 LD I, 0x300 ; I=0x0300",
+                "2 0x0204 0x73F0 ADD V3, 0xF0 ; V3=0x00",
+                "3 0x0206 0x1200 JP 0x200 ;",
+            ]
+        );
+        assert_eq!(lines.len(), 6);
+        let records = read_trace(text.as_bytes()).unwrap();
+        assert_eq!(records[1].deltas, [(16, 0x300)]);
+        assert_eq!(
+            records.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            lines
+        );
+        assert!(read_trace(b"0 0x0200 0x6310 LD V3, 0x10 ; V3").is_err());
+    }
+
+    #[test]
+    fn binary_trace() {
+        let binary = trace_program(TraceFormat::Binary, |_| {});
+        let text = trace_program(TraceFormat::Text, |_| {});
+        assert!(binary.starts_with(b"C8TR\x01"));
+        assert!(binary.len() < text.len());
+        assert_eq!(read_trace(&binary).unwrap(), read_trace(&text).unwrap());
+        assert!(read_trace(&binary[..binary.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn filtered_trace() {
+        let text = trace_program(TraceFormat::Text, |trace| {
+            trace.set_address_range(0x202..=0x204);
+            trace.set_cycle_range(0..5);
+        });
+        let cycles = read_trace(&text)
+            .unwrap()
+            .iter()
+            .map(|record| (record.cycle, record.pc))
+            .collect::<Vec<(u64, u16)>>();
+        assert_eq!(cycles, [(1, 0x202), (2, 0x204)]);
+        assert_eq!(TraceRecord::register_name(18), "DT");
+    }
+}
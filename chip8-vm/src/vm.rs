@@ -1,13 +1,30 @@
 use crate::{
-    config::CHIP8_PROGRAM_LOAD_ADDRESS,
-    cpu::{Registers, Stack, VMContext, OPCODES},
+    config::{
+        CHIP8_AUDIO_PATTERN_SIZE, CHIP8_DEFAULT_CYCLES_PER_FRAME, CHIP8_PROGRAM_LOAD_ADDRESS,
+        CHIP8_TOTAL_KEYS, CHIP8_TOTAL_STACK_DEPTH,
+    },
+    cpu::{Registers, Rng, RngAlgorithm, Stack, VMContext, OPCODES},
     errors::VMError,
-    io::{Keyboard, Screen},
-    memory::RAM,
+    io::{Audio, ChipKey, Keyboard, Screen},
+    memory::{MemoryAccess, RAM},
+    quirks::Quirks,
+    state::{StateReader, StateWriter},
+    trace::{Trace, TraceRegisters},
 };
 
 pub use crate::cpu::Signal;
 
+/// What happened while `VM::run_frame` ran a frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameSummary {
+    /// Instructions executed, less than requested if the program exited.
+    pub cycles: usize,
+    /// True if the screen changed and should be drawn.
+    pub draw_screen: bool,
+    /// True if the program executed the EXIT instruction.
+    pub exit: bool,
+}
+
 #[derive(Default)]
 pub struct VM {
     memory: RAM,
@@ -15,11 +32,58 @@ pub struct VM {
     stack: Stack,
     keyboard: Keyboard,
     screen: Screen,
+    audio: Audio,
+    quirks: Quirks,
+    rng: Rng,
+    waiting_vblank: bool,
+    waiting_keyup: Option<u8>,
+    trace: Option<Trace>,
 }
 
 impl VM {
-    pub fn new() -> Self {
-        VM::default()
+    /// Instructions per 60 Hz frame when the front-end does not ask for another speed.
+    pub const DEFAULT_CYCLES_PER_FRAME: usize = CHIP8_DEFAULT_CYCLES_PER_FRAME;
+
+    pub fn new(quirks: Quirks) -> Self {
+        VM {
+            quirks,
+            ..Default::default()
+        }
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    /// Replace the random number generator used by `Cxkk` with one seeded with `seed`, so the
+    /// random numbers are the same on every run.
+    pub fn set_rng(&mut self, algorithm: RngAlgorithm, seed: u64) {
+        self.rng = Rng::new(algorithm, seed);
+    }
+
+    /// Trace the executed instructions into `trace`, or stop tracing with `None`.
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        self.trace = trace;
+    }
+
+    /// Stop tracing and return the trace, e.g. to flush it.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Signals the VM that the front-end started a new 60 Hz frame (vertical blank interrupt).
+    pub fn vblank(&mut self) {
+        self.waiting_vblank = false;
+    }
+
+    /// Count down the delay and sound timers, to be called once per 60 Hz frame.
+    pub fn tick_timers(&mut self) {
+        if self.registers.dt > 0 {
+            self.registers.dec_dt();
+        }
+        if self.registers.st > 0 {
+            self.registers.dec_st();
+        }
     }
 
     pub fn load_program(&mut self, buf: &[u8]) -> Result<(), VMError> {
@@ -28,74 +92,217 @@ impl VM {
         Ok(())
     }
 
-    pub fn keyboard_key_down(&mut self, key: i32, keymap: &[(i32, usize)]) {
-        self.keyboard.key_down(key, keymap)
+    /// Serializes the whole machine state (memory, registers, stack, keyboard, screen, audio,
+    /// quirks and random number generator) into a versioned binary save state.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        self.memory.save_state(&mut state);
+        self.registers.save_state(&mut state);
+        self.stack.save_state(&mut state);
+        self.keyboard.save_state(&mut state);
+        self.screen.save_state(&mut state);
+        self.audio.save_state(&mut state);
+        self.quirks.save_state(&mut state);
+        self.rng.save_state(&mut state);
+        state.write_bool(self.waiting_vblank);
+        state.write_bool(self.waiting_keyup.is_some());
+        state.write_u8(self.waiting_keyup.unwrap_or_default());
+        state.into_bytes()
     }
 
-    pub fn keyboard_key_up(&mut self, key: i32, keymap: &[(i32, usize)]) {
-        self.keyboard.key_up(key, keymap)
+    /// Restores a save state produced by `save_state`. The VM is left untouched when the state
+    /// is invalid.
+    pub fn load_state(&mut self, buf: &[u8]) -> Result<(), VMError> {
+        let mut state = StateReader::new(buf)?;
+        let mut vm = VM::default();
+        vm.memory.load_state(&mut state)?;
+        vm.registers.load_state(&mut state)?;
+        vm.stack.load_state(&mut state)?;
+        vm.keyboard.load_state(&mut state)?;
+        vm.screen.load_state(&mut state)?;
+        vm.audio.load_state(&mut state)?;
+        vm.quirks.load_state(&mut state)?;
+        vm.rng.load_state(&mut state)?;
+        vm.waiting_vblank = state.read_bool()?;
+        let waiting_keyup = state.read_bool()?;
+        let key = state.read_u8()?;
+        if key as usize >= CHIP8_TOTAL_KEYS {
+            return Err(VMError::InvalidState);
+        }
+        vm.waiting_keyup = waiting_keyup.then_some(key);
+        state.finish()?;
+        // The trace is not part of the machine state.
+        vm.trace = self.trace.take();
+        *self = vm;
+        Ok(())
     }
 
-    pub fn screen_is_pixel_set(&mut self, x: usize, y: usize) -> Result<bool, VMError> {
+    /// Press or release a CHIP-8 key. Mapping the host keys or buttons to the CHIP-8 keys is
+    /// left to the front-ends.
+    pub fn set_key(&mut self, key: ChipKey, down: bool) {
+        self.keyboard.set_key(key, down)
+    }
+
+    pub fn screen_width(&self) -> usize {
+        self.screen.width()
+    }
+
+    pub fn screen_height(&self) -> usize {
+        self.screen.height()
+    }
+
+    pub fn screen_is_pixel_set(&self, x: usize, y: usize) -> Result<bool, VMError> {
         self.screen.is_pixel_set(x, y)
     }
 
-    pub fn registers_dt(&mut self) -> u8 {
+    /// Returns the bit planes set for the pixel, which is also its colour index (0 to 3).
+    pub fn screen_pixel_planes(&self, x: usize, y: usize) -> Result<u8, VMError> {
+        self.screen.pixel_planes(x, y)
+    }
+
+    pub fn audio_pattern(&self) -> [u8; CHIP8_AUDIO_PATTERN_SIZE] {
+        *self.audio.pattern()
+    }
+
+    /// Audio pattern playback rate in bits per second.
+    pub fn audio_playback_rate(&self) -> f32 {
+        self.audio.playback_rate()
+    }
+
+    pub fn registers_dt(&self) -> u8 {
         self.registers.dt
     }
 
-    pub fn registers_st(&mut self) -> u8 {
+    pub fn registers_st(&self) -> u8 {
         self.registers.st
     }
 
-    pub fn exec_next_opcode(
-        &mut self,
-        debug_dump: bool,
-        time_acc: &mut u32,
-    ) -> Result<Signal, VMError> {
-        let binary_opcode = self.memory.get_opcode(self.registers.get_pc() as usize)?;
-        self.exec_opcode(binary_opcode, debug_dump, time_acc)
+    pub fn registers_v(&self, index: usize) -> u8 {
+        self.registers.get_v_register(index)
     }
 
-    fn exec_opcode(
-        &mut self,
-        binary_opcode: u16,
-        debug_dump: bool,
-        time_acc: &mut u32,
-    ) -> Result<Signal, VMError> {
-        let time_per_delay = 60; // miliseconds
-        for opcode_matcher in OPCODES {
-            if opcode_matcher.check_matching(binary_opcode) {
-                let mut ctx = self.build_vmcontext(binary_opcode, opcode_matcher.desc());
+    pub fn registers_i(&self) -> u16 {
+        self.registers.get_i()
+    }
 
-                if debug_dump {
-                    opcode_matcher.pre_ex_dump()(&ctx);
-                }
+    pub fn registers_pc(&self) -> u16 {
+        self.registers.get_pc()
+    }
 
-                // Execute Opcode instructions
-                let signal = opcode_matcher.instructions()(&mut ctx)?;
+    pub fn registers_sp(&self) -> u8 {
+        self.registers.get_sp()
+    }
 
-                if debug_dump {
-                    opcode_matcher.post_ex_dump()(&ctx);
-                }
+    /// Returns the return address stored at the `level` stack slot.
+    pub fn stack_get(&self, level: u8) -> Result<u16, VMError> {
+        if level as usize >= CHIP8_TOTAL_STACK_DEPTH {
+            return Err(VMError::StackOutOfBounds(level as usize));
+        }
+        self.stack.get_at(level)
+    }
+
+    /// The whole memory, to compare the memory of two VMs.
+    pub(crate) fn memory_bytes(&self) -> &[u8] {
+        self.memory.bytes()
+    }
+
+    pub fn memory_get(&self, addr: usize) -> Result<u8, VMError> {
+        self.memory.get(addr)
+    }
 
-                // Update timers
-                if *time_acc > time_per_delay {
-                    if self.registers_dt() > 0 {
-                        self.registers_dec_dt();
-                    }
-                    if self.registers_st() > 0 {
-                        self.registers_dec_st();
-                    }
-                    *time_acc = 0;
+    pub fn memory_opcode(&self, addr: usize) -> Result<u16, VMError> {
+        self.memory.get_opcode(addr)
+    }
+
+    pub(crate) fn set_memory_access_log(&mut self, enabled: bool) {
+        self.memory.set_access_log(enabled);
+    }
+
+    pub(crate) fn take_memory_accesses(&mut self) -> Vec<MemoryAccess> {
+        self.memory.take_accesses()
+    }
+
+    /// Returns the mnemonic of the opcode, or `None` if it is not a valid instruction.
+    pub fn opcode_mnemonic(binary_opcode: u16) -> Option<String> {
+        OPCODES
+            .iter()
+            .find(|opcode_matcher| opcode_matcher.check_matching(binary_opcode))
+            .map(|opcode_matcher| opcode_matcher.mnemonic(binary_opcode))
+    }
+
+    /// Run a 60 Hz frame: signal the vertical blank, count down the timers and execute
+    /// `cycles_per_frame` instructions, or less if the program exits. The game speed only
+    /// depends on the number of instructions per frame, not on how often the program draws,
+    /// and the timers run at 60 Hz whatever the speed.
+    pub fn run_frame(&mut self, cycles_per_frame: usize) -> Result<FrameSummary, VMError> {
+        let mut summary = FrameSummary::default();
+        self.vblank();
+        self.tick_timers();
+        while summary.cycles < cycles_per_frame {
+            let signal = self.exec_next_opcode()?;
+            summary.cycles += 1;
+            match signal {
+                Signal::DrawScreen => summary.draw_screen = true,
+                Signal::Exit => {
+                    summary.exit = true;
+                    break;
                 }
-                return Ok(signal);
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Execute the instruction at PC. The timers are not updated, see `tick_timers`.
+    pub fn exec_next_opcode(&mut self) -> Result<Signal, VMError> {
+        let binary_opcode = self.next_opcode()?;
+        self.exec_opcode(binary_opcode)
+    }
+
+    /// The opcode at PC.
+    pub(crate) fn next_opcode(&self) -> Result<u16, VMError> {
+        self.memory.get_opcode(self.registers.get_pc() as usize)
+    }
+
+    fn exec_opcode(&mut self, binary_opcode: u16) -> Result<Signal, VMError> {
+        let Some(mut trace) = self.trace.take() else {
+            return self.exec_opcode_untraced(binary_opcode);
+        };
+        let pc = self.registers.get_pc();
+        let before = self.trace_registers();
+        let result = self.exec_opcode_untraced(binary_opcode);
+        let traced = match result {
+            Ok(_) => trace.record(pc, binary_opcode, &before, &self.trace_registers()),
+            Err(_) => Ok(()),
+        };
+        self.trace = Some(trace);
+        traced?;
+        result
+    }
+
+    pub(crate) fn trace_registers(&self) -> TraceRegisters {
+        let mut registers = [0; crate::trace::TRACE_REGISTERS];
+        for (index, value) in registers.iter_mut().take(16).enumerate() {
+            *value = self.registers.get_v_register(index) as u16;
+        }
+        registers[16] = self.registers.get_i();
+        registers[17] = self.registers.get_sp() as u16;
+        registers[18] = self.registers.get_dt() as u16;
+        registers[19] = self.registers.st as u16;
+        registers
+    }
+
+    fn exec_opcode_untraced(&mut self, binary_opcode: u16) -> Result<Signal, VMError> {
+        for opcode_matcher in OPCODES {
+            if opcode_matcher.check_matching(binary_opcode) {
+                let mut ctx = self.build_vmcontext(binary_opcode);
+                return opcode_matcher.instructions()(&mut ctx);
             }
         }
         Err(VMError::InvalidOpcode(binary_opcode))
     }
 
-    fn build_vmcontext<'a>(&'a mut self, binary_opcode: u16, pattern: &'a str) -> VMContext<'a> {
+    fn build_vmcontext(&mut self, binary_opcode: u16) -> VMContext<'_> {
         VMContext {
             opcode: binary_opcode,
             stack: &mut self.stack,
@@ -103,30 +310,26 @@ impl VM {
             registers: &mut self.registers,
             keyboard: &self.keyboard,
             screen: &mut self.screen,
-            pattern: pattern,
+            audio: &mut self.audio,
+            quirks: &self.quirks,
+            rng: &mut self.rng,
+            waiting_vblank: &mut self.waiting_vblank,
+            waiting_keyup: &mut self.waiting_keyup,
         }
     }
-
-    fn registers_dec_dt(&mut self) {
-        self.registers.dec_dt();
-    }
-
-    fn registers_dec_st(&mut self) {
-        self.registers.dec_st();
-    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::VM;
+    use crate::{ChipKey, Quirks, VM};
 
     #[test]
     fn call_ret() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
 
         // Call
-        chip8.exec_opcode(0x2300, false, &mut 0).expect("Call");
+        chip8.exec_opcode(0x2300).expect("Call");
 
         assert_eq!(chip8.registers.get_sp(), 0x001);
         assert_eq!(
@@ -139,44 +342,40 @@ mod tests {
         assert_eq!(chip8.registers.get_pc(), 0x0300);
 
         // Return
-        chip8
-            .exec_opcode(0x00EE, false, &mut 0)
-            .expect("Return from subroutine");
+        chip8.exec_opcode(0x00EE).expect("Return from subroutine");
         assert_eq!(chip8.registers.get_pc(), 0x0202);
         assert_eq!(chip8.registers.get_sp(), 0x0000);
     }
 
     #[test]
     fn jp() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
 
-        chip8.exec_opcode(0x1300, false, &mut 0).expect("Jump");
+        chip8.exec_opcode(0x1300).expect("Jump");
 
         assert_eq!(chip8.registers.get_pc(), 0x0300);
     }
 
     #[test]
     fn se_vx_byte() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x001);
 
-        chip8
-            .exec_opcode(0x3001, false, &mut 0)
-            .expect("Skip next instruction");
+        chip8.exec_opcode(0x3001).expect("Skip next instruction");
 
         assert_eq!(chip8.registers.get_pc(), 0x0204);
     }
 
     #[test]
     fn se_vx_byte_nojp() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x001);
 
         chip8
-            .exec_opcode(0x3002, false, &mut 0)
+            .exec_opcode(0x3002)
             .expect("not skip next instruction");
 
         assert_eq!(chip8.registers.get_pc(), 0x0202);
@@ -184,13 +383,13 @@ mod tests {
 
     #[test]
     fn se_vx_vy() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x001);
         chip8.registers.set_v_register(1, 0x001);
 
         chip8
-            .exec_opcode(0x5010, false, &mut 0)
+            .exec_opcode(0x5010)
             .expect("Skip next instruction if Vx = Vy");
 
         assert_eq!(chip8.registers.get_pc(), 0x0204);
@@ -198,13 +397,13 @@ mod tests {
 
     #[test]
     fn se_vx_vy_nojp() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x000);
         chip8.registers.set_v_register(1, 0x001);
 
         chip8
-            .exec_opcode(0x5010, false, &mut 0)
+            .exec_opcode(0x5010)
             .expect("Not skip next instruction if Vx = Vy");
 
         assert_eq!(chip8.registers.get_pc(), 0x0202);
@@ -212,36 +411,32 @@ mod tests {
 
     #[test]
     fn sne_vx_byte() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x01);
 
-        chip8.exec_opcode(0x4002, false, &mut 0).expect("Jump");
+        chip8.exec_opcode(0x4002).expect("Jump");
 
         assert_eq!(chip8.registers.get_pc(), 0x0204);
     }
 
     #[test]
     fn sne_vx_byte_nojp() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x01);
 
-        chip8.exec_opcode(0x4001, false, &mut 0).expect("No jump");
+        chip8.exec_opcode(0x4001).expect("No jump");
 
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
 
     #[test]
     fn add_vx_byte() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8
-            .exec_opcode(0x60fe, false, &mut 0)
-            .expect("Set V0 to 255");
-        chip8
-            .exec_opcode(0x7001, false, &mut 0)
-            .expect("Set V0 = V0 + KK");
+        chip8.exec_opcode(0x60fe).expect("Set V0 to 255");
+        chip8.exec_opcode(0x7001).expect("Set V0 = V0 + KK");
 
         assert_eq!(chip8.registers.v_0, 255);
         assert_eq!(chip8.registers.get_pc(), 0x0204);
@@ -249,12 +444,12 @@ mod tests {
 
     #[test]
     fn add_vx_vy_with_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 200);
         chip8.registers.set_v_register(1, 60);
         chip8
-            .exec_opcode(0x8014, false, &mut 0)
+            .exec_opcode(0x8014)
             .expect("Set V0 = V0 + V1, with carry");
 
         assert_eq!(chip8.registers.v_0, 4);
@@ -264,12 +459,12 @@ mod tests {
 
     #[test]
     fn add_vx_vy_not_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 200);
         chip8.registers.set_v_register(1, 50);
         chip8
-            .exec_opcode(0x8014, false, &mut 0)
+            .exec_opcode(0x8014)
             .expect("Set V0 = V0 + V1, not carry");
 
         assert_eq!(chip8.registers.v_0, 250);
@@ -279,12 +474,12 @@ mod tests {
 
     #[test]
     fn sub_vx_vy_not_borrow() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 255);
         chip8.registers.set_v_register(1, 254);
         chip8
-            .exec_opcode(0x8015, false, &mut 0)
+            .exec_opcode(0x8015)
             .expect("Set V0 = V0 - V1, with carry");
 
         assert_eq!(chip8.registers.v_0, 1);
@@ -294,12 +489,12 @@ mod tests {
 
     #[test]
     fn sub_vx_vy_with_borrow() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 254);
         chip8.registers.set_v_register(1, 255);
         chip8
-            .exec_opcode(0x8015, false, &mut 0)
+            .exec_opcode(0x8015)
             .expect("Set V0 = V0 - V1, not carry");
 
         assert_eq!(chip8.registers.v_0, 255);
@@ -309,13 +504,11 @@ mod tests {
 
     #[test]
     fn shr_vx_with_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.unset_vf();
-        chip8
-            .exec_opcode(0x7005, false, &mut 0)
-            .expect("Set V0 to 5");
-        chip8.exec_opcode(0x8006, false, &mut 0).expect("Set carry");
+        chip8.exec_opcode(0x7005).expect("Set V0 to 5");
+        chip8.exec_opcode(0x8006).expect("Set carry");
 
         assert_eq!(chip8.registers.v_0, 2);
         assert_eq!(chip8.registers.v_f, 1);
@@ -324,15 +517,11 @@ mod tests {
 
     #[test]
     fn shr_vx_not_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_vf();
-        chip8
-            .exec_opcode(0x600a, false, &mut 0)
-            .expect("Set V0 to 10");
-        chip8
-            .exec_opcode(0x8006, false, &mut 0)
-            .expect("Set not carry");
+        chip8.exec_opcode(0x600a).expect("Set V0 to 10");
+        chip8.exec_opcode(0x8006).expect("Set not carry");
 
         assert_eq!(chip8.registers.v_0, 5);
         assert_eq!(chip8.registers.v_f, 0);
@@ -341,17 +530,13 @@ mod tests {
 
     #[test]
     fn subn_vx_vy_with_borrow() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.unset_vf();
+        chip8.exec_opcode(0x60fe).expect("Set V0 to 254");
+        chip8.exec_opcode(0x71ff).expect("Set V1 to 255");
         chip8
-            .exec_opcode(0x60fe, false, &mut 0)
-            .expect("Set V0 to 254");
-        chip8
-            .exec_opcode(0x71ff, false, &mut 0)
-            .expect("Set V1 to 255");
-        chip8
-            .exec_opcode(0x8017, false, &mut 0)
+            .exec_opcode(0x8017)
             .expect("Set V0 = V1 - V0, with borrow");
 
         assert_eq!(chip8.registers.v_0, 1);
@@ -361,17 +546,13 @@ mod tests {
 
     #[test]
     fn subn_vx_vy_not_borrow() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_vf();
+        chip8.exec_opcode(0x60ff).expect("Set V0 to 255");
+        chip8.exec_opcode(0x71fe).expect("Set V1 to 254");
         chip8
-            .exec_opcode(0x60ff, false, &mut 0)
-            .expect("Set V0 to 255");
-        chip8
-            .exec_opcode(0x71fe, false, &mut 0)
-            .expect("Set V1 to 254");
-        chip8
-            .exec_opcode(0x8017, false, &mut 0)
+            .exec_opcode(0x8017)
             .expect("Set V0 = V1 - V0, not borrow");
 
         assert_eq!(chip8.registers.v_0, 255);
@@ -381,13 +562,11 @@ mod tests {
 
     #[test]
     fn shl_vx_with_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.unset_vf();
-        chip8
-            .exec_opcode(0x6080, false, &mut 0)
-            .expect("Set V0 to 128");
-        chip8.exec_opcode(0x800E, false, &mut 0).expect("Set carry");
+        chip8.exec_opcode(0x6080).expect("Set V0 to 128");
+        chip8.exec_opcode(0x800E).expect("Set carry");
 
         assert_eq!(chip8.registers.v_0, 0);
         assert_eq!(chip8.registers.v_f, 1);
@@ -396,15 +575,11 @@ mod tests {
 
     #[test]
     fn shl_vx_not_carry() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_vf();
-        chip8
-            .exec_opcode(0x607f, false, &mut 0)
-            .expect("Set V0 to 127");
-        chip8
-            .exec_opcode(0x800E, false, &mut 0)
-            .expect("Set not carry");
+        chip8.exec_opcode(0x607f).expect("Set V0 to 127");
+        chip8.exec_opcode(0x800E).expect("Set not carry");
 
         assert_eq!(chip8.registers.v_0, 254);
         assert_eq!(chip8.registers.v_f, 0);
@@ -413,45 +588,31 @@ mod tests {
 
     #[test]
     fn sne_vx_vy() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8
-            .exec_opcode(0x60ff, false, &mut 0)
-            .expect("Set V0 to 255");
-        chip8
-            .exec_opcode(0x61ee, false, &mut 0)
-            .expect("Set V1 t0 255");
-        chip8
-            .exec_opcode(0x9010, false, &mut 0)
-            .expect("Skip next instruction");
+        chip8.exec_opcode(0x60ff).expect("Set V0 to 255");
+        chip8.exec_opcode(0x61ee).expect("Set V1 t0 255");
+        chip8.exec_opcode(0x9010).expect("Skip next instruction");
 
         assert_eq!(chip8.registers.get_pc(), 0x0208);
     }
 
     #[test]
     fn sne_vx_vy_not_skip() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8
-            .exec_opcode(0x60FF, false, &mut 0)
-            .expect("Set V0 to 255");
-        chip8
-            .exec_opcode(0x61FF, false, &mut 0)
-            .expect("Set V1 t0 255");
-        chip8
-            .exec_opcode(0x9010, false, &mut 0)
-            .expect("Skip next instruction");
+        chip8.exec_opcode(0x60FF).expect("Set V0 to 255");
+        chip8.exec_opcode(0x61FF).expect("Set V1 t0 255");
+        chip8.exec_opcode(0x9010).expect("Skip next instruction");
 
         assert_eq!(chip8.registers.get_pc(), 0x0206);
     }
 
     #[test]
     fn ld_i_addr() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8
-            .exec_opcode(0xAFFF, false, &mut 0)
-            .expect("Set I to FFF");
+        chip8.exec_opcode(0xAFFF).expect("Set I to FFF");
 
         assert_eq!(chip8.registers.get_i(), 0x0FFF);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
@@ -459,187 +620,123 @@ mod tests {
 
     #[test]
     fn jp_v0_addr() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8
-            .exec_opcode(0x6002, false, &mut 0)
-            .expect("Set V0 to 002");
-        chip8
-            .exec_opcode(0xB300, false, &mut 0)
-            .expect("Set PC to V0 + 002");
+        chip8.exec_opcode(0x6002).expect("Set V0 to 002");
+        chip8.exec_opcode(0xB300).expect("Set PC to V0 + 002");
 
         assert_eq!(chip8.registers.get_pc(), 0x0302);
     }
 
     #[test]
     fn drw_vx_vy_nbytes() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks {
+            display_wait: false,
+            ..Quirks::default()
+        });
         chip8.registers.set_pc(0x0200);
 
         // No collision, yet
         assert_eq!(chip8.registers.get_v_register(0xF), 0);
 
-        chip8
-            .exec_opcode(0xA000, false, &mut 0)
-            .expect("Set I to 00");
-        chip8
-            .exec_opcode(0x600A, false, &mut 0)
-            .expect("Set V0 to 10");
-        chip8
-            .exec_opcode(0x610A, false, &mut 0)
-            .expect("Set V1 to 10");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0xA000).expect("Set I to 00");
+        chip8.exec_opcode(0x600A).expect("Set V0 to 10");
+        chip8.exec_opcode(0x610A).expect("Set V1 to 10");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
 
         // Expect that '0' is printed in screen at (10, 10)
-        assert_eq!(chip8.screen.is_pixel_set(10, 10).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(10, 11).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(10, 12).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(10, 13).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(10, 14).unwrap(), true);
-
-        assert_eq!(chip8.screen.is_pixel_set(11, 10).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(11, 11).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(11, 12).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(11, 13).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(11, 14).unwrap(), true);
-
-        assert_eq!(chip8.screen.is_pixel_set(12, 10).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(12, 11).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(12, 12).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(12, 13).unwrap(), false);
-        assert_eq!(chip8.screen.is_pixel_set(12, 14).unwrap(), true);
-
-        assert_eq!(chip8.screen.is_pixel_set(13, 10).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(13, 11).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(13, 12).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(13, 13).unwrap(), true);
-        assert_eq!(chip8.screen.is_pixel_set(13, 14).unwrap(), true);
+        assert!(chip8.screen.is_pixel_set(10, 10).unwrap());
+        assert!(chip8.screen.is_pixel_set(10, 11).unwrap());
+        assert!(chip8.screen.is_pixel_set(10, 12).unwrap());
+        assert!(chip8.screen.is_pixel_set(10, 13).unwrap());
+        assert!(chip8.screen.is_pixel_set(10, 14).unwrap());
+
+        assert!(chip8.screen.is_pixel_set(11, 10).unwrap());
+        assert!(!chip8.screen.is_pixel_set(11, 11).unwrap());
+        assert!(!chip8.screen.is_pixel_set(11, 12).unwrap());
+        assert!(!chip8.screen.is_pixel_set(11, 13).unwrap());
+        assert!(chip8.screen.is_pixel_set(11, 14).unwrap());
+
+        assert!(chip8.screen.is_pixel_set(12, 10).unwrap());
+        assert!(!chip8.screen.is_pixel_set(12, 11).unwrap());
+        assert!(!chip8.screen.is_pixel_set(12, 12).unwrap());
+        assert!(!chip8.screen.is_pixel_set(12, 13).unwrap());
+        assert!(chip8.screen.is_pixel_set(12, 14).unwrap());
+
+        assert!(chip8.screen.is_pixel_set(13, 10).unwrap());
+        assert!(chip8.screen.is_pixel_set(13, 11).unwrap());
+        assert!(chip8.screen.is_pixel_set(13, 12).unwrap());
+        assert!(chip8.screen.is_pixel_set(13, 13).unwrap());
+        assert!(chip8.screen.is_pixel_set(13, 14).unwrap());
 
         assert_eq!(chip8.registers.get_pc(), 0x0208);
 
-        chip8
-            .exec_opcode(0x600D, false, &mut 0)
-            .expect("Set V0 to 13");
-        chip8
-            .exec_opcode(0x610E, false, &mut 0)
-            .expect("Set V1 to 14");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0x600D).expect("Set V0 to 13");
+        chip8.exec_opcode(0x610E).expect("Set V1 to 14");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
         // Collision!
         assert_eq!(chip8.registers.get_v_register(0xF), 1);
 
         assert_eq!(chip8.registers.get_pc(), 0x020E);
 
-        chip8
-            .exec_opcode(0x600A, false, &mut 0)
-            .expect("Set V0 to 10");
-        chip8
-            .exec_opcode(0x6112, false, &mut 0)
-            .expect("Set V1 to 18");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0x600A).expect("Set V0 to 10");
+        chip8.exec_opcode(0x6112).expect("Set V1 to 18");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
         // Collision!
         assert_eq!(chip8.registers.get_v_register(0xF), 1);
 
         assert_eq!(chip8.registers.get_pc(), 0x0214);
 
-        chip8
-            .exec_opcode(0x6010, false, &mut 0)
-            .expect("Set V0 to 16");
-        chip8
-            .exec_opcode(0x610A, false, &mut 0)
-            .expect("Set V1 to 10");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0x6010).expect("Set V0 to 16");
+        chip8.exec_opcode(0x610A).expect("Set V1 to 10");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
         // Collision!
         assert_eq!(chip8.registers.get_v_register(0xF), 1);
 
-        chip8
-            .exec_opcode(0x6010, false, &mut 0)
-            .expect("Set V0 to 16");
-        chip8
-            .exec_opcode(0x6112, false, &mut 0)
-            .expect("Set V1 to 18");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0x6010).expect("Set V0 to 16");
+        chip8.exec_opcode(0x6112).expect("Set V1 to 18");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
         // Collision!
         assert_eq!(chip8.registers.get_v_register(0xF), 1);
 
-        chip8
-            .exec_opcode(0x6014, false, &mut 0)
-            .expect("Set V0 to 13");
-        chip8
-            .exec_opcode(0x610E, false, &mut 0)
-            .expect("Set V1 to 14");
-        chip8
-            .exec_opcode(0xD015, false, &mut 0)
-            .expect("Draw 5 bytes sprite");
+        chip8.exec_opcode(0x6014).expect("Set V0 to 13");
+        chip8.exec_opcode(0x610E).expect("Set V1 to 14");
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
 
         // Collision!
         assert_eq!(chip8.registers.get_v_register(0xF), 0);
     }
 
-    static KEYMAP: &'static [(i32, usize)] = &[
-        (49, 0x1),
-        (50, 0x2),
-        (51, 0x3),
-        (52, 0xC),
-        (113, 0x4),
-        (119, 0x5),
-        (101, 0x6),
-        (114, 0xD),
-        (97, 0x7),
-        (115, 0x8),
-        (100, 0x9),
-        (102, 0xE),
-        (122, 0xA),
-        (120, 0x0),
-        (99, 0xB),
-        (118, 0xF),
-    ];
-
     #[test]
     fn skp_vx_key_down() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8.keyboard_key_down(97, KEYMAP); // User press 'A' key
-        chip8
-            .exec_opcode(0x6007, false, &mut 0)
-            .expect("Set V0 to match A key");
+        chip8.set_key(ChipKey::Key7, true);
+        chip8.exec_opcode(0x6007).expect("Set V0 to match A key");
         assert_eq!(chip8.registers.get_v_register(0), 0x7);
-        chip8
-            .exec_opcode(0xE09E, false, &mut 0)
-            .expect("Skip next instruction");
+        chip8.exec_opcode(0xE09E).expect("Skip next instruction");
         assert_eq!(chip8.registers.get_pc(), 0x0206);
     }
 
     #[test]
     fn skp_vx_key_up() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8.keyboard_key_up(97, KEYMAP); // User release 'A' key
-        chip8
-            .exec_opcode(0x600A, false, &mut 0)
-            .expect("Set V0 to match A key");
+        chip8.set_key(ChipKey::Key7, false);
+        chip8.exec_opcode(0x600A).expect("Set V0 to match A key");
         chip8
-            .exec_opcode(0xE09E, false, &mut 0)
+            .exec_opcode(0xE09E)
             .expect("Not skip next instruction");
         assert_eq!(chip8.registers.get_pc(), 0x0204);
     }
 
     #[test]
     fn ld_vx_dt() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_dt(0x0A);
         chip8
-            .exec_opcode(0xF007, false, &mut 0)
+            .exec_opcode(0xF007)
             .expect("Set V0 to delay timer value");
         assert_eq!(chip8.registers.get_v_register(0), 0x0A);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
@@ -647,64 +744,67 @@ mod tests {
 
     #[test]
     fn ld_vx_k() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
-        chip8.keyboard_key_down(97, KEYMAP);
-        chip8
-            .exec_opcode(0xF00A, false, &mut 0)
-            .expect("Wait to key up");
-        chip8.keyboard_key_up(97, KEYMAP);
-        chip8
-            .exec_opcode(0xF00A, false, &mut 0)
-            .expect("Set V0 to 0xA key");
+        chip8.set_key(ChipKey::Key7, true);
+        chip8.exec_opcode(0xF00A).expect("Wait to key up");
+        chip8.set_key(ChipKey::Key7, false);
+        chip8.exec_opcode(0xF00A).expect("Set V0 to 0xA key");
         assert_eq!(chip8.registers.get_v_register(0), 0x7);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
 
     #[test]
     fn ld_dt_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 10);
-        chip8
-            .exec_opcode(0xF015, false, &mut 0)
-            .expect("Set delay timer to Vx");
+        chip8.exec_opcode(0xF015).expect("Set delay timer to Vx");
         assert_eq!(chip8.registers.get_dt(), 0x0A);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
 
     #[test]
     fn ld_st_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 10);
-        chip8
-            .exec_opcode(0xF018, false, &mut 0)
-            .expect("Set sound timer to Vx");
+        chip8.exec_opcode(0xF018).expect("Set sound timer to Vx");
         assert_eq!(chip8.registers.st, 0x0A);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
 
     #[test]
     fn add_i_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 10);
         chip8.registers.set_i(10);
-        chip8
-            .exec_opcode(0xF01E, false, &mut 0)
-            .expect("Set I = I + V0 = 20");
+        chip8.exec_opcode(0xF01E).expect("Set I = I + V0 = 20");
         assert_eq!(chip8.registers.get_i(), 0x14);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
 
+    #[test]
+    fn add_i_vx_wraps() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8
+            .load_program(&[0xF0, 0x00, 0xFF, 0xFF, 0x60, 0x01, 0xF0, 0x1E])
+            .unwrap();
+        chip8.exec_next_opcode().expect("Set I = 0xFFFF");
+        chip8.exec_next_opcode().expect("Set V0 = 1");
+        chip8.exec_next_opcode().expect("Set I = I + V0");
+        assert_eq!(chip8.registers.get_i(), 0x0000);
+        assert_eq!(chip8.registers.get_pc(), 0x0208);
+    }
+
     #[test]
     fn ld_f_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_v_register(0, 0x02);
         chip8
-            .exec_opcode(0xF029, false, &mut 0)
+            .exec_opcode(0xF029)
             .expect("Set I = location of sprite for digit Vx");
         assert_eq!(chip8.registers.get_i(), 0x0A);
         assert_eq!(chip8.registers.get_pc(), 0x0202);
@@ -712,12 +812,12 @@ mod tests {
 
     #[test]
     fn ld_bcd_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_i(0x200);
         chip8.registers.set_v_register(0, 245);
         chip8
-            .exec_opcode(0xF033, false, &mut 0)
+            .exec_opcode(0xF033)
             .expect("Store BCD representation of Vx in memory locations I, I+1, and I+2");
 
         let base_addr = 0x200;
@@ -729,7 +829,7 @@ mod tests {
 
     #[test]
     fn ld_i_vx() {
-        let mut chip8: VM = VM::new();
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_i(0x200);
 
@@ -751,10 +851,10 @@ mod tests {
         chip8.registers.set_v_register(0xF, 0x00F);
 
         chip8
-            .exec_opcode(0xFF55, false, &mut 0)
+            .exec_opcode(0xFF55)
             .expect("Store registers V0 through Vx in memory starting at location I.");
 
-        let base_addr = 0x200 as usize;
+        let base_addr = 0x200_usize;
         assert_eq!(chip8.memory.get_ref(base_addr)[0x0], 0x000);
         assert_eq!(chip8.memory.get_ref(base_addr)[0x1], 0x001);
         assert_eq!(chip8.memory.get_ref(base_addr)[0x2], 0x002);
@@ -777,15 +877,12 @@ mod tests {
 
     #[test]
     fn ld_vx_i() {
-        let base_addr = 0x200 as usize;
-        let mut chip8: VM = VM::new();
+        let base_addr = 0x200_usize;
+        let mut chip8: VM = VM::new(Quirks::default());
         chip8.registers.set_pc(0x0200);
         chip8.registers.set_i(base_addr as u16);
 
-        chip8
-            .memory
-            .set(base_addr + 0x0, 0x000)
-            .expect("Store value");
+        chip8.memory.set(base_addr, 0x000).expect("Store value");
         chip8
             .memory
             .set(base_addr + 0x1, 0x001)
@@ -848,7 +945,7 @@ mod tests {
             .expect("Store value");
 
         chip8
-            .exec_opcode(0xFF65, false, &mut 0)
+            .exec_opcode(0xFF65)
             .expect("Store registers V0 through Vx in memory starting at location I.");
 
         assert_eq!(chip8.registers.get_v_register(0x0), 0x000);
@@ -870,4 +967,538 @@ mod tests {
 
         assert_eq!(chip8.registers.get_pc(), 0x0202);
     }
+
+    #[test]
+    fn shr_vx_shift_uses_vy() {
+        let mut chip8: VM = VM::new(Quirks::COSMAC_VIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_v_register(0, 0x10);
+        chip8.registers.set_v_register(1, 0x03);
+        chip8.exec_opcode(0x8016).expect("Set V0 = V1 SHR 1");
+
+        assert_eq!(chip8.registers.v_0, 0x01);
+        assert_eq!(chip8.registers.v_f, 1);
+    }
+
+    #[test]
+    fn shl_vx_shift_in_place() {
+        let mut chip8: VM = VM::new(Quirks::CHIP48);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_v_register(0, 0x81);
+        chip8.registers.set_v_register(1, 0x01);
+        chip8.exec_opcode(0x801E).expect("Set V0 = V0 SHL 1");
+
+        assert_eq!(chip8.registers.v_0, 0x02);
+        assert_eq!(chip8.registers.v_f, 1);
+    }
+
+    #[test]
+    fn or_vx_vy_vf_reset() {
+        let mut chip8: VM = VM::new(Quirks::COSMAC_VIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_vf();
+        chip8.exec_opcode(0x8011).expect("Set V0 = V0 OR V1");
+        assert_eq!(chip8.registers.v_f, 0);
+
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_vf();
+        chip8.exec_opcode(0x8011).expect("Set V0 = V0 OR V1");
+        assert_eq!(chip8.registers.v_f, 1);
+    }
+
+    #[test]
+    fn jp_vx_addr() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_v_register(0, 0x01);
+        chip8.registers.set_v_register(3, 0x02);
+        chip8.exec_opcode(0xB300).expect("Set PC to V3 + 300");
+
+        assert_eq!(chip8.registers.get_pc(), 0x0302);
+    }
+
+    #[test]
+    fn ld_i_vx_increments_i() {
+        let mut chip8: VM = VM::new(Quirks::COSMAC_VIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_i(0x300);
+        chip8
+            .exec_opcode(0xF355)
+            .expect("Store registers V0 through V3");
+        assert_eq!(chip8.registers.get_i(), 0x304);
+
+        let mut chip8: VM = VM::new(Quirks::CHIP48);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_i(0x300);
+        chip8
+            .exec_opcode(0xF355)
+            .expect("Store registers V0 through V3");
+        assert_eq!(chip8.registers.get_i(), 0x303);
+
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_i(0x300);
+        chip8
+            .exec_opcode(0xF365)
+            .expect("Read registers V0 through V3");
+        assert_eq!(chip8.registers.get_i(), 0x300);
+    }
+
+    #[test]
+    fn drw_vx_vy_nbytes_clip_and_wrap() {
+        let clipping = Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        };
+        let wrapping = Quirks {
+            clip_sprites: false,
+            ..Quirks::default()
+        };
+
+        for quirks in [clipping, wrapping] {
+            let mut chip8: VM = VM::new(quirks);
+            chip8.registers.set_pc(0x0200);
+            chip8.registers.set_i(0x000);
+            chip8.registers.set_v_register(0, 62);
+            chip8.registers.set_v_register(1, 30);
+            chip8
+                .exec_opcode(0xD015)
+                .expect("Draw 5 bytes sprite at the bottom right corner");
+
+            assert!(chip8.screen.is_pixel_set(62, 30).unwrap());
+            assert!(chip8.screen.is_pixel_set(62, 31).unwrap());
+            assert_eq!(
+                chip8.screen.is_pixel_set(0, 30).unwrap(),
+                !quirks.clip_sprites
+            );
+            assert_eq!(
+                chip8.screen.is_pixel_set(62, 0).unwrap(),
+                !quirks.clip_sprites
+            );
+        }
+    }
+
+    #[test]
+    fn drw_vx_vy_nbytes_display_wait() {
+        let mut chip8: VM = VM::new(Quirks::COSMAC_VIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
+        assert_eq!(chip8.registers.get_pc(), 0x0202);
+
+        chip8.exec_opcode(0xD015).expect("Wait for vertical blank");
+        assert_eq!(chip8.registers.get_pc(), 0x0202);
+
+        chip8.vblank();
+        chip8.exec_opcode(0xD015).expect("Draw 5 bytes sprite");
+        assert_eq!(chip8.registers.get_pc(), 0x0204);
+    }
+
+    #[test]
+    fn high_low() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.exec_opcode(0x00FF).expect("Enable high resolution");
+        assert_eq!(chip8.screen_width(), 128);
+        assert_eq!(chip8.screen_height(), 64);
+        assert!(!chip8.screen.is_pixel_set(127, 63).unwrap());
+
+        chip8.exec_opcode(0x00FE).expect("Disable high resolution");
+        assert_eq!(chip8.screen_width(), 64);
+        assert_eq!(chip8.screen_height(), 32);
+        assert_eq!(chip8.registers.get_pc(), 0x0204);
+    }
+
+    #[test]
+    fn drw_vx_vy_16x16() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.exec_opcode(0x00FF).expect("High res");
+        for row in 0..16 {
+            chip8
+                .memory
+                .set(0x300 + row * 2, 0x80)
+                .expect("Store value");
+            chip8
+                .memory
+                .set(0x300 + row * 2 + 1, 0x01)
+                .expect("Store value");
+        }
+        chip8.registers.set_i(0x300);
+        chip8.registers.set_v_register(0, 100);
+        chip8.registers.set_v_register(1, 40);
+        chip8.exec_opcode(0xD010).expect("Draw 16x16 sprite");
+
+        assert!(chip8.screen.is_pixel_set(100, 40).unwrap());
+        assert!(chip8.screen.is_pixel_set(115, 55).unwrap());
+        assert!(!chip8.screen.is_pixel_set(101, 40).unwrap());
+        assert!(!chip8.screen.is_pixel_set(100, 56).unwrap());
+        assert_eq!(chip8.registers.get_v_register(0xF), 0);
+    }
+
+    #[test]
+    fn drw_vx_vy_0_profiles() {
+        for (quirks, width) in [
+            (Quirks::COSMAC_VIP, 0),
+            (Quirks::CHIP48, 0),
+            (Quirks::SUPER_CHIP, 8),
+            (Quirks::XO_CHIP, 16),
+        ] {
+            let mut chip8: VM = VM::new(quirks);
+            chip8.registers.set_pc(0x0200);
+            for row in 0..16 {
+                chip8
+                    .memory
+                    .set(0x300 + row * 2, 0xFF)
+                    .expect("Store value");
+                chip8
+                    .memory
+                    .set(0x300 + row * 2 + 1, 0xFF)
+                    .expect("Store value");
+            }
+            chip8.registers.set_i(0x300);
+            chip8.exec_opcode(0xD000).expect("Draw sprite");
+
+            let lit = (0..64)
+                .flat_map(|x| (0..32).map(move |y| (x, y)))
+                .filter(|(x, y)| chip8.screen.is_pixel_set(*x, *y).unwrap())
+                .count();
+            assert_eq!(lit, width * 16);
+            assert_eq!(chip8.screen.is_pixel_set(7, 15).unwrap(), width != 0);
+            assert_eq!(chip8.screen.is_pixel_set(8, 0).unwrap(), width == 16);
+            assert_eq!(chip8.registers.get_pc(), 0x0202);
+        }
+    }
+
+    #[test]
+    fn scd_scr_scl() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_i(0x000);
+        chip8
+            .exec_opcode(0xD011)
+            .expect("Draw first row of '0' at (0, 0)");
+        assert!(chip8.screen.is_pixel_set(0, 0).unwrap());
+
+        chip8.exec_opcode(0x00C3).expect("Scroll down 3");
+        assert!(!chip8.screen.is_pixel_set(0, 0).unwrap());
+        assert!(chip8.screen.is_pixel_set(0, 3).unwrap());
+
+        chip8.exec_opcode(0x00FB).expect("Scroll right");
+        assert!(!chip8.screen.is_pixel_set(0, 3).unwrap());
+        assert!(chip8.screen.is_pixel_set(4, 3).unwrap());
+
+        chip8.exec_opcode(0x00FC).expect("Scroll left");
+        assert!(chip8.screen.is_pixel_set(0, 3).unwrap());
+        assert!(!chip8.screen.is_pixel_set(4, 3).unwrap());
+    }
+
+    #[test]
+    fn exit() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        let signal = chip8.exec_opcode(0x00FD).expect("Exit");
+        assert!(matches!(signal, crate::Signal::Exit));
+    }
+
+    #[test]
+    fn ld_hf_vx() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_v_register(0, 0x02);
+        chip8
+            .exec_opcode(0xF030)
+            .expect("Set I = location of big sprite for digit Vx");
+        assert_eq!(chip8.registers.get_i(), 0x64);
+        assert_eq!(chip8.memory.get_ref(0x64)[0], 0x3E);
+    }
+
+    #[test]
+    fn ld_r_vx_ld_vx_r() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_v_register(0, 0x0A);
+        chip8.registers.set_v_register(1, 0x0B);
+        chip8.exec_opcode(0xF175).expect("Store V0-V1 in RPL flags");
+        chip8.registers.set_v_register(0, 0x00);
+        chip8.registers.set_v_register(1, 0x00);
+        chip8
+            .exec_opcode(0xF185)
+            .expect("Read V0-V1 from RPL flags");
+        assert_eq!(chip8.registers.get_v_register(0), 0x0A);
+        assert_eq!(chip8.registers.get_v_register(1), 0x0B);
+
+        assert!(chip8.exec_opcode(0xF875).is_err());
+    }
+
+    #[test]
+    fn ld_i_long() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8.load_program(&[0xF0, 0x00, 0xE1, 0x23]).unwrap();
+        chip8.exec_next_opcode().expect("Set I = 0xE123");
+        assert_eq!(chip8.registers.get_i(), 0xE123);
+        assert_eq!(chip8.registers.get_pc(), 0x0204);
+    }
+
+    #[test]
+    fn skip_ld_i_long() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8
+            .load_program(&[0x30, 0x00, 0xF0, 0x00, 0xE1, 0x23])
+            .unwrap();
+        chip8
+            .exec_next_opcode()
+            .expect("Skip the 4 bytes instruction");
+        assert_eq!(chip8.registers.get_pc(), 0x0206);
+    }
+
+    #[test]
+    fn ld_i_vx_vy_ld_vx_vy_i() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.registers.set_i(0x300);
+        chip8.registers.set_v_register(2, 0x0A);
+        chip8.registers.set_v_register(3, 0x0B);
+        chip8.registers.set_v_register(4, 0x0C);
+        chip8.exec_opcode(0x5242).expect("Store V2-V4 in memory");
+        assert_eq!(chip8.memory.get_ref(0x300)[..3], [0x0A, 0x0B, 0x0C]);
+        assert_eq!(chip8.registers.get_i(), 0x300);
+
+        chip8.exec_opcode(0x5733).expect("Read V7-V5 from memory");
+        assert_eq!(chip8.registers.get_v_register(7), 0x0A);
+        assert_eq!(chip8.registers.get_v_register(6), 0x0B);
+        assert_eq!(chip8.registers.get_v_register(5), 0x0C);
+        assert_eq!(chip8.registers.get_pc(), 0x0204);
+    }
+
+    #[test]
+    fn plane_drw() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8.registers.set_pc(0x0200);
+        chip8.memory.set(0x300, 0x80).expect("Store value");
+        chip8.memory.set(0x301, 0xC0).expect("Store value");
+        chip8.registers.set_i(0x300);
+
+        chip8.exec_opcode(0xF301).expect("Select both planes");
+        chip8
+            .exec_opcode(0xD011)
+            .expect("Draw 1 byte sprite on each plane");
+        assert_eq!(chip8.screen_pixel_planes(0, 0).unwrap(), 0b11);
+        assert_eq!(chip8.screen_pixel_planes(1, 0).unwrap(), 0b10);
+
+        chip8.exec_opcode(0xF201).expect("Select second plane");
+        chip8.exec_opcode(0x00E0).expect("Clear");
+        assert_eq!(chip8.screen_pixel_planes(0, 0).unwrap(), 0b01);
+        assert_eq!(chip8.screen_pixel_planes(1, 0).unwrap(), 0b00);
+    }
+
+    #[test]
+    fn ld_audio_i_ld_pitch_vx() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8.registers.set_pc(0x0200);
+        for offset in 0..16 {
+            chip8
+                .memory
+                .set(0x300 + offset, offset as u8)
+                .expect("Store value");
+        }
+        chip8.registers.set_i(0x300);
+        assert_eq!(chip8.audio_playback_rate(), 3520.0);
+        chip8.exec_opcode(0xF002).expect("Load audio pattern");
+        assert_eq!(chip8.audio_pattern()[15], 15);
+
+        assert_eq!(chip8.audio_playback_rate(), 4000.0);
+        chip8.registers.set_v_register(0, 112);
+        chip8.exec_opcode(0xF03A).expect("Set pitch");
+        assert_eq!(chip8.audio_playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn load_program_64k() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8
+            .load_program(&vec![0xAB; 0x10000 - 0x201])
+            .expect("Load a program using the XO-CHIP memory");
+        assert_eq!(chip8.memory.get(0xFFFE).unwrap(), 0xAB);
+        assert!(chip8.load_program(&vec![0xAB; 0x10000]).is_err());
+    }
+
+    #[test]
+    fn save_load_state() {
+        let mut chip8: VM = VM::new(Quirks::SUPER_CHIP);
+        chip8.load_program(&[0x00, 0xFF]).expect("Load program");
+        chip8.exec_next_opcode().expect("High resolution");
+        chip8.registers.set_v_register(3, 0x10);
+        chip8.registers.set_i(0x0300);
+        chip8.registers.set_dt(42);
+        chip8.stack.set_at(0, 0x0204).expect("Push stack");
+        chip8.memory.set(0x300, 0x80).expect("Store value");
+        chip8.exec_opcode(0xD001).expect("Draw");
+        chip8.exec_opcode(0xF00A).expect("Wait key");
+        let state = chip8.save_state();
+
+        let mut restored: VM = VM::new(Quirks::default());
+        restored.load_state(&state).expect("Load state");
+        assert_eq!(restored.quirks(), &Quirks::SUPER_CHIP);
+        assert_eq!(restored.registers.get_v_register(3), 0x10);
+        assert_eq!(restored.registers.get_i(), 0x0300);
+        assert_eq!(restored.registers.get_dt(), 42);
+        assert_eq!(restored.registers.get_pc(), chip8.registers.get_pc());
+        assert_eq!(restored.stack.get_at(0).unwrap(), 0x0204);
+        assert_eq!(restored.memory.get(0x300).unwrap(), 0x80);
+        assert_eq!(restored.screen_width(), 128);
+        assert!(restored.screen_is_pixel_set(0, 0).unwrap());
+        assert_eq!(restored.waiting_keyup, chip8.waiting_keyup);
+        assert_eq!(restored.save_state(), state);
+    }
+
+    #[test]
+    fn seeded_random_numbers() {
+        use crate::RngAlgorithm;
+
+        for algorithm in [RngAlgorithm::Xorshift, RngAlgorithm::CosmacVip] {
+            let random_bytes = |chip8: &mut VM| {
+                (0..16)
+                    .map(|_| {
+                        chip8.exec_opcode(0xC0FF).expect("Random byte");
+                        chip8.registers.get_v_register(0)
+                    })
+                    .collect::<Vec<u8>>()
+            };
+            let mut chip8: VM = VM::new(Quirks::default());
+            chip8.set_rng(algorithm, 1234);
+            let first = random_bytes(&mut chip8);
+            let state = chip8.save_state();
+            let next = random_bytes(&mut chip8);
+
+            let mut other: VM = VM::new(Quirks::default());
+            other.set_rng(algorithm, 1234);
+            assert_eq!(random_bytes(&mut other), first);
+            other.set_rng(algorithm, 4321);
+            assert_ne!(random_bytes(&mut other), first);
+
+            // The generator is part of the save state.
+            other.load_state(&state).expect("Load state");
+            assert_eq!(random_bytes(&mut other), next);
+        }
+    }
+
+    #[test]
+    fn load_invalid_state() {
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        let mut state = chip8.save_state();
+
+        assert!(chip8.load_state(&state[..state.len() - 1]).is_err());
+        assert!(chip8.load_state(b"not a state").is_err());
+        state[4] = 99;
+        assert!(matches!(
+            chip8.load_state(&state),
+            Err(crate::errors::VMError::UnsupportedStateVersion(99))
+        ));
+        assert_eq!(chip8.quirks(), &Quirks::XO_CHIP);
+    }
+
+    #[test]
+    fn load_tampered_state() {
+        use crate::{errors::VMError, state::StateWriter};
+
+        let mut chip8: VM = VM::new(Quirks::XO_CHIP);
+        chip8.exec_opcode(0xF00A).expect("Wait key");
+        let state = chip8.save_state();
+        chip8.load_state(&state).expect("Load state");
+
+        // The key waited for is the last byte, 0 to F.
+        let mut tampered = state.clone();
+        *tampered.last_mut().unwrap() = 0x10;
+        assert!(matches!(
+            chip8.load_state(&tampered),
+            Err(VMError::InvalidState)
+        ));
+
+        // The screen follows the keyboard: the resolution, the selected planes and the pixels,
+        // one bit per plane.
+        let mut before_screen = StateWriter::new();
+        chip8.memory.save_state(&mut before_screen);
+        chip8.registers.save_state(&mut before_screen);
+        chip8.stack.save_state(&mut before_screen);
+        chip8.keyboard.save_state(&mut before_screen);
+        let screen = before_screen.into_bytes().len();
+        let mut tampered = state.clone();
+        tampered[screen + 1] = 0x04;
+        assert!(matches!(
+            chip8.load_state(&tampered),
+            Err(VMError::InvalidState)
+        ));
+        let mut tampered = state.clone();
+        tampered[screen + 2 + 100] = 0x03;
+        chip8
+            .load_state(&tampered)
+            .expect("Pixel set on both planes");
+        tampered[screen + 2 + 100] = 0x04;
+        assert!(matches!(
+            chip8.load_state(&tampered),
+            Err(VMError::InvalidState)
+        ));
+        assert_eq!(chip8.save_state()[screen + 2 + 100], 0x03);
+    }
+
+    #[test]
+    fn opcode_mnemonic() {
+        assert_eq!(VM::opcode_mnemonic(0x6310).unwrap(), "LD V3, 0x10");
+        assert_eq!(VM::opcode_mnemonic(0xD125).unwrap(), "DRW V1, V2, 5");
+        assert_eq!(VM::opcode_mnemonic(0x1234).unwrap(), "JP 0x234");
+        assert_eq!(VM::opcode_mnemonic(0xF201).unwrap(), "PLANE 2");
+        assert_eq!(VM::opcode_mnemonic(0xF355).unwrap(), "LD [I], V3");
+        assert!(VM::opcode_mnemonic(0x5001).is_none());
+    }
+
+    #[test]
+    fn run_frame() {
+        let mut chip8: VM = VM::new(Quirks::default());
+        // 0x200: ADD V0, 1, 0x202: DRW V1, V1, 1, 0x204: SE V0, 5, 0x206: JP 0x200, 0x208: EXIT
+        let program = [0x70, 0x01, 0xD1, 0x11, 0x30, 0x05, 0x12, 0x00, 0x00, 0xFD];
+        chip8.load_program(&program).expect("Load program");
+        let frame = chip8.run_frame(3).unwrap();
+        assert_eq!(frame.cycles, 3);
+        assert!(frame.draw_screen);
+        assert!(!frame.exit);
+
+        let frame = chip8.run_frame(1).unwrap();
+        assert!(!frame.draw_screen);
+
+        // With the display wait quirk the program draws once per frame.
+        let frame = chip8.run_frame(100).unwrap();
+        assert_eq!(frame.cycles, 100);
+        assert_eq!(chip8.registers_v(0), 3);
+        chip8.run_frame(100).unwrap();
+        chip8.run_frame(100).unwrap();
+
+        let frame = chip8.run_frame(100).unwrap();
+        assert_eq!(frame.cycles, 3);
+        assert!(frame.exit);
+        assert_eq!(chip8.registers_v(0), 5);
+    }
+
+    #[test]
+    fn timers_tick_once_per_frame() {
+        let mut chip8: VM = VM::new(Quirks::default());
+        // 0x200: LD V0, 3, 0x202: LD DT, V0, 0x204: LD ST, V0, 0x206: JP 0x206
+        let program = [0x60, 0x03, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06];
+        chip8.load_program(&program).expect("Load program");
+
+        chip8.run_frame(3).unwrap();
+        assert_eq!((chip8.registers_dt(), chip8.registers_st()), (3, 3));
+        // Executing instructions does not count the timers down.
+        for _ in 0..100 {
+            chip8.exec_next_opcode().unwrap();
+        }
+        assert_eq!(chip8.registers_dt(), 3);
+
+        chip8.run_frame(1000).unwrap();
+        assert_eq!((chip8.registers_dt(), chip8.registers_st()), (2, 2));
+        chip8.tick_timers();
+        chip8.tick_timers();
+        chip8.tick_timers();
+        assert_eq!((chip8.registers_dt(), chip8.registers_st()), (0, 0));
+    }
 }
@@ -0,0 +1,94 @@
+//! Golden image tests of the bundled ROMs, see `tests/golden/roms.txt`. Run with
+//! `CHIP8_UPDATE_GOLDENS=1` to write the current screens as the new golden images.
+
+use std::{env, fs, path::Path};
+
+use chip8_vm::{screen_pbm, Headless, KeyScript, Quirks, RngAlgorithm, RunLength, VM};
+
+const UPDATE_GOLDENS_VAR: &str = "CHIP8_UPDATE_GOLDENS";
+/// Seed of the random number generator, fixed so the random ROMs give the same screens.
+const RNG_SEED: u64 = 0xC8;
+
+fn run_rom(rom: &[u8], frames: u64, keys: Option<KeyScript>) -> String {
+    let mut chip8 = VM::new(Quirks::default());
+    chip8.set_rng(RngAlgorithm::Xorshift, RNG_SEED);
+    chip8.load_program(rom).expect("Load program");
+    let mut headless = Headless::new();
+    if let Some(keys) = keys {
+        headless.set_key_script(keys);
+    }
+    headless
+        .run(&mut chip8, RunLength::Frames(frames))
+        .expect("Run program");
+    screen_pbm(&chip8).expect("Screen image")
+}
+
+/// Describe the first different row of two PBM images.
+fn first_difference(expected: &str, actual: &str) -> String {
+    expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual)
+        .map(|(row, (expected, actual))| {
+            format!(
+                "line {}:\n  expected {}\n    actual {}",
+                row + 1,
+                expected,
+                actual
+            )
+        })
+        .unwrap_or_else(|| "different number of lines".to_string())
+}
+
+#[test]
+fn bundled_roms_match_golden_images() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let golden_dir = root.join("tests/golden");
+    let update = env::var_os(UPDATE_GOLDENS_VAR).is_some();
+    let manifest = fs::read_to_string(golden_dir.join("roms.txt")).expect("Read manifest");
+
+    let mut failures = Vec::new();
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        let (name, frames, keys) = match fields[..] {
+            [name, frames] => (name, frames, None),
+            [name, frames, keys] => (name, frames, Some(keys)),
+            _ => panic!("invalid manifest entry '{}'", line),
+        };
+        let frames = frames.parse::<u64>().expect("Frame count");
+        let keys = keys.map(|keys| {
+            fs::read_to_string(golden_dir.join(keys))
+                .expect("Read key script")
+                .parse::<KeyScript>()
+                .expect("Parse key script")
+        });
+        let rom = fs::read(root.join("../chip8-roms").join(name)).expect("Read ROM");
+
+        let actual = run_rom(&rom, frames, keys);
+        let golden = golden_dir.join(format!("{}.pbm", name));
+        if update {
+            fs::write(&golden, &actual).expect("Write golden image");
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{}: screen differs from the golden image, {}",
+                name,
+                first_difference(&expected, &actual)
+            )),
+            Err(e) => failures.push(format!("{}: {}: {}", name, golden.display(), e)),
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\n\nRun with {}=1 to update the golden images.",
+        failures.join("\n"),
+        UPDATE_GOLDENS_VAR
+    );
+}